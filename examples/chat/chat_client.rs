@@ -19,8 +19,8 @@ fn main() {
 
         socket.connect_to("127.0.0.1:8008").unwrap();
 
-        socket.on("recv_message", |_client, (_addr, bytes)| {
-            let msg = String::from_utf8(bytes.to_vec()).unwrap();
+        socket.on("recv_message", |_client, ctx| {
+            let msg = String::from_utf8(ctx.payload.to_vec()).unwrap();
             println!("{}", msg);
         });
     }
@@ -1,5 +1,3 @@
-use std::{collections::HashMap, net::SocketAddr};
-
 use nautilus_sockets::prelude::*;
 
 fn main() {
@@ -17,94 +15,48 @@ fn main() {
     }
 }
 
-#[derive(Default)]
-pub struct Chatters {
-    names: HashMap<ConnectionId, String>,
-}
-
-impl Persistent for Chatters {}
+/// The name a chatter joined with, attached to their connection so it is dropped when they leave
+pub struct ChatterName(String);
 
 pub struct ChattersPlugin;
 
 impl SocketPlugin<'_, NautServer> for ChattersPlugin {
     fn register(&self, socket: &mut NautSocket<'_, NautServer>) {
-        socket.init_persistent::<Chatters>();
-
         socket.on("new_messenger", create_new_chatter);
         socket.on("send_message", on_send_message);
-
-        socket.on_poll(remove_chatters_on_disconnect);
     }
 }
 
-fn remove_chatters_on_disconnect(socket: &mut NautSocket<'_, NautServer>) {
-    for event in socket.server().iter_server_events() {
-        match event {
-            ServerEvent::OnClientTimeout(id) | ServerEvent::OnClientDisconnected(id) => {
-                let Some(chatters) = socket.get_persistent::<Chatters>() else {
-                    return;
-                };
-
-                let Ok(mut chatters) = chatters.write() else {
-                    return;
-                };
-
-                chatters.names.remove(id);
-            }
-            _ => {}
-        }
-    }
-}
-
-fn on_send_message(socket: &mut NautSocket<'_, NautServer>, (addr, packet): (SocketAddr, &[u8])) {
-    let Some(id) = socket.server().get_client_id(&addr) else {
+fn on_send_message(socket: &mut NautSocket<'_, NautServer>, ctx: EventContext) {
+    let Some(ChatterName(name)) = ctx
+        .connection_id
+        .and_then(|id| socket.server().connection_data::<ChatterName>(&id))
+    else {
         return;
     };
 
-    let name = {
-        let Some(chatters) = socket.get_persistent::<Chatters>() else {
-            return;
-        };
-        let Ok(chatters) = chatters.read() else {
-            return;
-        };
-
-        chatters.names.get(id).cloned()
-    };
-
-    let Some(name) = name else {
-        return;
-    };
-
-    let msg = String::from_utf8(packet.to_vec()).unwrap();
+    let msg = String::from_utf8(ctx.payload.to_vec()).unwrap();
     let string = format!("{}: {}", name, msg);
-    let _ = socket.broadcast("recv_message", string.as_bytes(), PacketDelivery::Reliable);
+    socket.broadcast("recv_message", string.as_bytes(), PacketDelivery::Reliable);
 }
 
-fn create_new_chatter(
-    socket: &mut NautSocket<'_, NautServer>,
-    (addr, packet): (SocketAddr, &[u8]),
-) {
-    let Some(id) = socket.server().get_client_id(&addr) else {
+fn create_new_chatter(socket: &mut NautSocket<'_, NautServer>, ctx: EventContext) {
+    let Some(id) = ctx.connection_id else {
         return;
     };
 
-    let Some(chatters) = socket.get_persistent::<Chatters>() else {
-        return;
-    };
-
-    let name = String::from_utf8(packet.to_vec()).unwrap();
+    let name = String::from_utf8(ctx.payload.to_vec()).unwrap();
+    if socket
+        .server_mut()
+        .insert_connection_data(&id, ChatterName(name.clone()))
+        .is_err()
     {
-        let Ok(mut chatters) = chatters.write() else {
-            return;
-        };
-
-        chatters.names.insert(*id, name.clone());
+        return;
     }
 
     let join_msg = format!("Welcome {name}");
 
-    let _ = socket.broadcast(
+    socket.broadcast(
         "recv_message",
         join_msg.as_bytes(),
         PacketDelivery::Reliable,
@@ -7,9 +7,9 @@ fn main() {
         NautSocket::<NautServer>::new("127.0.0.1:8008", ServerConfig::default()).unwrap();
     socket.register_plugin(LoggingPlugin);
 
-    socket.on("join", move |socket, (addr, _packet)| {
-        let client = socket.server().get_client_id(&addr);
-        let _ = socket.send("hello", &[], PacketDelivery::Reliable, *client.unwrap());
+    // Replies go back to whoever sent the event, without looking up their connection
+    socket.on("join", move |socket, ctx| {
+        let _ = ctx.reply(socket, "hello", &[], PacketDelivery::Reliable);
     });
 
     loop {
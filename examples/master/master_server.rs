@@ -0,0 +1,24 @@
+use std::{thread::sleep, time::Duration};
+
+use nautilus_sockets::prelude::*;
+
+fn main() {
+    let mut master = NautMasterServer::new(
+        "127.0.0.1:8009",
+        ServerConfig {
+            server_name: String::from("Nautilus Master Server"),
+            // Keeps the registered servers listed across restarts
+            snapshot: Some(SnapshotConfig::new("master_server.snapshot")),
+            ..Default::default()
+        },
+        MasterServerPlugin::default(),
+    )
+    .unwrap();
+
+    master.socket_mut().register_plugin(LoggingPlugin);
+
+    loop {
+        sleep(Duration::from_millis(1));
+        master.run();
+    }
+}
@@ -0,0 +1,42 @@
+use std::{thread::sleep, time::Duration};
+
+use anyhow::anyhow;
+use nautilus_sockets::prelude::*;
+
+fn main() {
+    let mut server =
+        NautSocket::<NautServer>::new("127.0.0.1:8012", ServerConfig::default()).unwrap();
+    server.register_plugin(LoggingPlugin);
+
+    // The returned bytes are sent back to whoever asked, an error is sent back instead of them
+    server.on_request("get_inventory", |_server, ctx| {
+        let Some(id) = ctx.connection_id else {
+            return Err(anyhow!("{} has no inventory", ctx.addr));
+        };
+
+        Ok(format!("sword, shield and a potion for {}", id.to_bits()).into_bytes())
+    });
+
+    let mut client = NautSocket::<NautClient>::new("127.0.0.1:0").unwrap();
+    client.connect_to("127.0.0.1:8012").unwrap();
+    client
+        .request(
+            "get_inventory",
+            &[],
+            Duration::from_secs(2),
+            |_client, response| match response {
+                Ok(inventory) => println!("{}", String::from_utf8_lossy(&inventory)),
+                Err(e) => println!("{e}"),
+            },
+        )
+        .unwrap();
+
+    loop {
+        server.poll();
+        server.run_events();
+        client.poll();
+        client.run_events();
+
+        sleep(Duration::from_millis(10));
+    }
+}
@@ -0,0 +1,72 @@
+use std::{thread::sleep, time::Duration};
+
+use nautilus_sockets::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Sent by a client when it joins
+#[derive(Serialize, Deserialize, NautMessage, Debug)]
+#[naut(event = "join")]
+struct Join {
+    name: String,
+}
+
+/// Sent by a client to move, only the latest position matters
+#[derive(Serialize, Deserialize, NautMessage, Debug)]
+#[naut(event = "move", delivery = UnreliableSequenced)]
+struct Move {
+    x: f32,
+    y: f32,
+}
+
+/// Sent by the server to every client when anyone chats or joins
+#[derive(Serialize, Deserialize, NautMessage, Debug)]
+#[naut(event = "chat")]
+enum Chat {
+    Joined(String),
+    Said { name: String, message: String },
+}
+
+fn main() {
+    let mut server =
+        NautSocket::<NautServer>::new("127.0.0.1:8011", ServerConfig::default()).unwrap();
+
+    // The event name and delivery come from the message, so they cannot be mistyped
+    server.on_msg(|server, _ctx, join: Join| {
+        let _ = server.broadcast_msg(&Chat::Joined(join.name));
+    });
+
+    server.on_msg(|server, ctx, position: Move| {
+        println!("{} moved to {}, {}", ctx.addr, position.x, position.y);
+
+        let said = Chat::Said {
+            name: ctx.addr.to_string(),
+            message: String::from("I moved"),
+        };
+        let _ = server.broadcast_msg(&said);
+    });
+
+    let mut client = NautSocket::<NautClient>::new("127.0.0.1:0").unwrap();
+    client.on_msg(|client, _ctx, chat: Chat| {
+        println!("{chat:?}");
+
+        if let Chat::Joined(_) = chat {
+            let _ = client.send_msg(&Move { x: 4.0, y: 2.0 });
+        }
+    });
+
+    client.connect_to("127.0.0.1:8011").unwrap();
+    client
+        .send_msg(&Join {
+            name: String::from("Nautilus"),
+        })
+        .unwrap();
+
+    loop {
+        server.poll();
+        server.run_events();
+        client.poll();
+        client.run_events();
+
+        sleep(Duration::from_millis(10));
+    }
+}
@@ -0,0 +1,56 @@
+use std::{thread::sleep, time::Duration};
+
+use nautilus_sockets::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Move {
+    x: f32,
+    y: f32,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Moved {
+    client: u32,
+    x: f32,
+    y: f32,
+}
+
+fn main() {
+    let mut server =
+        NautSocket::<NautServer>::new("127.0.0.1:8010", ServerConfig::default()).unwrap();
+    server.register_plugin(LoggingPlugin);
+
+    // The packet is decoded before the callback runs, a malformed packet becomes a socket event
+    server.on_typed("move", |server, ctx, position: Move| {
+        let Some(id) = ctx.connection_id else {
+            return;
+        };
+
+        let moved = Moved {
+            client: id.to_bits(),
+            x: position.x,
+            y: position.y,
+        };
+        let _ = server.broadcast_typed("moved", &moved, PacketDelivery::Reliable);
+    });
+
+    let mut client = NautSocket::<NautClient>::new("127.0.0.1:0").unwrap();
+    client.on_typed("moved", |_client, _ctx, moved: Moved| {
+        println!("{moved:?}");
+    });
+
+    client.connect_to("127.0.0.1:8010").unwrap();
+    client
+        .send_typed("move", &Move { x: 4.0, y: 2.0 }, PacketDelivery::Reliable)
+        .unwrap();
+
+    loop {
+        server.poll();
+        server.run_events();
+        client.poll();
+        client.run_events();
+
+        sleep(Duration::from_millis(10));
+    }
+}
@@ -0,0 +1,294 @@
+use std::fmt::Write;
+
+use crate::schema::{pascal_case, FieldType, Schema};
+
+/// Packet framing matching the header written by the socket, so a C# client can talk to a
+/// nautilus server without the socket itself
+const PACKET: &str = r#"    public enum PacketDelivery : ushort
+    {
+        Unreliable = 0,
+        UnreliableSequenced = 1,
+        Reliable = 2,
+        ReliableSequenced = 3,
+        Ack = 10,
+    }
+
+    public static class Packet
+    {
+        public const int HeaderSize = 22;
+
+        /// Writes a packet in the layout of the socket, the sequence is only read for sequenced
+        /// deliveries and the ack number only for reliable deliveries
+        public static byte[] Write(string eventName, byte[] payload, PacketDelivery delivery, uint seq, uint ack, ulong token)
+        {
+            var eventBytes = Encoding.UTF8.GetBytes(eventName);
+            var pad = (4 - eventBytes.Length % 4) % 4;
+            using var stream = new MemoryStream();
+            using var writer = new BinaryWriter(stream);
+            writer.Write((ushort)delivery);
+            writer.Write(seq);
+            writer.Write(ack);
+            writer.Write(token);
+            writer.Write((uint)eventBytes.Length);
+            writer.Write(eventBytes);
+            writer.Write(new byte[pad]);
+            writer.Write(payload);
+            return stream.ToArray();
+        }
+
+        /// Writes the acknowledgement of a reliable packet
+        public static byte[] WriteAck(uint ack)
+        {
+            var packet = new byte[6];
+            BinaryPrimitives.WriteUInt16LittleEndian(packet, (ushort)PacketDelivery.Ack);
+            BinaryPrimitives.WriteUInt32LittleEndian(packet.AsSpan(2), ack);
+            return packet;
+        }
+
+        /// Reads a packet in the layout of the socket, returning false if it is too short
+        public static bool Read(byte[] packet, out PacketDelivery delivery, out uint ack, out string eventName, out byte[] payload)
+        {
+            delivery = default;
+            ack = 0;
+            eventName = "";
+            payload = Array.Empty<byte>();
+            if (packet.Length < HeaderSize)
+            {
+                return false;
+            }
+
+            delivery = (PacketDelivery)BinaryPrimitives.ReadUInt16LittleEndian(packet);
+            ack = BinaryPrimitives.ReadUInt32LittleEndian(packet.AsSpan(6));
+            var eventLen = (int)BinaryPrimitives.ReadUInt32LittleEndian(packet.AsSpan(18));
+            var payloadOffset = HeaderSize + eventLen + (4 - eventLen % 4) % 4;
+            if (packet.Length < payloadOffset)
+            {
+                return false;
+            }
+
+            eventName = Encoding.UTF8.GetString(packet, HeaderSize, eventLen);
+            payload = packet[payloadOffset..];
+            return true;
+        }
+    }
+
+    static class ProtocolValue
+    {
+        public static void WriteString(BinaryWriter writer, string value)
+        {
+            WriteBytes(writer, Encoding.UTF8.GetBytes(value));
+        }
+
+        public static string ReadString(BinaryReader reader)
+        {
+            return Encoding.UTF8.GetString(ReadBytes(reader));
+        }
+
+        public static void WriteBytes(BinaryWriter writer, byte[] value)
+        {
+            writer.Write((uint)value.Length);
+            writer.Write(value);
+        }
+
+        public static byte[] ReadBytes(BinaryReader reader)
+        {
+            var len = ReadLength(reader);
+            var bytes = reader.ReadBytes(len);
+            if (bytes.Length < len)
+                throw new EndOfStreamException();
+            return bytes;
+        }
+
+        // Every byte, character or list item takes at least a byte, so a length past what is
+        // left in the payload can only come from a malformed packet
+        public static int ReadLength(BinaryReader reader)
+        {
+            var len = reader.ReadUInt32();
+            if (len > reader.BaseStream.Length - reader.BaseStream.Position)
+                throw new EndOfStreamException();
+            return (int)len;
+        }
+    }
+"#;
+
+fn csharp_type(ty: &FieldType) -> String {
+    match ty {
+        FieldType::Bool => "bool".into(),
+        FieldType::U8 => "byte".into(),
+        FieldType::U16 => "ushort".into(),
+        FieldType::U32 => "uint".into(),
+        FieldType::U64 => "ulong".into(),
+        FieldType::I8 => "sbyte".into(),
+        FieldType::I16 => "short".into(),
+        FieldType::I32 => "int".into(),
+        FieldType::I64 => "long".into(),
+        FieldType::F32 => "float".into(),
+        FieldType::F64 => "double".into(),
+        FieldType::String => "string".into(),
+        FieldType::Bytes => "byte[]".into(),
+        FieldType::List(inner) => format!("List<{}>", csharp_type(inner)),
+    }
+}
+
+fn default_value(ty: &FieldType) -> Option<String> {
+    match ty {
+        FieldType::String => Some("\"\"".into()),
+        FieldType::Bytes => Some("Array.Empty<byte>()".into()),
+        FieldType::List(inner) => Some(format!("new List<{}>()", csharp_type(inner))),
+        _ => None,
+    }
+}
+
+/// Writes the statements encoding the value, nesting a loop for every list
+fn write_value(out: &mut String, ty: &FieldType, value: &str, indent: &str, depth: usize) {
+    match ty {
+        FieldType::String => {
+            let _ = writeln!(out, "{indent}ProtocolValue.WriteString(writer, {value});");
+        }
+        FieldType::Bytes => {
+            let _ = writeln!(out, "{indent}ProtocolValue.WriteBytes(writer, {value});");
+        }
+        FieldType::List(inner) => {
+            let item = format!("item{depth}");
+            let _ = writeln!(out, "{indent}writer.Write((uint){value}.Count);");
+            let _ = writeln!(out, "{indent}foreach (var {item} in {value})");
+            let _ = writeln!(out, "{indent}{{");
+            write_value(out, inner, &item, &format!("{indent}    "), depth + 1);
+            let _ = writeln!(out, "{indent}}}");
+        }
+        _ => {
+            let _ = writeln!(out, "{indent}writer.Write({value});");
+        }
+    }
+}
+
+/// Writes the statements decoding a value into the target, nesting a loop for every list
+fn read_value(out: &mut String, ty: &FieldType, target: &str, indent: &str, depth: usize) {
+    let read = match ty {
+        FieldType::Bool => "reader.ReadBoolean()",
+        FieldType::U8 => "reader.ReadByte()",
+        FieldType::U16 => "reader.ReadUInt16()",
+        FieldType::U32 => "reader.ReadUInt32()",
+        FieldType::U64 => "reader.ReadUInt64()",
+        FieldType::I8 => "reader.ReadSByte()",
+        FieldType::I16 => "reader.ReadInt16()",
+        FieldType::I32 => "reader.ReadInt32()",
+        FieldType::I64 => "reader.ReadInt64()",
+        FieldType::F32 => "reader.ReadSingle()",
+        FieldType::F64 => "reader.ReadDouble()",
+        FieldType::String => "ProtocolValue.ReadString(reader)",
+        FieldType::Bytes => "ProtocolValue.ReadBytes(reader)",
+        FieldType::List(inner) => {
+            // Scoped so the lengths of sibling lists do not clash
+            let (len, item) = (format!("len{depth}"), format!("item{depth}"));
+            let block_indent = format!("{indent}    ");
+            let inner_indent = format!("{block_indent}    ");
+            let _ = writeln!(out, "{indent}{{");
+            let _ = writeln!(
+                out,
+                "{block_indent}var {len} = ProtocolValue.ReadLength(reader);"
+            );
+            let _ = writeln!(
+                out,
+                "{block_indent}{target} = new List<{}>({len});",
+                csharp_type(inner)
+            );
+            let _ = writeln!(
+                out,
+                "{block_indent}for (var i{depth} = 0; i{depth} < {len}; i{depth}++)"
+            );
+            let _ = writeln!(out, "{block_indent}{{");
+            let _ = writeln!(out, "{inner_indent}{} {item};", csharp_type(inner));
+            read_value(out, inner, &item, &inner_indent, depth + 1);
+            let _ = writeln!(out, "{inner_indent}{target}.Add({item});");
+            let _ = writeln!(out, "{block_indent}}}");
+            let _ = writeln!(out, "{indent}}}");
+            return;
+        }
+    };
+
+    let _ = writeln!(out, "{indent}{target} = {read};");
+}
+
+/// Generates a class with an encoder and decoder for every event of the schema, along with the
+/// packet framing of the socket
+pub fn generate(schema: &Schema, namespace: &str) -> String {
+    let mut out = String::new();
+    out.push_str("// Generated by nautilus-sockets-codegen, do not edit\n");
+    out.push_str("using System;\n");
+    out.push_str("using System.Buffers.Binary;\n");
+    out.push_str("using System.Collections.Generic;\n");
+    out.push_str("using System.IO;\n");
+    out.push_str("using System.Text;\n\n");
+    let _ = writeln!(out, "namespace {namespace}");
+    out.push_str("{\n");
+    out.push_str(PACKET);
+
+    for event in &schema.events {
+        let name = pascal_case(&event.name);
+        out.push('\n');
+        let _ = writeln!(out, "    public sealed class {name}");
+        out.push_str("    {\n");
+        let _ = writeln!(
+            out,
+            "        public const string Event = \"{}\";",
+            event.name
+        );
+        let _ = writeln!(
+            out,
+            "        public const PacketDelivery Delivery = PacketDelivery.{};",
+            event.delivery.variant()
+        );
+        out.push('\n');
+
+        for field in &event.fields {
+            let ty = csharp_type(&field.ty);
+            let field_name = pascal_case(&field.name);
+            match default_value(&field.ty) {
+                Some(default) => {
+                    let _ = writeln!(out, "        public {ty} {field_name} = {default};");
+                }
+                None => {
+                    let _ = writeln!(out, "        public {ty} {field_name};");
+                }
+            }
+        }
+
+        if !event.fields.is_empty() {
+            out.push('\n');
+        }
+
+        out.push_str("        public byte[] Encode()\n");
+        out.push_str("        {\n");
+        out.push_str("            using var stream = new MemoryStream();\n");
+        out.push_str("            using var writer = new BinaryWriter(stream);\n");
+        for field in &event.fields {
+            write_value(
+                &mut out,
+                &field.ty,
+                &pascal_case(&field.name),
+                "            ",
+                0,
+            );
+        }
+        out.push_str("            return stream.ToArray();\n");
+        out.push_str("        }\n\n");
+
+        let _ = writeln!(out, "        public static {name} Decode(byte[] payload)");
+        out.push_str("        {\n");
+        out.push_str(
+            "            using var reader = new BinaryReader(new MemoryStream(payload));\n",
+        );
+        let _ = writeln!(out, "            var value = new {name}();");
+        for field in &event.fields {
+            let target = format!("value.{}", pascal_case(&field.name));
+            read_value(&mut out, &field.ty, &target, "            ", 0);
+        }
+        out.push_str("            return value;\n");
+        out.push_str("        }\n");
+        out.push_str("    }\n");
+    }
+
+    out.push_str("}\n");
+    out
+}
@@ -0,0 +1,233 @@
+use std::fmt::Write;
+
+use crate::schema::{pascal_case, FieldType, Schema};
+
+/// Packet framing matching the header written by the socket, so a Godot client can talk to a
+/// nautilus server without the socket itself
+const PACKET: &str = r#"const HEADER_SIZE := 22
+
+enum PacketDelivery {
+	UNRELIABLE = 0,
+	UNRELIABLE_SEQUENCED = 1,
+	RELIABLE = 2,
+	RELIABLE_SEQUENCED = 3,
+	ACK = 10,
+}
+
+## Writes a packet in the layout of the socket, the sequence is only read for sequenced deliveries
+## and the ack number only for reliable deliveries
+static func write_packet(event: String, payload: PackedByteArray, delivery: int, seq: int, ack: int, token: int) -> PackedByteArray:
+	var event_bytes := event.to_utf8_buffer()
+	var stream := StreamPeerBuffer.new()
+	stream.big_endian = false
+	stream.put_u16(delivery)
+	stream.put_u32(seq)
+	stream.put_u32(ack)
+	stream.put_u64(token)
+	stream.put_u32(event_bytes.size())
+	stream.put_data(event_bytes)
+	for _i in (4 - event_bytes.size() % 4) % 4:
+		stream.put_u8(0)
+	stream.put_data(payload)
+	return stream.data_array
+
+## Writes the acknowledgement of a reliable packet
+static func write_ack(ack: int) -> PackedByteArray:
+	var stream := StreamPeerBuffer.new()
+	stream.big_endian = false
+	stream.put_u16(PacketDelivery.ACK)
+	stream.put_u32(ack)
+	return stream.data_array
+
+## Reads a packet in the layout of the socket into a dictionary with the delivery, ack, event and
+## payload, returning an empty dictionary if it is too short
+static func read_packet(packet: PackedByteArray) -> Dictionary:
+	if packet.size() < HEADER_SIZE:
+		return {}
+	var event_len := packet.decode_u32(18)
+	var payload_offset := HEADER_SIZE + event_len + (4 - event_len % 4) % 4
+	if packet.size() < payload_offset:
+		return {}
+	return {
+		"delivery": packet.decode_u16(0),
+		"ack": packet.decode_u32(6),
+		"event": packet.slice(HEADER_SIZE, HEADER_SIZE + event_len).get_string_from_utf8(),
+		"payload": packet.slice(payload_offset),
+	}
+"#;
+
+fn gdscript_type(ty: &FieldType) -> String {
+    match ty {
+        FieldType::Bool => "bool".into(),
+        FieldType::U8
+        | FieldType::U16
+        | FieldType::U32
+        | FieldType::U64
+        | FieldType::I8
+        | FieldType::I16
+        | FieldType::I32
+        | FieldType::I64 => "int".into(),
+        FieldType::F32 | FieldType::F64 => "float".into(),
+        FieldType::String => "String".into(),
+        FieldType::Bytes => "PackedByteArray".into(),
+        // Godot does not support nested typed arrays
+        FieldType::List(inner) if matches!(**inner, FieldType::List(_)) => "Array".into(),
+        FieldType::List(inner) => format!("Array[{}]", gdscript_type(inner)),
+    }
+}
+
+fn delivery_constant(variant: &str) -> String {
+    let mut constant = String::new();
+    for (i, c) in variant.chars().enumerate() {
+        if c.is_ascii_uppercase() && i > 0 {
+            constant.push('_');
+        }
+        constant.push(c.to_ascii_uppercase());
+    }
+    constant
+}
+
+/// Writes the statements encoding the value, nesting a loop for every list
+fn write_value(out: &mut String, ty: &FieldType, value: &str, indent: &str, depth: usize) {
+    let put = match ty {
+        FieldType::Bool => format!("stream.put_u8(1 if {value} else 0)"),
+        FieldType::U8 => format!("stream.put_u8({value})"),
+        FieldType::U16 => format!("stream.put_u16({value})"),
+        FieldType::U32 => format!("stream.put_u32({value})"),
+        FieldType::U64 => format!("stream.put_u64({value})"),
+        FieldType::I8 => format!("stream.put_8({value})"),
+        FieldType::I16 => format!("stream.put_16({value})"),
+        FieldType::I32 => format!("stream.put_32({value})"),
+        FieldType::I64 => format!("stream.put_64({value})"),
+        FieldType::F32 => format!("stream.put_float({value})"),
+        FieldType::F64 => format!("stream.put_double({value})"),
+        FieldType::String => {
+            let _ = writeln!(
+                out,
+                "{indent}stream.put_u32({value}.to_utf8_buffer().size())"
+            );
+            format!("stream.put_data({value}.to_utf8_buffer())")
+        }
+        FieldType::Bytes => {
+            let _ = writeln!(out, "{indent}stream.put_u32({value}.size())");
+            format!("stream.put_data({value})")
+        }
+        FieldType::List(inner) => {
+            let item = format!("item{depth}");
+            let _ = writeln!(out, "{indent}stream.put_u32({value}.size())");
+            let _ = writeln!(out, "{indent}for {item} in {value}:");
+            write_value(out, inner, &item, &format!("{indent}\t"), depth + 1);
+            return;
+        }
+    };
+
+    let _ = writeln!(out, "{indent}{put}");
+}
+
+/// The expression decoding a value, lists are decoded by statements instead
+fn read_expr(ty: &FieldType) -> Option<&'static str> {
+    Some(match ty {
+        FieldType::Bool => "stream.get_u8() != 0",
+        FieldType::U8 => "stream.get_u8()",
+        FieldType::U16 => "stream.get_u16()",
+        FieldType::U32 => "stream.get_u32()",
+        FieldType::U64 => "stream.get_u64()",
+        FieldType::I8 => "stream.get_8()",
+        FieldType::I16 => "stream.get_16()",
+        FieldType::I32 => "stream.get_32()",
+        FieldType::I64 => "stream.get_64()",
+        FieldType::F32 => "stream.get_float()",
+        FieldType::F64 => "stream.get_double()",
+        FieldType::String => "stream.get_data(stream.get_u32())[1].get_string_from_utf8()",
+        FieldType::Bytes => "stream.get_data(stream.get_u32())[1]",
+        FieldType::List(_) => return None,
+    })
+}
+
+/// Writes the statements decoding a list and appending every item to the target
+fn read_list(out: &mut String, inner: &FieldType, target: &str, indent: &str, depth: usize) {
+    // Every item takes at least a byte, so a malformed length stops at the end of the payload
+    let _ = writeln!(
+        out,
+        "{indent}for _i{depth} in mini(stream.get_u32(), stream.get_available_bytes()):"
+    );
+    let inner_indent = format!("{indent}\t");
+    match read_expr(inner) {
+        Some(expr) => {
+            let _ = writeln!(out, "{inner_indent}{target}.append({expr})");
+        }
+        None => {
+            let FieldType::List(nested) = inner else {
+                unreachable!("Only lists have no read expression");
+            };
+
+            let item = format!("item{depth}");
+            let _ = writeln!(
+                out,
+                "{inner_indent}var {item}: {} = []",
+                gdscript_type(inner)
+            );
+            read_list(out, nested, &item, &inner_indent, depth + 1);
+            let _ = writeln!(out, "{inner_indent}{target}.append({item})");
+        }
+    }
+}
+
+/// Generates an inner class with an encoder and decoder for every event of the schema, along with
+/// the packet framing of the socket. The script is meant to be preloaded by the client
+pub fn generate(schema: &Schema) -> String {
+    let mut out = String::new();
+    out.push_str("# Generated by nautilus-sockets-codegen, do not edit\n");
+    out.push_str(PACKET);
+
+    for event in &schema.events {
+        let name = pascal_case(&event.name);
+        out.push_str("\n\n");
+        let _ = writeln!(out, "class {name}:");
+        let _ = writeln!(out, "\tconst EVENT := \"{}\"", event.name);
+        let _ = writeln!(
+            out,
+            "\tconst DELIVERY := PacketDelivery.{}",
+            delivery_constant(event.delivery.variant())
+        );
+        out.push('\n');
+
+        for field in &event.fields {
+            let _ = writeln!(out, "\tvar {}: {}", field.name, gdscript_type(&field.ty));
+        }
+
+        if !event.fields.is_empty() {
+            out.push('\n');
+        }
+
+        out.push_str("\tfunc encode() -> PackedByteArray:\n");
+        out.push_str("\t\tvar stream := StreamPeerBuffer.new()\n");
+        out.push_str("\t\tstream.big_endian = false\n");
+        for field in &event.fields {
+            write_value(&mut out, &field.ty, &field.name, "\t\t", 0);
+        }
+        out.push_str("\t\treturn stream.data_array\n\n");
+
+        let _ = writeln!(
+            out,
+            "\tstatic func decode(payload: PackedByteArray) -> {name}:"
+        );
+        out.push_str("\t\tvar stream := StreamPeerBuffer.new()\n");
+        out.push_str("\t\tstream.big_endian = false\n");
+        out.push_str("\t\tstream.data_array = payload\n");
+        let _ = writeln!(out, "\t\tvar value := {name}.new()");
+        for field in &event.fields {
+            let target = format!("value.{}", field.name);
+            match (&field.ty, read_expr(&field.ty)) {
+                (_, Some(expr)) => {
+                    let _ = writeln!(out, "\t\t{target} = {expr}");
+                }
+                (FieldType::List(inner), None) => read_list(&mut out, inner, &target, "\t\t", 0),
+                _ => unreachable!("Only lists have no read expression"),
+            }
+        }
+        out.push_str("\t\treturn value\n");
+    }
+
+    out
+}
@@ -0,0 +1,51 @@
+//! Generates the events of a protocol definition for nautilus sockets, as typed Rust events for
+//! `NautSocket` and matching encoders and decoders for C# and GDScript clients
+//!
+//! # Examples
+//!
+//! In the `build.rs` of the crate using the protocol:
+//!
+//! ```ignore
+//! fn main() {
+//!     nautilus_sockets_codegen::build_rust("protocol.naut").unwrap();
+//! }
+//! ```
+//!
+//! And in the crate itself:
+//!
+//! ```ignore
+//! include!(concat!(env!("OUT_DIR"), "/protocol.rs"));
+//! ```
+use std::{env, fs, path::Path};
+
+use anyhow::anyhow;
+
+pub mod csharp;
+pub mod gdscript;
+pub mod rust;
+pub mod schema;
+
+pub use schema::Schema;
+
+/// Generates the Rust events of the protocol definition into `OUT_DIR`, named after the definition
+/// with an `rs` extension. Meant to be called from a build script
+pub fn build_rust<P>(path: P) -> anyhow::Result<()>
+where
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    println!("cargo:rerun-if-changed={}", path.display());
+
+    let schema = Schema::parse(&fs::read_to_string(path)?)
+        .map_err(|e| anyhow!("{}: {e}", path.display()))?;
+
+    let Some(stem) = path.file_stem() else {
+        return Err(anyhow!("{} is not a file", path.display()));
+    };
+
+    let out_dir = env::var("OUT_DIR")?;
+    let out = Path::new(&out_dir).join(stem).with_extension("rs");
+    fs::write(out, rust::generate(&schema))?;
+
+    Ok(())
+}
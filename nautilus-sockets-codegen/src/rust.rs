@@ -0,0 +1,90 @@
+use std::fmt::Write;
+
+use crate::schema::{pascal_case, FieldType, Schema};
+
+fn rust_type(ty: &FieldType) -> String {
+    match ty {
+        FieldType::Bool => "bool".into(),
+        FieldType::U8 => "u8".into(),
+        FieldType::U16 => "u16".into(),
+        FieldType::U32 => "u32".into(),
+        FieldType::U64 => "u64".into(),
+        FieldType::I8 => "i8".into(),
+        FieldType::I16 => "i16".into(),
+        FieldType::I32 => "i32".into(),
+        FieldType::I64 => "i64".into(),
+        FieldType::F32 => "f32".into(),
+        FieldType::F64 => "f64".into(),
+        FieldType::String => "String".into(),
+        FieldType::Bytes => "Vec<u8>".into(),
+        FieldType::List(inner) => format!("Vec<{}>", rust_type(inner)),
+    }
+}
+
+/// Generates a struct implementing `ProtocolEvent` for every event of the schema
+pub fn generate(schema: &Schema) -> String {
+    let mut out = String::new();
+    out.push_str("// Generated by nautilus-sockets-codegen, do not edit\n");
+
+    for event in &schema.events {
+        let name = pascal_case(&event.name);
+        let _ = writeln!(out);
+        let _ = writeln!(out, "#[derive(Debug, Clone, PartialEq, Default)]");
+        let _ = writeln!(out, "pub struct {name} {{");
+        for field in &event.fields {
+            let _ = writeln!(out, "    pub {}: {},", field.name, rust_type(&field.ty));
+        }
+        let _ = writeln!(out, "}}");
+        let _ = writeln!(out);
+
+        let _ = writeln!(
+            out,
+            "impl ::nautilus_sockets::protocol::ProtocolEvent for {name} {{"
+        );
+        let _ = writeln!(out, "    const EVENT: &'static str = \"{}\";", event.name);
+        let _ = writeln!(
+            out,
+            "    const DELIVERY: ::nautilus_sockets::packet::PacketDelivery ="
+        );
+        let _ = writeln!(
+            out,
+            "        ::nautilus_sockets::packet::PacketDelivery::{};",
+            event.delivery.variant()
+        );
+        let _ = writeln!(out);
+
+        let buf = if event.fields.is_empty() {
+            "_buf"
+        } else {
+            "buf"
+        };
+        let _ = writeln!(out, "    fn encode(&self, {buf}: &mut Vec<u8>) {{");
+        for field in &event.fields {
+            let _ = writeln!(
+                out,
+                "        ::nautilus_sockets::protocol::ProtocolValue::write(&self.{}, buf);",
+                field.name
+            );
+        }
+        let _ = writeln!(out, "    }}");
+        let _ = writeln!(out);
+
+        let _ = writeln!(
+            out,
+            "    fn decode({buf}: &mut &[u8]) -> ::anyhow::Result<Self> {{"
+        );
+        let _ = writeln!(out, "        Ok(Self {{");
+        for field in &event.fields {
+            let _ = writeln!(
+                out,
+                "            {}: ::nautilus_sockets::protocol::ProtocolValue::read(buf)?,",
+                field.name
+            );
+        }
+        let _ = writeln!(out, "        }})");
+        let _ = writeln!(out, "    }}");
+        let _ = writeln!(out, "}}");
+    }
+
+    out
+}
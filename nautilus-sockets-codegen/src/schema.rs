@@ -0,0 +1,259 @@
+use std::collections::HashSet;
+
+use anyhow::anyhow;
+
+/// Field names that are keywords, or locals of the generated code, in one of the languages
+const RESERVED: [&str; 26] = [
+    "as", "break", "class", "const", "continue", "else", "enum", "fn", "for", "func", "if", "impl",
+    "in", "let", "loop", "match", "mod", "move", "payload", "pub", "ref", "return", "self",
+    "static", "stream", "type",
+];
+
+/// A parsed protocol definition
+///
+/// ```text
+/// # Sent when a player moves
+/// event player_moved unreliable_sequenced {
+///     id: u32
+///     x: f32
+///     y: f32
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Schema {
+    pub events: Vec<Event>,
+}
+
+/// An event of the [schema](Schema), sent under its name
+#[derive(Debug, Clone, PartialEq)]
+pub struct Event {
+    pub name: String,
+    pub delivery: Delivery,
+    pub fields: Vec<Field>,
+}
+
+/// A field of an [event](Event), written in the order it is declared
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field {
+    pub name: String,
+    pub ty: FieldType,
+}
+
+/// The deliveries an event may be sent with, matching the public packet deliveries
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Delivery {
+    Unreliable,
+    UnreliableSequenced,
+    Reliable,
+    ReliableSequenced,
+}
+
+impl Delivery {
+    const ALL: [(&'static str, Delivery); 4] = [
+        ("unreliable", Delivery::Unreliable),
+        ("unreliable_sequenced", Delivery::UnreliableSequenced),
+        ("reliable", Delivery::Reliable),
+        ("reliable_sequenced", Delivery::ReliableSequenced),
+    ];
+
+    /// The name of the delivery in the packet delivery enum
+    pub fn variant(&self) -> &'static str {
+        match self {
+            Delivery::Unreliable => "Unreliable",
+            Delivery::UnreliableSequenced => "UnreliableSequenced",
+            Delivery::Reliable => "Reliable",
+            Delivery::ReliableSequenced => "ReliableSequenced",
+        }
+    }
+}
+
+/// The type of a [field](Field). Numbers are little endian, bools are a single byte, and strings,
+/// bytes and lists are prefixed with their length as a u32
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldType {
+    Bool,
+    U8,
+    U16,
+    U32,
+    U64,
+    I8,
+    I16,
+    I32,
+    I64,
+    F32,
+    F64,
+    String,
+    Bytes,
+    List(Box<FieldType>),
+}
+
+impl FieldType {
+    fn parse(ty: &str) -> Option<Self> {
+        if let Some(inner) = ty.strip_prefix('[').and_then(|ty| ty.strip_suffix(']')) {
+            return Some(FieldType::List(Box::new(FieldType::parse(inner.trim())?)));
+        }
+
+        Some(match ty {
+            "bool" => FieldType::Bool,
+            "u8" => FieldType::U8,
+            "u16" => FieldType::U16,
+            "u32" => FieldType::U32,
+            "u64" => FieldType::U64,
+            "i8" => FieldType::I8,
+            "i16" => FieldType::I16,
+            "i32" => FieldType::I32,
+            "i64" => FieldType::I64,
+            "f32" => FieldType::F32,
+            "f64" => FieldType::F64,
+            "string" => FieldType::String,
+            "bytes" => FieldType::Bytes,
+            _ => return None,
+        })
+    }
+}
+
+impl Schema {
+    /// Parses a protocol definition, failing with the line of the first error
+    pub fn parse(src: &str) -> anyhow::Result<Self> {
+        let mut events: Vec<Event> = Vec::new();
+        let mut current: Option<Event> = None;
+        let mut field_names = HashSet::new();
+
+        for (index, line) in src.lines().enumerate() {
+            let line_num = index + 1;
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let Some(event) = current.as_mut() else {
+                let event =
+                    Self::parse_event_header(line).map_err(|e| anyhow!("Line {line_num}: {e}"))?;
+                if events.iter().any(|other| other.name == event.name) {
+                    return Err(anyhow!(
+                        "Line {line_num}: Event {} is defined twice",
+                        event.name
+                    ));
+                }
+
+                field_names.clear();
+                current = Some(event);
+                continue;
+            };
+
+            if line == "}" {
+                events.extend(current.take());
+                continue;
+            }
+
+            let field = Self::parse_field(line).map_err(|e| anyhow!("Line {line_num}: {e}"))?;
+            if !field_names.insert(field.name.clone()) {
+                return Err(anyhow!(
+                    "Line {line_num}: Field {} of {} is defined twice",
+                    field.name,
+                    event.name
+                ));
+            }
+
+            event.fields.push(field);
+        }
+
+        if let Some(event) = current {
+            return Err(anyhow!("Event {} is never closed", event.name));
+        }
+
+        Ok(Self { events })
+    }
+
+    /// Parses `event <name> [delivery] {`
+    fn parse_event_header(line: &str) -> anyhow::Result<Event> {
+        let Some(header) = line.strip_suffix('{') else {
+            return Err(anyhow!(
+                "Expected an event such as `event name reliable {{`"
+            ));
+        };
+
+        let mut words = header.split_whitespace();
+        if words.next() != Some("event") {
+            return Err(anyhow!(
+                "Expected an event such as `event name reliable {{`"
+            ));
+        }
+
+        let Some(name) = words.next() else {
+            return Err(anyhow!("The event has no name"));
+        };
+
+        if !is_ident(name) {
+            return Err(anyhow!("{name} is not a valid event name, use snake_case"));
+        }
+
+        let delivery = match words.next() {
+            Some(delivery) => Delivery::ALL
+                .iter()
+                .find(|(word, _)| *word == delivery)
+                .map(|(_, delivery)| *delivery)
+                .ok_or_else(|| anyhow!("Unknown delivery {delivery}"))?,
+            None => Delivery::Reliable,
+        };
+
+        if let Some(word) = words.next() {
+            return Err(anyhow!("Unexpected {word} after the delivery"));
+        }
+
+        Ok(Event {
+            name: name.to_string(),
+            delivery,
+            fields: Vec::new(),
+        })
+    }
+
+    /// Parses `<name>: <type>`
+    fn parse_field(line: &str) -> anyhow::Result<Field> {
+        let line = line.strip_suffix(',').unwrap_or(line);
+        let Some((name, ty)) = line.split_once(':') else {
+            return Err(anyhow!(
+                "Expected a field such as `name: type` or a closing }}"
+            ));
+        };
+
+        let (name, ty) = (name.trim(), ty.trim());
+        if !is_ident(name) {
+            return Err(anyhow!("{name} is not a valid field name, use snake_case"));
+        }
+
+        if RESERVED.contains(&name) {
+            return Err(anyhow!("{name} is reserved by a generated language"));
+        }
+
+        let Some(ty) = FieldType::parse(ty) else {
+            return Err(anyhow!("Unknown type {ty} for field {name}"));
+        };
+
+        Ok(Field {
+            name: name.to_string(),
+            ty,
+        })
+    }
+}
+
+/// Whether the name is a lowercase snake case identifier
+fn is_ident(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_lowercase())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+}
+
+/// Converts a snake case name into pascal case
+pub(crate) fn pascal_case(name: &str) -> String {
+    name.split('_')
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first.to_ascii_uppercase().to_string() + chars.as_str(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
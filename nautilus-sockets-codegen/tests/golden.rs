@@ -0,0 +1,203 @@
+use std::{
+    env, fs,
+    path::Path,
+    sync::{Arc, Mutex},
+    thread::sleep,
+    time::Duration,
+};
+
+use nautilus_sockets::prelude::*;
+use nautilus_sockets_codegen::{csharp, gdscript, rust, Schema};
+
+mod protocol {
+    include!("golden/protocol.rs.golden");
+}
+
+fn schema() -> Schema {
+    Schema::parse(include_str!("golden/protocol.naut")).unwrap()
+}
+
+/// Compares the generated code to the golden file, rewriting it when `UPDATE_GOLDEN` is set
+fn assert_golden(file: &str, generated: &str) {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(file);
+
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::write(&path, generated).unwrap();
+        return;
+    }
+
+    let golden = fs::read_to_string(&path).unwrap();
+    assert!(
+        golden == generated,
+        "{file} is out of date, run with UPDATE_GOLDEN=1 to regenerate it"
+    );
+}
+
+#[test]
+fn rust_matches_golden() {
+    assert_golden("protocol.rs.golden", &rust::generate(&schema()));
+}
+
+#[test]
+fn csharp_matches_golden() {
+    assert_golden(
+        "protocol.cs.golden",
+        &csharp::generate(&schema(), "Protocol"),
+    );
+}
+
+#[test]
+fn gdscript_matches_golden() {
+    assert_golden("protocol.gd.golden", &gdscript::generate(&schema()));
+}
+
+#[test]
+fn generated_rust_round_trips() {
+    let snapshot = protocol::Snapshot {
+        tick: 42,
+        alive: true,
+        health: -3,
+        score: -300,
+        delta: 70000,
+        elapsed: -1,
+        precise: 0.5,
+        flags: 0b101,
+        players: vec![1, 2, 3],
+        names: vec!["crab".into(), "ferris".into()],
+        grid: vec![vec![1, -1], vec![]],
+        blob: vec![9, 8, 7],
+    };
+
+    let mut buf = Vec::new();
+    snapshot.encode(&mut buf);
+
+    let mut read = buf.as_slice();
+    assert_eq!(protocol::Snapshot::decode(&mut read).unwrap(), snapshot);
+    assert!(read.is_empty());
+
+    assert_eq!(protocol::Snapshot::EVENT, "snapshot");
+    assert!(matches!(
+        protocol::Snapshot::DELIVERY,
+        PacketDelivery::Unreliable
+    ));
+    assert!(matches!(
+        protocol::Leave::DELIVERY,
+        PacketDelivery::Reliable
+    ));
+}
+
+#[test]
+fn generated_rust_matches_wire_format() {
+    let join = protocol::Join {
+        name: "ab".into(),
+        version: 3,
+    };
+
+    let mut buf = Vec::new();
+    join.encode(&mut buf);
+    assert_eq!(buf, [2, 0, 0, 0, b'a', b'b', 3, 0]);
+
+    let moved = protocol::PlayerMoved {
+        id: 1,
+        x: 1.0,
+        y: -1.0,
+    };
+
+    buf.clear();
+    moved.encode(&mut buf);
+    assert_eq!(buf, [1, 0, 0, 0, 0, 0, 128, 63, 0, 0, 128, 191]);
+
+    // A truncated packet fails to decode rather than reading past the end
+    assert!(protocol::PlayerMoved::decode(&mut &buf[..8]).is_err());
+
+    // A length claiming more than is left fails before anything is allocated for it
+    assert!(protocol::Join::decode(&mut &[0xFF, 0xFF, 0xFF, 0xFF, b'a', 3, 0][..]).is_err());
+}
+
+#[test]
+fn long_strings_and_lists_are_written_whole() {
+    let join = protocol::Join {
+        name: "\u{e9}".repeat(u16::MAX as usize),
+        version: 3,
+    };
+
+    let mut buf = Vec::new();
+    join.encode(&mut buf);
+    assert_eq!(protocol::Join::decode(&mut buf.as_slice()).unwrap(), join);
+}
+
+#[test]
+fn generated_rust_sends_through_sockets() {
+    let mut server = NautSocket::<NautServer>::new("127.0.0.1:0", ServerConfig::default()).unwrap();
+    let server_addr = server.socket().local_addr().unwrap().to_string();
+
+    server.on_event(|server, _ctx, join: protocol::Join| {
+        server.broadcast_event(&protocol::Chat {
+            id: join.version as u32,
+            message: format!("{} joined", join.name),
+        });
+    });
+
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let mut client = NautSocket::<NautClient>::new("127.0.0.1:0").unwrap();
+    let client_received = received.clone();
+    client.on_event(move |_client, _ctx, chat: protocol::Chat| {
+        client_received.lock().unwrap().push(chat);
+    });
+
+    client.connect_to(server_addr).unwrap();
+    client
+        .send_event(&protocol::Join {
+            name: "crab".into(),
+            version: 7,
+        })
+        .unwrap();
+
+    for _ in 0..200 {
+        server.poll();
+        server.run_events();
+        client.poll();
+        client.run_events();
+
+        if !received.lock().unwrap().is_empty() {
+            break;
+        }
+
+        sleep(Duration::from_millis(5));
+    }
+
+    let expected = protocol::Chat {
+        id: 7,
+        message: "crab joined".into(),
+    };
+    assert_eq!(*received.lock().unwrap(), [expected]);
+}
+
+#[test]
+fn parse_errors_name_the_line() {
+    let cases = [
+        ("event join {\n  name: text\n}", "Line 2: Unknown type text"),
+        (
+            "event join {\n}\nevent join {\n}",
+            "Line 3: Event join is defined twice",
+        ),
+        (
+            "event join {\n  a: u8\n  a: u8\n}",
+            "Line 3: Field a of join is defined twice",
+        ),
+        ("event join fast {\n}", "Line 1: Unknown delivery fast"),
+        ("event Join {\n}", "Line 1: Join is not a valid event name"),
+        ("event join {\n  type: u8\n}", "Line 2: type is reserved"),
+        ("event join {\n  a: u8", "Event join is never closed"),
+    ];
+
+    for (src, expected) in cases {
+        let err = Schema::parse(src).unwrap_err().to_string();
+        assert!(
+            err.starts_with(expected),
+            "{err:?} should start with {expected:?}"
+        );
+    }
+}
@@ -0,0 +1,101 @@
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, DeriveInput, Ident, LitStr, Path};
+
+/// The deliveries a message may be sent with, the rest are used by the socket itself
+const DELIVERIES: [&str; 4] = [
+    "Unreliable",
+    "UnreliableSequenced",
+    "Reliable",
+    "ReliableSequenced",
+];
+
+/// Derives `NautMessage`, tying a type to the event it is sent as, its default delivery and the
+/// codec it is encoded with
+///
+/// ```ignore
+/// #[derive(Serialize, Deserialize, NautMessage)]
+/// #[naut(event = "move", delivery = ReliableSequenced)]
+/// struct Move {
+///     x: f32,
+///     y: f32,
+/// }
+/// ```
+///
+/// The delivery defaults to `Reliable` and the codec to `DefaultCodec`, another codec is given as
+/// `codec = path::to::Codec`
+#[proc_macro_derive(NautMessage, attributes(naut))]
+pub fn derive_naut_message(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match expand(input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn expand(input: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let mut event: Option<LitStr> = None;
+    let mut delivery: Option<Ident> = None;
+    let mut codec: Option<Path> = None;
+
+    for attr in input.attrs.iter().filter(|attr| attr.path().is_ident("naut")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("event") {
+                event = Some(meta.value()?.parse()?);
+            } else if meta.path.is_ident("delivery") {
+                let ident: Ident = meta.value()?.parse()?;
+                if !DELIVERIES.contains(&ident.to_string().as_str()) {
+                    return Err(syn::Error::new(
+                        ident.span(),
+                        format!("Unknown delivery, expected one of {}", DELIVERIES.join(", ")),
+                    ));
+                }
+
+                delivery = Some(ident);
+            } else if meta.path.is_ident("codec") {
+                codec = Some(meta.value()?.parse()?);
+            } else {
+                return Err(meta.error("Unknown naut attribute, expected event, delivery or codec"));
+            }
+
+            Ok(())
+        })?;
+    }
+
+    let Some(event) = event else {
+        return Err(syn::Error::new_spanned(
+            &input.ident,
+            "NautMessage requires an event, such as #[naut(event = \"move\")]",
+        ));
+    };
+
+    if event.value().is_empty() {
+        return Err(syn::Error::new(event.span(), "The event must not be empty"));
+    }
+
+    if event.value().starts_with("naut::") {
+        return Err(syn::Error::new(
+            event.span(),
+            "Events starting with naut:: are reserved for the socket",
+        ));
+    }
+
+    let delivery = delivery.unwrap_or_else(|| Ident::new("Reliable", event.span()));
+    let codec = match codec {
+        Some(codec) => quote!(#codec),
+        None => quote!(::nautilus_sockets::codec::DefaultCodec),
+    };
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::nautilus_sockets::message::NautMessage for #ident #ty_generics
+        #where_clause
+        {
+            const EVENT: &'static str = #event;
+            const DELIVERY: ::nautilus_sockets::packet::PacketDelivery =
+                ::nautilus_sockets::packet::PacketDelivery::#delivery;
+            type Codec = #codec;
+        }
+    })
+}
@@ -1,6 +1,6 @@
 use std::{
     collections::HashMap,
-    net::ToSocketAddrs,
+    net::{SocketAddr, ToSocketAddrs},
     time::{Duration, Instant},
 };
 
@@ -51,6 +51,31 @@ impl AcknowledgementManager {
         self.packets_waiting_on_ack
             .insert(ack_num, AckPacket::new(buf, Instant::now(), addr));
     }
+
+    /// Forgets every packet waiting on an ack from an address that will never acknowledge them
+    pub(crate) fn remove_packets_to(&mut self, addr: &SocketAddr) {
+        let addr = addr.to_string();
+        self.packets_waiting_on_ack
+            .retain(|_, packet| packet.target != addr);
+    }
+
+    /// Checks if any packet is waiting on an ack from the address
+    pub(crate) fn has_packets_to(&self, addr: &SocketAddr) -> bool {
+        let addr = addr.to_string();
+        self.packets_waiting_on_ack
+            .values()
+            .any(|packet| packet.target == addr)
+    }
+
+    /// Points every packet waiting on an ack to a new address, used when a connection migrates
+    pub(crate) fn retarget_packets(&mut self, from: &SocketAddr, to: &SocketAddr) {
+        let from = from.to_string();
+        for packet in self.packets_waiting_on_ack.values_mut() {
+            if packet.target == from {
+                packet.target = to.to_string();
+            }
+        }
+    }
 }
 
 impl Default for AcknowledgementManager {
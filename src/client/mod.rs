@@ -1,27 +1,145 @@
 use std::{
-    collections::VecDeque,
+    collections::{HashMap, VecDeque},
+    fmt::Display,
     marker::PhantomData,
-    net::{SocketAddr, ToSocketAddrs, UdpSocket},
+    net::{Ipv4Addr, SocketAddr, SocketAddrV4, ToSocketAddrs, UdpSocket},
     str::FromStr,
+    time::{Duration, Instant},
 };
 
+use anyhow::anyhow;
+
 use byteorder::{ByteOrder, LittleEndian};
 
 use crate::{
     acknowledgement::{manager::AcknowledgementManager, packet::AckNumber},
     connection::EstablishedConnection,
-    events::EventEmitter,
-    packet::{IntoPacketDelivery, PacketDelivery},
+    details::ServerDetails,
+    events::{EventContext, EventEmitter},
+    packet::{IntoPacketDelivery, PacketDelivery, CONNECTION_ESTABLISHED_EVENT, PROTOCOL_VERSION},
     persistent::storage::PersistentStorage,
+    rpc::RpcManager,
     sequence::SequenceNumber,
+    server::{reflection::ReflectionGuard, rejection::RejectionReason},
     socket::{events::SocketEvent, NautSocket, SocketType},
 };
 
-pub type ConnectionId = u16;
+/// Identifies a connection on a [server](crate::server::NautServer). The index of a freed
+/// connection is given to the next new connection with a new generation, so an id held onto after
+/// its connection is freed never resolves to the connection that took its place
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug, Default)]
+pub struct ConnectionId {
+    index: u16,
+    generation: u16,
+}
+
+impl ConnectionId {
+    /// Creates a connection id from its index and generation
+    pub fn new(index: u16, generation: u16) -> Self {
+        Self { index, generation }
+    }
+
+    /// The index of the connection, shared with every earlier connection that held it
+    pub fn index(&self) -> u16 {
+        self.index
+    }
+
+    /// How many times the index was held by an earlier connection
+    pub fn generation(&self) -> u16 {
+        self.generation
+    }
+
+    /// The id of the next connection to hold the same index
+    pub(crate) fn next_generation(&self) -> Self {
+        Self::new(self.index, self.generation.wrapping_add(1))
+    }
+
+    /// Packs the id into a u32 to send over the wire, the generation in the high bits
+    pub fn to_bits(&self) -> u32 {
+        (self.generation as u32) << 16 | self.index as u32
+    }
+
+    /// Unpacks an id packed with [to bits](Self::to_bits)
+    pub fn from_bits(bits: u32) -> Self {
+        Self::new(bits as u16, (bits >> 16) as u16)
+    }
+}
+
+impl Display for ConnectionId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}v{}", self.index, self.generation)
+    }
+}
+
+/// A secret the [server](crate::server::NautServer) hands to each connection, carried in every
+/// packet so the connection can be recognised if the client's address changes
+pub type ConnectionToken = u64;
+
 #[derive(Default)]
 pub struct NautClient {
     /// The [nautilus server](crate::server::NautServer) we are connected to
     server_connection: Option<EstablishedConnection>,
+
+    pub(crate) client_events: VecDeque<ClientEvent>,
+
+    /// The nonce given to the next detail request
+    next_detail_nonce: u32,
+    /// Detail requests and discovery probes awaiting a response
+    detail_requests: HashMap<u32, PendingDetailRequest>,
+    /// The latest [server details](ServerDetails) received from each server
+    server_details: HashMap<SocketAddr, ServerDetails>,
+    /// The [server details](ServerDetails) of every server that answered a discovery probe
+    discovered_servers: HashMap<SocketAddr, ServerDetails>,
+    /// The address and cookie of the last challenge we answered
+    answered_challenge: Option<(SocketAddr, Option<u64>)>,
+    /// Sends detail requests and discovery probes, see [query socket](NautSocket::query_socket)
+    query_socket: Option<UdpSocket>,
+}
+
+/// A detail request or discovery probe awaiting a response
+struct PendingDetailRequest {
+    /// The time the request was sent
+    sent: Instant,
+    /// Whether the request was a discovery probe, which may be answered by many servers
+    discovery: bool,
+}
+
+impl NautClient {
+    /// How long a detail request or discovery probe waits on responses before it is forgotten
+    pub const DETAIL_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+    /// The largest size a server may ask us to pad a challenge answer to, as no larger packet is
+    /// read in full by the server
+    pub const MAX_CHALLENGE_RESPONSE_SIZE: usize = 1024;
+
+    /// Gets the latest [server details](ServerDetails) received from an [address](SocketAddr)
+    pub fn get_server_details(&self, addr: &SocketAddr) -> Option<&ServerDetails> {
+        self.server_details.get(addr)
+    }
+
+    /// Gets an iterator to the latest [server details](ServerDetails) received from every server
+    pub fn iter_server_details(
+        &self,
+    ) -> std::collections::hash_map::Iter<'_, SocketAddr, ServerDetails> {
+        self.server_details.iter()
+    }
+
+    /// Gets an iterator to the [server details](ServerDetails) of every server found through
+    /// discovery
+    pub fn iter_discovered_servers(
+        &self,
+    ) -> std::collections::hash_map::Iter<'_, SocketAddr, ServerDetails> {
+        self.discovered_servers.iter()
+    }
+
+    /// Forgets every server found through discovery
+    pub fn clear_discovered_servers(&mut self) {
+        self.discovered_servers.clear();
+    }
+
+    /// Gets an iterator to all [client events](ClientEvent) in the queue, this will not remove any from queue
+    pub fn iter_client_events(&self) -> std::collections::vec_deque::Iter<'_, ClientEvent> {
+        self.client_events.iter()
+    }
 }
 
 impl<'socket> SocketType<'socket> for NautClient {
@@ -61,6 +179,17 @@ impl<'socket> SocketType<'socket> for NautClient {
 
         Some(seq)
     }
+
+    fn connection_token(&self, _addr: &SocketAddr) -> ConnectionToken {
+        self.server_connection
+            .as_ref()
+            .map(|connection| connection.token)
+            .unwrap_or_default()
+    }
+
+    fn may_send(&mut self, _addr: &SocketAddr, _len: usize) -> bool {
+        true
+    }
 }
 
 impl<'socket> NautSocket<'socket, NautClient> {
@@ -73,7 +202,7 @@ impl<'socket> NautSocket<'socket, NautClient> {
         socket.set_nonblocking(true)?;
 
         let client = NautClient::default();
-        let naut_socket = Self {
+        let mut naut_socket = Self {
             socket,
             packet_queue: VecDeque::new(),
             inner: client,
@@ -82,11 +211,23 @@ impl<'socket> NautSocket<'socket, NautClient> {
             phantom: PhantomData,
             socket_events: Vec::new(),
             persistent: PersistentStorage::new(),
+            rpc: RpcManager::new(),
         };
 
+        naut_socket.register_rpc_events();
         Ok(naut_socket)
     }
 
+    /// Gets a reference to the [client](NautClient)
+    pub fn client(&self) -> &NautClient {
+        &self.inner
+    }
+
+    /// Gets a mutable reference to the [client](NautClient)
+    pub fn client_mut(&mut self) -> &mut NautClient {
+        &mut self.inner
+    }
+
     /// Gets the [address](SocketAddr) of the (server)[crate::server::NautServer] we are connected
     /// to
     pub fn get_server_address(&self) -> Option<&SocketAddr> {
@@ -106,6 +247,191 @@ impl<'socket> NautSocket<'socket, NautClient> {
         Ok(self.socket().connect(addr)?)
     }
 
+    /// Requests the [details](ServerDetails) of a [server](crate::server::NautServer) without
+    /// establishing a connection, the response is pushed as a
+    /// [server details event](ClientEvent::OnServerDetails)
+    pub fn request_details<A>(&mut self, addr: A) -> anyhow::Result<()>
+    where
+        A: ToSocketAddrs,
+    {
+        let nonce = self.inner.next_detail_nonce;
+        self.inner.next_detail_nonce = self.inner.next_detail_nonce.wrapping_add(1);
+
+        let packet = ServerDetails::request_packet(nonce)?;
+        self.query_socket()?.send_to(&packet, addr)?;
+        self.inner.detail_requests.insert(
+            nonce,
+            PendingDetailRequest {
+                sent: Instant::now(),
+                discovery: false,
+            },
+        );
+
+        Ok(())
+    }
+
+    /// Broadcasts a discovery probe over the local network to the port, every
+    /// [server](crate::server::NautServer) with discovery enabled will respond with its
+    /// [details](ServerDetails), pushed as a [server discovered event](ClientEvent::OnServerDiscovered)
+    pub fn discover_servers(&mut self, port: u16) -> anyhow::Result<()> {
+        self.query_socket()?.set_broadcast(true)?;
+        self.send_discovery_probe(SocketAddrV4::new(Ipv4Addr::BROADCAST, port))
+    }
+
+    /// Sends a discovery probe to a multicast group on the port, every
+    /// [server](crate::server::NautServer) in the group with discovery enabled will respond with its
+    /// [details](ServerDetails), pushed as a [server discovered event](ClientEvent::OnServerDiscovered)
+    pub fn discover_servers_multicast(&mut self, group: Ipv4Addr, port: u16) -> anyhow::Result<()> {
+        if !group.is_multicast() {
+            return Err(anyhow!("{group} is not a multicast address"));
+        }
+
+        self.send_discovery_probe(SocketAddrV4::new(group, port))
+    }
+
+    /// Sends a discovery probe to the address
+    fn send_discovery_probe(&mut self, addr: SocketAddrV4) -> anyhow::Result<()> {
+        let nonce = self.inner.next_detail_nonce;
+        self.inner.next_detail_nonce = self.inner.next_detail_nonce.wrapping_add(1);
+
+        let packet = ServerDetails::discovery_packet(nonce)?;
+        self.query_socket()?.send_to(&packet, addr)?;
+        self.inner.detail_requests.insert(
+            nonce,
+            PendingDetailRequest {
+                sent: Instant::now(),
+                discovery: true,
+            },
+        );
+
+        Ok(())
+    }
+
+    /// Gets the socket detail requests and discovery probes are sent from, created on first use.
+    /// Once we [connect to](Self::connect_to) a server our socket only receives packets from that
+    /// server, so queries to any other server are sent from this socket which is never connected
+    fn query_socket(&mut self) -> anyhow::Result<&UdpSocket> {
+        if self.inner.query_socket.is_none() {
+            let socket = UdpSocket::bind(SocketAddr::new(self.socket.local_addr()?.ip(), 0))?;
+            socket.set_nonblocking(true)?;
+            self.inner.query_socket = Some(socket);
+        }
+
+        self.inner
+            .query_socket
+            .as_ref()
+            .ok_or(anyhow!("Failed to create the query socket"))
+    }
+
+    /// Pushes the answers received on the [query socket](Self::query_socket) to the
+    /// [packet queue](Self::packet_queue)
+    fn poll_query_socket(&mut self) {
+        let Some(socket) = self.inner.query_socket.as_ref() else {
+            return;
+        };
+
+        let mut buf = vec![0; 1024];
+        while let Ok((size, addr)) = socket.recv_from(&mut buf) {
+            self.packet_queue
+                .push_back((addr, buf[0..size].to_vec(), Instant::now()));
+        }
+    }
+
+    /// Records the [server details](ServerDetails) of a detail response if we requested them
+    fn receive_server_details(&mut self, addr: SocketAddr, packet: &[u8]) -> anyhow::Result<()> {
+        let Some(nonce) = ServerDetails::get_nonce_from_packet(packet) else {
+            return Err(anyhow!("No nonce in detail response"));
+        };
+
+        let Some(request) = self.inner.detail_requests.get(&nonce) else {
+            return Err(anyhow!("Received details from {addr} that were never requested"));
+        };
+
+        let mut details = ServerDetails::from_response_packet(packet)?;
+        details.latency = Instant::now().duration_since(request.sent);
+
+        // Many servers may answer the same discovery probe, so it is kept until it times out
+        if request.discovery {
+            self.inner.discovered_servers.insert(addr, details);
+            self.inner
+                .client_events
+                .push_back(ClientEvent::OnServerDiscovered(addr));
+
+            return Ok(());
+        }
+
+        self.inner.detail_requests.remove(&nonce);
+        self.inner.server_details.insert(addr, details);
+        self.inner
+            .client_events
+            .push_back(ClientEvent::OnServerDetails(addr));
+
+        Ok(())
+    }
+
+    /// Handles our server telling us our position in its wait queue, the packet is echoed back so
+    /// the server knows we are still waiting
+    fn receive_queue_position(&mut self, addr: SocketAddr, packet: &[u8]) -> anyhow::Result<()> {
+        if self.get_server_address() != Some(&addr) {
+            return Err(anyhow!("Received a queue position from {addr} which is not our server"));
+        }
+
+        let Some(position) = packet.get(Self::DELIVERY_TYPE_BUF..Self::DELIVERY_TYPE_BUF + 4)
+        else {
+            return Err(anyhow!("No position in queue position"));
+        };
+
+        let position = LittleEndian::read_u32(position) as usize;
+        self.inner
+            .client_events
+            .push_back(ClientEvent::OnQueued(position));
+
+        self.socket.send_to(packet, addr)?;
+
+        Ok(())
+    }
+
+    /// Answers a challenge from our server, or from a server waiting on packets we sent it such as
+    /// a master server, and resends the packets it dropped while we were unverified so the
+    /// connection is established straight away. Each dropped packet is challenged, but the
+    /// packets are only resent for a new challenge
+    fn receive_challenge(&mut self, addr: SocketAddr, packet: &[u8]) -> anyhow::Result<()> {
+        if self.get_server_address() != Some(&addr) && !self.ack_manager.has_packets_to(&addr) {
+            return Err(anyhow!("Received a challenge from {addr} we have not contacted"));
+        }
+
+        let answer =
+            ReflectionGuard::challenge_answer(packet, NautClient::MAX_CHALLENGE_RESPONSE_SIZE)?;
+        self.socket.send_to(&answer, addr)?;
+
+        let cookie = ReflectionGuard::get_cookie_from_packet(packet);
+        if self.inner.answered_challenge.replace((addr, cookie)) != Some((addr, cookie)) {
+            self.resend_ack_packets_to(&addr);
+        }
+
+        Ok(())
+    }
+
+    /// Handles our server refusing our connection, packets waiting on an ack from it are
+    /// forgotten as they will never be acknowledged
+    fn receive_rejection(&mut self, addr: SocketAddr, packet: &[u8]) -> anyhow::Result<()> {
+        if self.get_server_address() != Some(&addr) {
+            return Err(anyhow!("Received a rejection from {addr} which is not our server"));
+        }
+
+        let Some(reason) = packet.get(Self::DELIVERY_TYPE_BUF..Self::DELIVERY_TYPE_BUF + 2) else {
+            return Err(anyhow!("No reason in rejection"));
+        };
+
+        let reason = RejectionReason::from_raw(LittleEndian::read_u16(reason))?;
+        self.ack_manager.remove_packets_to(&addr);
+        self.inner
+            .client_events
+            .push_back(ClientEvent::OnConnectionRejected(reason));
+
+        Ok(())
+    }
+
     /// Sends an event message to the [server](crate::server::NautServer) we are connected to
     pub fn send(
         &mut self,
@@ -124,9 +450,18 @@ impl<'socket> NautSocket<'socket, NautClient> {
     /// [ack packets](crate::acknowledgement::packet::AckPacket), resolving sequenced packets and emitting
     /// listening events
     pub fn run_events(&mut self) {
-        let event_emitter = std::mem::take(&mut self.event_emitter);
+        self.poll_query_socket();
+        self.save_snapshot_if_due();
+        self.expire_requests();
+
+        let mut event_emitter = std::mem::take(&mut self.event_emitter);
         let event_emitter_ref = &event_emitter;
-        while let Some((addr, packet)) = self.oldest_packet_in_queue() {
+        while let Some((addr, packet, received_at)) = self.oldest_packet_in_queue() {
+            // Lets other protocols sharing the socket take the packet before we parse it
+            if event_emitter_ref.emit_raw_packet_events(self, addr, &packet) {
+                continue;
+            }
+
             let Some(delivery_type) = Self::get_delivery_type_from_packet(&packet) else {
                 self.socket_events
                     .push(SocketEvent::ReadPacketFail("No delivery type".to_string()));
@@ -147,6 +482,46 @@ impl<'socket> NautSocket<'socket, NautClient> {
                 continue;
             }
 
+            // A server has responded to our detail request
+            if delivery_type == PacketDelivery::detail_response() {
+                if let Err(e) = self.receive_server_details(addr, &packet) {
+                    self.socket_events
+                        .push(SocketEvent::ReadPacketFail(e.to_string()));
+                }
+
+                continue;
+            }
+
+            // Our server is full and is holding us in its wait queue
+            if delivery_type == PacketDelivery::queue_position() {
+                if let Err(e) = self.receive_queue_position(addr, &packet) {
+                    self.socket_events
+                        .push(SocketEvent::ReadPacketFail(e.to_string()));
+                }
+
+                continue;
+            }
+
+            // Our server wants proof we can receive packets before establishing our connection
+            if delivery_type == PacketDelivery::challenge() {
+                if let Err(e) = self.receive_challenge(addr, &packet) {
+                    self.socket_events
+                        .push(SocketEvent::ReadPacketFail(e.to_string()));
+                }
+
+                continue;
+            }
+
+            // Our server has refused our connection
+            if delivery_type == PacketDelivery::rejection() {
+                if let Err(e) = self.receive_rejection(addr, &packet) {
+                    self.socket_events
+                        .push(SocketEvent::ReadPacketFail(e.to_string()));
+                }
+
+                continue;
+            }
+
             // Check size here instead of in poll as ack packets do not fit into padding
             if packet.len() < Self::PACKET_PADDING {
                 continue;
@@ -154,9 +529,26 @@ impl<'socket> NautSocket<'socket, NautClient> {
 
             // Gets the event title from the packet
             let Ok(event) = Self::get_event_from_packet(&packet) else {
+                self.socket_events.push(SocketEvent::ReadPacketFail(format!(
+                    "Failed to read the event from {addr}, it may be using a protocol version \
+                     other than {PROTOCOL_VERSION}"
+                )));
                 continue;
             };
 
+            // Keep hold of the token the server gave us, so it can still recognise us if our
+            // address changes
+            if let Some(token) = Self::get_connection_token_from_packet(&packet) {
+                if let Some(server_connection) = self
+                    .inner
+                    .server_connection
+                    .as_mut()
+                    .filter(|connection| connection.addr == addr)
+                {
+                    server_connection.token = token;
+                }
+            }
+
             // Send a packet  to acknowledge the sender we have recieved their packet
             if delivery_type.is_reliable() {
                 if let Err(e) = self.send_ack_packet(addr, &packet) {
@@ -190,12 +582,40 @@ impl<'socket> NautSocket<'socket, NautClient> {
                 };
             }
 
+            // Other servers we talk to, such as a master server, also establish connections with
+            // us but only our own server's matters
+            if event == CONNECTION_ESTABLISHED_EVENT {
+                if self.get_server_address() == Some(&addr) {
+                    self.inner.client_events.push_back(ClientEvent::OnConnected);
+                }
+
+                continue;
+            }
+
             let bytes = Self::get_packet_bytes(&packet).unwrap_or(Default::default());
+            let ctx = EventContext {
+                addr,
+                connection_id: None,
+                delivery: delivery_type,
+                sequence: Self::get_seq_if_sequenced(delivery_type, &packet),
+                ack: Self::get_ack_if_reliable(delivery_type, &packet),
+                received_at,
+                payload: &bytes,
+            };
             // Emits the event to the event listeners
-            event_emitter_ref.emit_event(&event, self, (addr, &bytes));
+            event_emitter_ref.emit_event(&event, self, ctx);
         }
 
-        event_emitter_ref.emit_polled_events(self);
+        event_emitter.emit_polled_events(self);
+        event_emitter.emit_persistent_changed_events(self);
+
+        // Clear client events this time around
+        self.inner.client_events.clear();
+
+        // Forget detail requests that were never answered
+        self.inner
+            .detail_requests
+            .retain(|_, request| request.sent.elapsed() < NautClient::DETAIL_REQUEST_TIMEOUT);
 
         // Retry ack packets
         self.socket_events.clear();
@@ -204,3 +624,22 @@ impl<'socket> NautSocket<'socket, NautClient> {
         self.event_emitter = event_emitter;
     }
 }
+
+#[derive(Clone, Copy, Debug)]
+pub enum ClientEvent {
+    /// Pushed to the client event queue when the server has established our connection
+    OnConnected,
+    /// Pushed to the client event queue when a server responds to our detail request
+    OnServerDetails(SocketAddr),
+    /// Pushed to the client event queue when a server responds to our discovery probe
+    OnServerDiscovered(SocketAddr),
+    /// Pushed to the client event queue when every page of a master server list has been received
+    /// from the master server
+    OnMasterServerList(SocketAddr),
+    /// Pushed to the client event queue when our server refuses our connection
+    OnConnectionRejected(RejectionReason),
+    /// Pushed to the client event queue when our server is full and tells us our position in its
+    /// wait queue, starting from 1. We are connected once we receive
+    /// [connected](ClientEvent::OnConnected)
+    OnQueued(usize),
+}
@@ -0,0 +1,184 @@
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    client::{ConnectionId, NautClient},
+    events::EventContext,
+    packet::PacketDelivery,
+    server::NautServer,
+    socket::{events::SocketEvent, NautSocket, SocketType},
+};
+
+/// Turns typed values into the bytes of a packet and back
+pub trait Codec: 'static {
+    /// Encodes the value into bytes
+    fn encode<T>(value: &T) -> anyhow::Result<Vec<u8>>
+    where
+        T: Serialize;
+
+    /// Decodes a value from bytes encoded by [encode](Self::encode)
+    fn decode<T>(bytes: &[u8]) -> anyhow::Result<T>
+    where
+        T: DeserializeOwned;
+}
+
+/// A compact binary [codec](Codec) using bincode
+pub struct BincodeCodec;
+
+impl Codec for BincodeCodec {
+    fn encode<T>(value: &T) -> anyhow::Result<Vec<u8>>
+    where
+        T: Serialize,
+    {
+        Ok(bincode::serialize(value)?)
+    }
+
+    fn decode<T>(bytes: &[u8]) -> anyhow::Result<T>
+    where
+        T: DeserializeOwned,
+    {
+        Ok(bincode::deserialize(bytes)?)
+    }
+}
+
+/// The [codec](Codec) used by the typed methods that do not name one
+pub type DefaultCodec = BincodeCodec;
+
+impl<'socket, S> NautSocket<'socket, S>
+where
+    S: SocketType<'socket>,
+{
+    /// Run a function as a callback when the event is received, with the packet decoded by the
+    /// [default codec](DefaultCodec). A packet that fails to decode never reaches the callback and
+    /// pushes a [read packet fail](SocketEvent::ReadPacketFail) event instead
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// #[derive(Deserialize)]
+    /// struct Move {
+    ///     x: f32,
+    ///     y: f32,
+    /// }
+    ///
+    /// server.on_typed("move", |_server, ctx, position: Move| {
+    ///     println!("{} moved to {}, {}", ctx.addr, position.x, position.y);
+    /// });
+    /// ```
+    pub fn on_typed<T, F>(&mut self, event: &str, cb: F)
+    where
+        T: DeserializeOwned,
+        F: Fn(&mut NautSocket<'socket, S>, EventContext, T) + Send + Sync + 'static,
+    {
+        self.on_typed_with::<DefaultCodec, T, F>(event, cb);
+    }
+
+    /// Run a function as a callback when the event is received, with the packet decoded by the
+    /// [codec](Codec), see [on typed](Self::on_typed)
+    pub fn on_typed_with<C, T, F>(&mut self, event: &str, cb: F)
+    where
+        C: Codec,
+        T: DeserializeOwned,
+        F: Fn(&mut NautSocket<'socket, S>, EventContext, T) + Send + Sync + 'static,
+    {
+        let event_name = event.to_string();
+        self.on(event, move |socket, ctx| match C::decode(ctx.payload) {
+            Ok(value) => cb(socket, ctx, value),
+            Err(e) => socket
+                .socket_events
+                .push(SocketEvent::ReadPacketFail(format!(
+                    "Failed to decode {event_name} from {}: {e}",
+                    ctx.addr
+                ))),
+        });
+    }
+}
+
+impl NautSocket<'_, NautClient> {
+    /// Sends the value encoded by the [default codec](DefaultCodec) to the server we are
+    /// connected to
+    pub fn send_typed<T>(
+        &mut self,
+        event: &str,
+        value: &T,
+        delivery: PacketDelivery,
+    ) -> anyhow::Result<()>
+    where
+        T: Serialize,
+    {
+        self.send_typed_with::<DefaultCodec, T>(event, value, delivery)
+    }
+
+    /// Sends the value encoded by the [codec](Codec) to the server we are connected to
+    pub fn send_typed_with<C, T>(
+        &mut self,
+        event: &str,
+        value: &T,
+        delivery: PacketDelivery,
+    ) -> anyhow::Result<()>
+    where
+        C: Codec,
+        T: Serialize,
+    {
+        self.send(event, &C::encode(value)?, delivery)
+    }
+}
+
+impl NautSocket<'_, NautServer> {
+    /// Sends the value encoded by the [default codec](DefaultCodec) to the client
+    pub fn send_typed<T>(
+        &mut self,
+        event: &str,
+        value: &T,
+        delivery: PacketDelivery,
+        client: ConnectionId,
+    ) -> anyhow::Result<()>
+    where
+        T: Serialize,
+    {
+        self.send_typed_with::<DefaultCodec, T>(event, value, delivery, client)
+    }
+
+    /// Sends the value encoded by the [codec](Codec) to the client
+    pub fn send_typed_with<C, T>(
+        &mut self,
+        event: &str,
+        value: &T,
+        delivery: PacketDelivery,
+        client: ConnectionId,
+    ) -> anyhow::Result<()>
+    where
+        C: Codec,
+        T: Serialize,
+    {
+        self.send(event, &C::encode(value)?, delivery, client)
+    }
+
+    /// Sends the value encoded by the [default codec](DefaultCodec) to every established
+    /// connection
+    pub fn broadcast_typed<T>(
+        &mut self,
+        event: &str,
+        value: &T,
+        delivery: PacketDelivery,
+    ) -> anyhow::Result<()>
+    where
+        T: Serialize,
+    {
+        self.broadcast_typed_with::<DefaultCodec, T>(event, value, delivery)
+    }
+
+    /// Sends the value encoded by the [codec](Codec) to every established connection
+    pub fn broadcast_typed_with<C, T>(
+        &mut self,
+        event: &str,
+        value: &T,
+        delivery: PacketDelivery,
+    ) -> anyhow::Result<()>
+    where
+        C: Codec,
+        T: Serialize,
+    {
+        self.broadcast(event, &C::encode(value)?, delivery);
+        Ok(())
+    }
+}
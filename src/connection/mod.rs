@@ -1,6 +1,12 @@
-use std::{collections::HashMap, net::SocketAddr};
+use std::{
+    any::{Any, TypeId},
+    collections::{hash_map::RandomState, HashMap},
+    hash::{BuildHasher, Hasher},
+    net::SocketAddr,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
 
-use crate::sequence::SequenceNumber;
+use crate::{client::ConnectionToken, sequence::SequenceNumber};
 
 pub(crate) struct EstablishedConnection {
     /// Each individual event has its own [seq number](crate::sequence::SequenceNumber)
@@ -9,6 +15,15 @@ pub(crate) struct EstablishedConnection {
     pub last_seq_num_recv: HashMap<String, SequenceNumber>,
     /// The established [connection address](SocketAddr)
     pub addr: SocketAddr,
+    /// The [token](ConnectionToken) that identifies this connection regardless of its address
+    pub token: ConnectionToken,
+    /// Whether the connection has proven it can receive packets at its address
+    pub verified: bool,
+    /// When a packet was last received from the connection
+    pub last_seen: Instant,
+    /// Application defined data attached to the connection, one value of each type, dropped when
+    /// the connection is freed
+    pub data: HashMap<TypeId, Box<dyn Any + Send + Sync>>,
 }
 
 impl EstablishedConnection {
@@ -18,6 +33,63 @@ impl EstablishedConnection {
             current_send_seq_num: HashMap::new(),
             last_seq_num_recv: HashMap::new(),
             addr,
+            token: 0,
+            verified: false,
+            last_seen: Instant::now(),
+            data: HashMap::new(),
+        }
+    }
+
+    /// Gets the data of the type attached to the connection
+    pub fn data<T>(&self) -> Option<&T>
+    where
+        T: Any + Send + Sync,
+    {
+        self.data.get(&TypeId::of::<T>())?.downcast_ref()
+    }
+
+    /// Gets a mutable reference to the data of the type attached to the connection
+    pub fn data_mut<T>(&mut self) -> Option<&mut T>
+    where
+        T: Any + Send + Sync,
+    {
+        self.data.get_mut(&TypeId::of::<T>())?.downcast_mut()
+    }
+
+    /// Attaches data to the connection, returning the data of the same type it replaced
+    pub fn insert_data<T>(&mut self, data: T) -> Option<T>
+    where
+        T: Any + Send + Sync,
+    {
+        let old = self.data.insert(TypeId::of::<T>(), Box::new(data))?;
+        old.downcast().ok().map(|old| *old)
+    }
+
+    /// Removes the data of the type from the connection
+    pub fn remove_data<T>(&mut self) -> Option<T>
+    where
+        T: Any + Send + Sync,
+    {
+        let data = self.data.remove(&TypeId::of::<T>())?;
+        data.downcast().ok().map(|data| *data)
+    }
+}
+
+/// Generates a new random non-zero [connection token](ConnectionToken)
+pub(crate) fn generate_connection_token() -> ConnectionToken {
+    loop {
+        // Each random state is seeded with different random keys, so hashing the time with it
+        // gives us an unpredictable token without pulling in a random number generator
+        let mut hasher = RandomState::new().build_hasher();
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_nanos())
+            .unwrap_or_default();
+        hasher.write_u128(nanos);
+
+        let token = hasher.finish();
+        if token != 0 {
+            return token;
         }
     }
 }
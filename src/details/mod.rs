@@ -0,0 +1,129 @@
+use std::time::Duration;
+
+use anyhow::anyhow;
+use byteorder::{ByteOrder, LittleEndian};
+
+use crate::packet::{IntoPacketDelivery, PacketDelivery};
+
+/// The details a [server](crate::server::NautServer) reports about itself to anything that
+/// requests them, without a connection being established
+#[derive(Clone, Debug, Default)]
+pub struct ServerDetails {
+    /// The name of the server
+    pub name: String,
+    /// The amount of established connections on the server
+    pub current_connections: u32,
+    /// The max amount of connections the server will process
+    pub max_connections: u32,
+    /// The [protocol version](crate::packet::PROTOCOL_VERSION) the server speaks
+    pub protocol_version: u16,
+    /// Application defined bytes supplied by the server's detail callback
+    pub payload: Vec<u8>,
+    /// How long it took for the response to arrive after the request was sent, measured by the
+    /// requesting client
+    pub latency: Duration,
+}
+
+impl ServerDetails {
+    /// The offset in a detail packet of the request nonce
+    pub const NONCE_OFFSET: usize = 2;
+    /// The amount of space in a detail packet for the request nonce
+    pub const NONCE_BUF: usize = 4;
+
+    /// The size of the contents of a detail request packet
+    pub const REQUEST_SIZE: usize = Self::NONCE_OFFSET + Self::NONCE_BUF;
+    /// The size detail requests are padded to, as a server never answers a request with a larger
+    /// response
+    pub const PADDED_REQUEST_SIZE: usize = 512;
+
+    /// The offset in a detail response packet of the details themselves
+    pub(crate) const DETAILS_OFFSET: usize = Self::REQUEST_SIZE;
+
+    /// Creates a detail request packet carrying a nonce which the response will echo
+    pub(crate) fn request_packet(nonce: u32) -> anyhow::Result<Vec<u8>> {
+        Self::nonce_packet(PacketDelivery::detail_request(), nonce)
+    }
+
+    /// Creates a discovery probe packet carrying a nonce which every response will echo
+    pub(crate) fn discovery_packet(nonce: u32) -> anyhow::Result<Vec<u8>> {
+        Self::nonce_packet(PacketDelivery::discovery_probe(), nonce)
+    }
+
+    /// Creates a packet of the [delivery type](PacketDelivery) carrying only a nonce, padded to
+    /// the [padded request size](Self::PADDED_REQUEST_SIZE)
+    fn nonce_packet(delivery: PacketDelivery, nonce: u32) -> anyhow::Result<Vec<u8>> {
+        let mut buf = vec![0; Self::PADDED_REQUEST_SIZE];
+        LittleEndian::write_u16(&mut buf[0..2], delivery.packet_delivery_as()?);
+        LittleEndian::write_u32(
+            &mut buf[Self::NONCE_OFFSET..Self::NONCE_OFFSET + Self::NONCE_BUF],
+            nonce,
+        );
+
+        Ok(buf)
+    }
+
+    /// Gets the nonce from a detail request or response packet
+    pub(crate) fn get_nonce_from_packet(buf: &[u8]) -> Option<u32> {
+        if Self::NONCE_OFFSET + Self::NONCE_BUF > buf.len() {
+            return None;
+        }
+
+        Some(LittleEndian::read_u32(
+            &buf[Self::NONCE_OFFSET..Self::NONCE_OFFSET + Self::NONCE_BUF],
+        ))
+    }
+
+    /// Creates a detail response packet answering the request with the nonce
+    pub(crate) fn response_packet(&self, nonce: u32) -> anyhow::Result<Vec<u8>> {
+        let name = self.name.as_bytes();
+        if name.len() > u16::MAX as usize {
+            return Err(anyhow!("Server name is too long to fit into a detail response"));
+        }
+
+        let mut buf = vec![0; Self::DETAILS_OFFSET + 12 + name.len()];
+        LittleEndian::write_u16(
+            &mut buf[0..2],
+            PacketDelivery::detail_response().packet_delivery_as()?,
+        );
+        LittleEndian::write_u32(
+            &mut buf[Self::NONCE_OFFSET..Self::NONCE_OFFSET + Self::NONCE_BUF],
+            nonce,
+        );
+
+        let details = &mut buf[Self::DETAILS_OFFSET..];
+        LittleEndian::write_u16(&mut details[0..2], self.protocol_version);
+        LittleEndian::write_u32(&mut details[2..6], self.current_connections);
+        LittleEndian::write_u32(&mut details[6..10], self.max_connections);
+        LittleEndian::write_u16(&mut details[10..12], name.len() as u16);
+        details[12..].copy_from_slice(name);
+
+        buf.extend_from_slice(&self.payload);
+
+        Ok(buf)
+    }
+
+    /// Reads the details from a detail response packet
+    pub(crate) fn from_response_packet(buf: &[u8]) -> anyhow::Result<Self> {
+        let Some(details) = buf.get(Self::DETAILS_OFFSET..) else {
+            return Err(anyhow!("Packet not large enough for server details"));
+        };
+
+        if details.len() < 12 {
+            return Err(anyhow!("Packet not large enough for server details"));
+        }
+
+        let name_len = LittleEndian::read_u16(&details[10..12]) as usize;
+        let Some(name) = details.get(12..12 + name_len) else {
+            return Err(anyhow!("Packet not large enough for server name"));
+        };
+
+        Ok(Self {
+            name: String::from_utf8(name.to_vec())?,
+            current_connections: LittleEndian::read_u32(&details[2..6]),
+            max_connections: LittleEndian::read_u32(&details[6..10]),
+            protocol_version: LittleEndian::read_u16(&details[0..2]),
+            payload: details[12 + name_len..].to_vec(),
+            latency: Duration::ZERO,
+        })
+    }
+}
@@ -1,13 +1,70 @@
-use std::{collections::HashMap, net::SocketAddr, sync::Arc};
+use std::{any::TypeId, collections::HashMap, net::SocketAddr, sync::Arc, time::Instant};
 
-use crate::socket::{NautSocket, SocketType};
+use crate::{
+    client::ConnectionId,
+    packet::PacketDelivery,
+    socket::{NautSocket, SocketType},
+};
 
-/// The arguments that are passed into a callback, the sending address and the packet itself
-pub(crate) type EventCallbackArgs<'callback> = (SocketAddr, &'callback [u8]);
-/// The structure of a callback, it passes in a reference to the socket type and the arguments
-pub(crate) type EventCallback<T> = dyn Fn(&mut T, EventCallbackArgs) + Send + Sync;
+/// Everything known about a received event, passed into its callbacks
+#[derive(Clone, Copy, Debug)]
+pub struct EventContext<'callback> {
+    /// The address the event was sent from
+    pub addr: SocketAddr,
+    /// The connection that sent the event, only known by the [server](crate::server::NautServer)
+    pub connection_id: Option<ConnectionId>,
+    /// How the event was delivered
+    pub delivery: PacketDelivery,
+    /// The sequence number of the event if it was sequenced
+    pub sequence: Option<u32>,
+    /// The acknowledgement number of the event if it was reliable
+    pub ack: Option<u32>,
+    /// When the packet carrying the event was polled from the socket
+    pub received_at: Instant,
+    /// The bytes of the event
+    pub payload: &'callback [u8],
+}
+
+impl EventContext<'_> {
+    /// Sends an event back to the address this event was sent from
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// server.on("ping", |server, ctx| {
+    ///     let _ = ctx.reply(server, "pong", ctx.payload, PacketDelivery::Unreliable);
+    /// });
+    /// ```
+    pub fn reply<'socket, S>(
+        &self,
+        socket: &mut NautSocket<'socket, S>,
+        event: &str,
+        buf: &[u8],
+        delivery: PacketDelivery,
+    ) -> anyhow::Result<()>
+    where
+        S: SocketType<'socket>,
+    {
+        socket.send_by_addr(event, buf, delivery, self.addr.to_string())
+    }
+}
+
+/// The structure of a callback, it passes in a reference to the socket type and the
+/// [context](EventContext) of the event
+pub(crate) type EventCallback<T> = dyn Fn(&mut T, EventContext) + Send + Sync;
 /// The arguments that are passed into a polled callback
 pub(crate) type PolledCallback<T> = dyn Fn(&mut T) + Send + Sync;
+/// The structure of a raw packet callback, it passes in the sending address and the unparsed
+/// packet and returns whether it has consumed the packet
+pub(crate) type RawPacketCallback<T> = dyn Fn(&mut T, SocketAddr, &[u8]) -> bool + Send + Sync;
+
+/// A callback run when a [persistent](crate::persistent::Persistent) changes, alongside the type
+/// it watches and the last change tick it was run for
+pub(crate) struct PersistentChangedCallback<T> {
+    type_id: TypeId,
+    seen_tick: u64,
+    callback: Arc<PolledCallback<T>>,
+}
 
 /// Listens to and emits events, running callbacks on events that have been emitted
 pub(crate) struct EventEmitter<'socket, T>
@@ -16,6 +73,8 @@ where
 {
     pub event_callbacks: HashMap<String, Vec<Arc<EventCallback<NautSocket<'socket, T>>>>>,
     pub polled_callbacks: Vec<Arc<PolledCallback<NautSocket<'socket, T>>>>,
+    pub raw_packet_callbacks: Vec<Arc<RawPacketCallback<NautSocket<'socket, T>>>>,
+    pub persistent_changed_callbacks: Vec<PersistentChangedCallback<NautSocket<'socket, T>>>,
 }
 
 impl<'socket, T> EventEmitter<'socket, T>
@@ -27,13 +86,15 @@ where
         Self {
             event_callbacks: HashMap::new(),
             polled_callbacks: Vec::new(),
+            raw_packet_callbacks: Vec::new(),
+            persistent_changed_callbacks: Vec::new(),
         }
     }
 
     /// Registers a callback to be run when an event is emitted
     pub(crate) fn register_event<F>(&mut self, event: &str, f: F)
     where
-        F: Fn(&mut NautSocket<T>, EventCallbackArgs) + Send + Sync + 'static,
+        F: Fn(&mut NautSocket<'socket, T>, EventContext) + Send + Sync + 'static,
     {
         let event = event.to_string();
         if let Some(callbacks) = self.event_callbacks.get_mut(&event) {
@@ -45,13 +106,18 @@ where
     }
 
     /// Emits an event and fires all callbacks registered for that event
-    pub(crate) fn emit_event(&self, event: &str, value: &mut NautSocket<'socket, T>, args: EventCallbackArgs) {
+    pub(crate) fn emit_event(
+        &self,
+        event: &str,
+        value: &mut NautSocket<'socket, T>,
+        ctx: EventContext,
+    ) {
         let Some(callbacks) = self.event_callbacks.get(event) else {
             return;
         };
 
         for callback in callbacks {
-            callback(value, args)
+            callback(value, ctx)
         }
     }
 
@@ -72,6 +138,58 @@ where
             callback(socket)
         }
     }
+
+    /// Registers a callback that is run on every packet before it is parsed
+    pub(crate) fn register_raw_packet_event<F>(&mut self, f: F)
+    where
+        F: Fn(&mut NautSocket<T>, SocketAddr, &[u8]) -> bool + Send + Sync + 'static,
+    {
+        self.raw_packet_callbacks.push(Arc::new(f));
+    }
+
+    /// Runs raw packet callbacks until one consumes the packet, returning whether it was consumed
+    pub(crate) fn emit_raw_packet_events(
+        &self,
+        socket: &mut NautSocket<'socket, T>,
+        addr: SocketAddr,
+        packet: &[u8],
+    ) -> bool {
+        for callback in self.raw_packet_callbacks.iter() {
+            if callback(socket, addr, packet) {
+                return true;
+            }
+        }
+
+        false
+    }
+
+    /// Registers a callback that is run when the persistent of the type changes after the tick
+    pub(crate) fn register_persistent_changed_event<F>(&mut self, type_id: TypeId, tick: u64, f: F)
+    where
+        F: Fn(&mut NautSocket<T>) + Send + Sync + 'static,
+    {
+        self.persistent_changed_callbacks.push(PersistentChangedCallback {
+            type_id,
+            seen_tick: tick,
+            callback: Arc::new(f),
+        });
+    }
+
+    /// Runs the callbacks of every persistent that changed since they were last run
+    pub(crate) fn emit_persistent_changed_events(&mut self, socket: &mut NautSocket<'socket, T>) {
+        for changed in self.persistent_changed_callbacks.iter_mut() {
+            let Some(tick) = socket.persistent.change_tick(changed.type_id) else {
+                continue;
+            };
+
+            if tick == changed.seen_tick {
+                continue;
+            }
+
+            changed.seen_tick = tick;
+            (changed.callback)(socket);
+        }
+    }
 }
 
 impl<'socket, T> Default for EventEmitter<'socket, T>
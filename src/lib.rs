@@ -1,13 +1,22 @@
 mod acknowledgement;
 pub mod client;
+#[cfg(feature = "serde")]
+pub mod codec;
 mod connection;
-mod events;
+pub mod details;
+pub mod events;
+pub mod master;
+#[cfg(feature = "serde")]
+pub mod message;
 pub mod packet;
 mod sequence;
 pub mod server;
 pub mod socket;
 pub mod plugins;
 pub mod persistent;
+pub mod protocol;
+pub mod replication;
+pub mod rpc;
 
 /// Gives you access to everything you need to create an event listening socket
 pub mod prelude {
@@ -16,7 +25,22 @@ pub mod prelude {
     pub use crate::socket::*;
     pub use crate::packet::*;
     pub use crate::server::config::*;
+    pub use crate::server::rejection::*;
+    pub use crate::server::ban::*;
+    pub use crate::server::rate_limit::*;
     pub use crate::plugins::*;
     pub use crate::plugins::logging::*;
+    pub use crate::plugins::a2s::*;
     pub use crate::persistent::*;
+    pub use crate::persistent::snapshot::*;
+    pub use crate::details::*;
+    pub use crate::events::*;
+    pub use crate::master::*;
+    pub use crate::replication::*;
+    pub use crate::protocol::*;
+    pub use crate::rpc::*;
+    #[cfg(feature = "serde")]
+    pub use crate::codec::*;
+    #[cfg(feature = "serde")]
+    pub use crate::message::*;
 }
@@ -0,0 +1,785 @@
+use std::{
+    collections::HashMap,
+    net::{SocketAddr, ToSocketAddrs},
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use anyhow::anyhow;
+use byteorder::{ByteOrder, LittleEndian};
+
+use crate::{
+    client::{ClientEvent, NautClient},
+    packet::{IntoPacketDelivery, PacketDelivery},
+    persistent::Persistent,
+    plugins::SocketPlugin,
+    server::{config::ServerConfig, reflection::ReflectionGuard, NautServer},
+    socket::{events::SocketEvent, NautSocket},
+};
+
+/// Sent by a game server to register itself, and periodically afterwards as a heartbeat
+pub const MASTER_REGISTER_EVENT: &str = "naut::master::register";
+/// Sent by a game server to remove itself from the master server
+pub const MASTER_UNREGISTER_EVENT: &str = "naut::master::unregister";
+/// Sent by a client to query the master server for a filtered list of servers
+pub const MASTER_QUERY_EVENT: &str = "naut::master::query";
+/// Sent by the master server to a client with a page of the servers matching its query
+pub const MASTER_LIST_EVENT: &str = "naut::master::list";
+
+/// The most bytes of entries sent in a single page of a server list, keeping each packet within
+/// the size the socket polls
+const MAX_PAGE_SIZE: usize = 900;
+
+/// A server registered with the [master server](NautMasterServer)
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct MasterServerEntry {
+    /// The address the game server registered from
+    pub addr: SocketAddr,
+    /// The name of the game server
+    pub name: String,
+    /// The region the game server is hosted in
+    pub region: String,
+    /// Application defined tags such as the game mode
+    pub tags: Vec<String>,
+    /// The amount of established connections on the game server
+    pub current_connections: u32,
+    /// The max amount of connections the game server will process
+    pub max_connections: u32,
+}
+
+impl MasterServerEntry {
+    /// Writes the entry to the end of the buffer
+    fn write(&self, buf: &mut Vec<u8>) {
+        write_string(buf, &self.addr.to_string());
+        write_string(buf, &self.name);
+        write_string(buf, &self.region);
+        write_strings(buf, &self.tags);
+        write_u32(buf, self.current_connections);
+        write_u32(buf, self.max_connections);
+    }
+
+    /// Reads an entry from the front of the buffer, advancing it past the entry
+    fn read(buf: &mut &[u8]) -> anyhow::Result<Self> {
+        Ok(Self {
+            addr: SocketAddr::from_str(&read_string(buf)?)?,
+            name: read_string(buf)?,
+            region: read_string(buf)?,
+            tags: read_strings(buf)?,
+            current_connections: read_u32(buf)?,
+            max_connections: read_u32(buf)?,
+        })
+    }
+}
+
+/// How a game server describes itself to the [master server](NautMasterServer), the name and
+/// connection counts are filled in from the [server](NautServer) on every heartbeat
+#[derive(Clone, Debug, Default)]
+pub struct MasterServerRegistration {
+    /// The region the game server is hosted in
+    pub region: String,
+    /// Application defined tags such as the game mode
+    pub tags: Vec<String>,
+}
+
+/// Narrows down the servers returned by a [master server](NautMasterServer) query
+#[derive(Clone, Debug, Default)]
+pub struct MasterServerFilter {
+    /// Only servers that have every one of these tags
+    pub tags: Vec<String>,
+    /// Only servers in this region
+    pub region: Option<String>,
+    /// Only servers with at least this many connections
+    pub min_connections: Option<u32>,
+    /// Only servers with at most this many connections
+    pub max_connections: Option<u32>,
+    /// Leave out servers that cannot take any more connections
+    pub hide_full: bool,
+}
+
+impl MasterServerFilter {
+    /// Checks if the entry passes the filter
+    pub fn matches(&self, entry: &MasterServerEntry) -> bool {
+        if !self.tags.iter().all(|tag| entry.tags.contains(tag)) {
+            return false;
+        }
+
+        if self
+            .region
+            .as_ref()
+            .is_some_and(|region| *region != entry.region)
+        {
+            return false;
+        }
+
+        if self
+            .min_connections
+            .is_some_and(|min| entry.current_connections < min)
+        {
+            return false;
+        }
+
+        if self
+            .max_connections
+            .is_some_and(|max| entry.current_connections > max)
+        {
+            return false;
+        }
+
+        !(self.hide_full && entry.current_connections >= entry.max_connections)
+    }
+
+    /// Writes the filter to the end of the buffer
+    fn write(&self, buf: &mut Vec<u8>) {
+        write_strings(buf, &self.tags);
+        write_string(buf, self.region.as_deref().unwrap_or_default());
+        write_u32(buf, self.min_connections.unwrap_or(0));
+        write_u32(buf, self.max_connections.unwrap_or(u32::MAX));
+        buf.push(self.hide_full as u8);
+    }
+
+    /// Reads a filter from the front of the buffer, advancing it past the filter
+    fn read(buf: &mut &[u8]) -> anyhow::Result<Self> {
+        let tags = read_strings(buf)?;
+        let region = read_string(buf)?;
+        let min_connections = read_u32(buf)?;
+        let max_connections = read_u32(buf)?;
+        let Some((hide_full, _)) = buf.split_first() else {
+            return Err(anyhow!("Master server filter is missing hide full"));
+        };
+
+        Ok(Self {
+            tags,
+            region: (!region.is_empty()).then_some(region),
+            min_connections: (min_connections > 0).then_some(min_connections),
+            max_connections: (max_connections < u32::MAX).then_some(max_connections),
+            hide_full: *hide_full != 0,
+        })
+    }
+}
+
+/// The servers registered with the master server and when each last sent a heartbeat
+#[derive(Default)]
+pub struct MasterServerRegistry {
+    entries: HashMap<SocketAddr, (MasterServerEntry, Instant)>,
+}
+
+impl Persistent for MasterServerRegistry {
+    const SNAPSHOT_NAME: Option<&'static str> = Some("naut::master::registry");
+
+    fn write_snapshot(&self, buf: &mut Vec<u8>) {
+        write_u32(buf, self.entries.len() as u32);
+        for entry in self.iter() {
+            entry.write(buf);
+        }
+    }
+
+    /// Restored servers are listed for another entry lifetime, giving them time to heartbeat
+    fn read_snapshot(mut buf: &[u8]) -> anyhow::Result<Self> {
+        let count = read_u32(&mut buf)?;
+        let mut entries = HashMap::new();
+        for _ in 0..count {
+            let entry = MasterServerEntry::read(&mut buf)?;
+            entries.insert(entry.addr, (entry, Instant::now()));
+        }
+
+        Ok(Self { entries })
+    }
+}
+
+impl MasterServerRegistry {
+    /// Gets an iterator to every registered server
+    pub fn iter(&self) -> impl Iterator<Item = &MasterServerEntry> {
+        self.entries.values().map(|(entry, _)| entry)
+    }
+
+    /// Gets every registered server that passes the [filter](MasterServerFilter)
+    pub fn filtered(&self, filter: &MasterServerFilter) -> Vec<MasterServerEntry> {
+        self.iter()
+            .filter(|entry| filter.matches(entry))
+            .cloned()
+            .collect()
+    }
+}
+
+/// A plugin that turns a [server](NautServer) into a master server, which game servers register
+/// and heartbeat with and clients query for a filtered list of live servers. Neither establishes
+/// a connection, instead an address must answer a challenge before it is listed or sent a list
+pub struct MasterServerPlugin {
+    /// How long a game server remains listed after its last heartbeat
+    pub entry_lifetime: Duration,
+}
+
+impl Default for MasterServerPlugin {
+    fn default() -> Self {
+        Self {
+            entry_lifetime: Duration::from_secs(30),
+        }
+    }
+}
+
+impl SocketPlugin<'_, NautServer> for MasterServerPlugin {
+    fn register(&self, socket: &mut NautSocket<'_, NautServer>) {
+        socket.init_persistent::<MasterServerRegistry>();
+
+        // Registry traffic is handled before it reaches the connection handling, so game servers
+        // and clients never take up one of our connection slots
+        socket.on_raw_packet(|socket, addr, packet| {
+            let Some((event, delivery, payload)) = read_master_packet(packet) else {
+                return false;
+            };
+
+            // A listed server has already proven its address, anything else must answer a
+            // challenge first so a spoofed address can neither be listed nor sent a list
+            let listed = socket
+                .with_persistent_ref(|registry: &MasterServerRegistry| {
+                    registry.entries.contains_key(&addr)
+                })
+                .unwrap_or_default();
+
+            if !listed && !socket.server().is_address_verified(&addr) {
+                if let Err(e) = socket.send_challenge(addr, packet.len()) {
+                    socket
+                        .socket_events
+                        .push(SocketEvent::SendPacketFail(e.to_string()));
+                }
+
+                return true;
+            }
+
+            // Only acknowledged once accepted, so a challenged packet is resent by the sender
+            if delivery.is_reliable() {
+                if let Err(e) = socket.send_ack_packet(addr, packet) {
+                    socket
+                        .socket_events
+                        .push(SocketEvent::SendPacketFail(e.to_string()));
+                }
+            }
+
+            let result = match event.as_str() {
+                MASTER_REGISTER_EVENT => register_server(socket, addr, &payload),
+                MASTER_UNREGISTER_EVENT => {
+                    socket.update_persistent(|registry: &mut MasterServerRegistry| {
+                        registry.entries.remove(&addr).is_some()
+                    });
+                    Ok(())
+                }
+                _ => answer_query(socket, addr, &payload),
+            };
+
+            if let Err(e) = result {
+                socket
+                    .socket_events
+                    .push(SocketEvent::ReadPacketFail(e.to_string()));
+            }
+
+            true
+        });
+
+        let entry_lifetime = self.entry_lifetime;
+        socket.on_poll(move |socket| {
+            let is_expired = |last_heartbeat: &Instant| last_heartbeat.elapsed() >= entry_lifetime;
+
+            socket.update_persistent(|registry: &mut MasterServerRegistry| {
+                let len = registry.entries.len();
+                registry
+                    .entries
+                    .retain(|_, (_, last_heartbeat)| !is_expired(last_heartbeat));
+                registry.entries.len() != len
+            });
+        });
+    }
+}
+
+/// Reads the event, delivery and payload of a packet if it carries one of the events handled by
+/// the [master server plugin](MasterServerPlugin)
+fn read_master_packet(packet: &[u8]) -> Option<(String, PacketDelivery, Vec<u8>)> {
+    if packet.len() < NautSocket::<NautServer>::PACKET_PADDING {
+        return None;
+    }
+
+    let delivery = NautSocket::<NautServer>::get_delivery_type_from_packet(packet)?;
+    let delivery = PacketDelivery::into_packet_delivery(delivery).ok()?;
+    if !matches!(
+        delivery,
+        PacketDelivery::Unreliable
+            | PacketDelivery::UnreliableSequenced
+            | PacketDelivery::Reliable
+            | PacketDelivery::ReliableSequenced
+    ) {
+        return None;
+    }
+
+    let event = NautSocket::<NautServer>::get_event_from_packet(packet).ok()?;
+    if ![
+        MASTER_REGISTER_EVENT,
+        MASTER_UNREGISTER_EVENT,
+        MASTER_QUERY_EVENT,
+    ]
+    .contains(&event.as_str())
+    {
+        return None;
+    }
+
+    let payload = NautSocket::<NautServer>::get_packet_bytes(packet)?;
+    Some((event, delivery, payload))
+}
+
+/// Lists the game server at the address, or refreshes its entry if it is already listed
+fn register_server(
+    socket: &NautSocket<'_, NautServer>,
+    addr: SocketAddr,
+    mut buf: &[u8],
+) -> anyhow::Result<()> {
+    let entry = MasterServerEntry {
+        addr,
+        name: read_string(&mut buf)?,
+        region: read_string(&mut buf)?,
+        tags: read_strings(&mut buf)?,
+        current_connections: read_u32(&mut buf)?,
+        max_connections: read_u32(&mut buf)?,
+    };
+
+    // A heartbeat only refreshes the entry, it is a change if the entry is new or different
+    socket.update_persistent(|registry: &mut MasterServerRegistry| {
+        let old = registry
+            .entries
+            .insert(addr, (entry.clone(), Instant::now()));
+        old.is_none_or(|(old, _)| old != entry)
+    });
+
+    Ok(())
+}
+
+/// Sends the pages of servers passing the filter of a query back to the address
+fn answer_query(
+    socket: &mut NautSocket<'_, NautServer>,
+    addr: SocketAddr,
+    mut buf: &[u8],
+) -> anyhow::Result<()> {
+    let query_id = read_u32(&mut buf)?;
+    let filter = MasterServerFilter::read(&mut buf)?;
+
+    let entries = socket
+        .with_persistent_ref(|registry: &MasterServerRegistry| registry.filtered(&filter))
+        .unwrap_or_default();
+
+    let pages = list_pages(&entries);
+    let page_count = pages.len() as u16;
+    for (page, (count, bytes)) in pages.into_iter().enumerate() {
+        let mut buf = Vec::new();
+        write_u32(&mut buf, query_id);
+        write_u16(&mut buf, page as u16);
+        write_u16(&mut buf, page_count);
+        write_u16(&mut buf, count);
+        buf.extend_from_slice(&bytes);
+
+        let _ = socket.send_by_addr(
+            MASTER_LIST_EVENT,
+            &buf,
+            PacketDelivery::Reliable,
+            addr.to_string(),
+        );
+    }
+
+    Ok(())
+}
+
+/// Splits the entries into pages that each fit into a single packet, returning the amount of
+/// entries in each page alongside its bytes
+fn list_pages(entries: &[MasterServerEntry]) -> Vec<(u16, Vec<u8>)> {
+    let mut pages = vec![(0, Vec::new())];
+    for entry in entries.iter() {
+        let mut bytes = Vec::new();
+        entry.write(&mut bytes);
+
+        let (count, page) = pages.last_mut().expect("There is always a page");
+        if *count > 0 && page.len() + bytes.len() > MAX_PAGE_SIZE {
+            pages.push((1, bytes));
+            continue;
+        }
+
+        *count += 1;
+        page.extend_from_slice(&bytes);
+    }
+
+    pages
+}
+
+/// A master server, just another [server](NautServer) with the
+/// [master server plugin](MasterServerPlugin) registered
+pub struct NautMasterServer<'socket> {
+    socket: NautSocket<'socket, NautServer>,
+}
+
+impl<'socket> NautMasterServer<'socket> {
+    /// Creates a new master server listening on the address
+    pub fn new<A>(addr: A, config: ServerConfig, plugin: MasterServerPlugin) -> anyhow::Result<Self>
+    where
+        A: ToSocketAddrs,
+    {
+        let mut socket = NautSocket::<NautServer>::new(addr, config)?;
+        socket.register_plugin(plugin);
+
+        Ok(Self { socket })
+    }
+
+    /// Polls the socket and runs its events, must be run for the master server to do anything
+    pub fn run(&mut self) {
+        self.socket.poll();
+        self.socket.run_events();
+    }
+
+    /// Gets every server currently registered with the master server
+    pub fn entries(&self) -> Vec<MasterServerEntry> {
+        self.socket
+            .with_persistent_ref(|registry: &MasterServerRegistry| {
+                registry.iter().cloned().collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Reference to the underlying [socket](NautSocket)
+    pub fn socket(&self) -> &NautSocket<'socket, NautServer> {
+        &self.socket
+    }
+
+    /// Mutable reference to the underlying [socket](NautSocket), used to register more plugins
+    /// and events
+    pub fn socket_mut(&mut self) -> &mut NautSocket<'socket, NautServer> {
+        &mut self.socket
+    }
+}
+
+/// When the game server last sent a heartbeat to the master server
+#[derive(Default)]
+pub struct MasterServerHeartbeat {
+    last_sent: Option<Instant>,
+}
+
+impl Persistent for MasterServerHeartbeat {}
+
+/// A plugin that registers a game [server](NautServer) with a master server and keeps it listed
+/// by sending heartbeats
+pub struct MasterServerHeartbeatPlugin {
+    /// The address of the master server
+    pub master_addr: SocketAddr,
+    /// How the game server describes itself
+    pub registration: MasterServerRegistration,
+    /// How often a heartbeat is sent, this must be shorter than the master server's entry
+    /// lifetime
+    pub heartbeat_interval: Duration,
+}
+
+impl MasterServerHeartbeatPlugin {
+    /// Creates a new heartbeat plugin registering with the master server at the address
+    pub fn new(master_addr: SocketAddr, registration: MasterServerRegistration) -> Self {
+        Self {
+            master_addr,
+            registration,
+            heartbeat_interval: Duration::from_secs(10),
+        }
+    }
+}
+
+impl SocketPlugin<'_, NautServer> for MasterServerHeartbeatPlugin {
+    fn register(&self, socket: &mut NautSocket<'_, NautServer>) {
+        socket.init_persistent::<MasterServerHeartbeat>();
+        socket.server_mut().add_outbound_address(self.master_addr);
+
+        // Anything the master server sends back is swallowed rather than establishing it as a
+        // client of ours. A challenge is answered and the heartbeat it dropped is sent again
+        // straight away
+        let master_addr = self.master_addr;
+        socket.on_raw_packet(move |socket, addr, packet| {
+            if addr != master_addr {
+                return false;
+            }
+
+            let is_challenge = NautSocket::<NautServer>::get_delivery_type_from_packet(packet)
+                .is_some_and(|delivery| {
+                    PacketDelivery::into_packet_delivery(delivery)
+                        .is_ok_and(|delivery| delivery == PacketDelivery::challenge())
+                });
+            if !is_challenge {
+                return true;
+            }
+
+            let answer =
+                ReflectionGuard::challenge_answer(packet, NautClient::MAX_CHALLENGE_RESPONSE_SIZE)
+                    .and_then(|answer| Ok(socket.socket().send_to(&answer, addr)?));
+            if let Err(e) = answer {
+                socket
+                    .socket_events
+                    .push(SocketEvent::SendPacketFail(e.to_string()));
+                return true;
+            }
+
+            if let Some(heartbeat) = socket.get_persistent::<MasterServerHeartbeat>() {
+                if let Ok(mut heartbeat) = heartbeat.write() {
+                    heartbeat.last_sent = None;
+                }
+            }
+
+            true
+        });
+
+        let registration = self.registration.clone();
+        let heartbeat_interval = self.heartbeat_interval;
+        socket.on_poll(move |socket| {
+            let Some(heartbeat) = socket.get_persistent::<MasterServerHeartbeat>() else {
+                return;
+            };
+
+            let Ok(mut heartbeat) = heartbeat.write() else {
+                return;
+            };
+
+            if heartbeat
+                .last_sent
+                .is_some_and(|last_sent| last_sent.elapsed() < heartbeat_interval)
+            {
+                return;
+            }
+
+            let server = socket.server();
+            let mut buf = Vec::new();
+            write_string(&mut buf, server.get_server_name());
+            write_string(&mut buf, &registration.region);
+            write_strings(&mut buf, &registration.tags);
+            write_u32(&mut buf, server.get_current_connections() as u32);
+            write_u32(&mut buf, server.get_max_connections() as u32);
+
+            // Heartbeats are sent regularly so there is no need for them to be reliable
+            if let Err(e) = socket.send_by_addr(
+                MASTER_REGISTER_EVENT,
+                &buf,
+                PacketDelivery::Unreliable,
+                master_addr.to_string(),
+            ) {
+                socket
+                    .socket_events
+                    .push(SocketEvent::SendPacketFail(e.to_string()));
+            }
+
+            heartbeat.last_sent = Some(Instant::now());
+        });
+    }
+}
+
+impl NautSocket<'_, NautServer> {
+    /// Removes the game server from the master server at the address straight away, rather than
+    /// waiting for its entry to expire
+    pub fn unregister_from_master_server(&mut self, master_addr: SocketAddr) -> anyhow::Result<()> {
+        self.send_by_addr(
+            MASTER_UNREGISTER_EVENT,
+            &[],
+            PacketDelivery::Unreliable,
+            master_addr.to_string(),
+        )
+    }
+}
+
+/// The servers received from the latest master server query
+#[derive(Default)]
+pub struct MasterServerList {
+    query_id: u32,
+    entries: Vec<MasterServerEntry>,
+    pages_received: u16,
+    page_count: u16,
+}
+
+impl Persistent for MasterServerList {}
+
+impl MasterServerList {
+    /// Gets the servers received so far
+    pub fn entries(&self) -> &[MasterServerEntry] {
+        &self.entries
+    }
+
+    /// The amount of pages the list is sent in, 0 until the first page is received
+    pub fn page_count(&self) -> u16 {
+        self.page_count
+    }
+
+    /// Checks if every page of the list has been received
+    pub fn is_complete(&self) -> bool {
+        self.page_count > 0 && self.pages_received >= self.page_count
+    }
+}
+
+/// A plugin that collects the responses to master server queries into the
+/// [master server list](MasterServerList), pushing a
+/// [master server list event](ClientEvent::OnMasterServerList) once a list is complete
+pub struct MasterServerBrowserPlugin;
+
+impl SocketPlugin<'_, NautClient> for MasterServerBrowserPlugin {
+    fn register(&self, socket: &mut NautSocket<'_, NautClient>) {
+        socket.init_persistent::<MasterServerList>();
+
+        socket.on(MASTER_LIST_EVENT, |socket, ctx| {
+            let Some(list) = socket.get_persistent::<MasterServerList>() else {
+                return;
+            };
+
+            let mut buf = ctx.payload;
+            let page = (|| {
+                let query_id = read_u32(&mut buf)?;
+                let _page = read_u16(&mut buf)?;
+                let page_count = read_u16(&mut buf)?;
+                let count = read_u16(&mut buf)?;
+
+                let mut entries = Vec::with_capacity(count as usize);
+                for _ in 0..count {
+                    entries.push(MasterServerEntry::read(&mut buf)?);
+                }
+
+                anyhow::Ok((query_id, page_count, entries))
+            })();
+
+            let (query_id, page_count, entries) = match page {
+                Ok(page) => page,
+                Err(e) => {
+                    socket
+                        .socket_events
+                        .push(SocketEvent::ReadPacketFail(e.to_string()));
+                    return;
+                }
+            };
+
+            let complete = {
+                let Ok(mut list) = list.write() else {
+                    return;
+                };
+
+                // Pages of an older query are no longer wanted
+                if query_id != list.query_id {
+                    return;
+                }
+
+                list.entries.extend(entries);
+                list.pages_received += 1;
+                list.page_count = page_count;
+                list.is_complete()
+            };
+
+            socket.mark_persistent_changed::<MasterServerList>();
+
+            if complete {
+                socket
+                    .inner
+                    .client_events
+                    .push_back(ClientEvent::OnMasterServerList(ctx.addr));
+            }
+        });
+    }
+}
+
+impl NautSocket<'_, NautClient> {
+    /// Queries the master server at the address for the servers passing the
+    /// [filter](MasterServerFilter), the results are collected by the
+    /// [master server browser plugin](MasterServerBrowserPlugin). The socket must not be
+    /// connected to another server, as the response would not be received
+    pub fn query_master_server(
+        &mut self,
+        master_addr: SocketAddr,
+        filter: &MasterServerFilter,
+    ) -> anyhow::Result<()> {
+        let query_id = {
+            let list = self.get_persistent::<MasterServerList>().ok_or(anyhow!(
+                "The master server browser plugin is not registered"
+            ))?;
+            let Ok(mut list) = list.write() else {
+                return Err(anyhow!("The master server list is poisoned"));
+            };
+
+            *list = MasterServerList {
+                query_id: list.query_id.wrapping_add(1),
+                ..Default::default()
+            };
+
+            list.query_id
+        };
+        self.mark_persistent_changed::<MasterServerList>();
+
+        let mut buf = Vec::new();
+        write_u32(&mut buf, query_id);
+        filter.write(&mut buf);
+
+        self.send_by_addr(
+            MASTER_QUERY_EVENT,
+            &buf,
+            PacketDelivery::Reliable,
+            master_addr.to_string(),
+        )
+    }
+}
+
+/// Writes a little endian u16
+fn write_u16(buf: &mut Vec<u8>, value: u16) {
+    let mut bytes = [0; 2];
+    LittleEndian::write_u16(&mut bytes, value);
+    buf.extend_from_slice(&bytes);
+}
+
+/// Writes a little endian u32
+fn write_u32(buf: &mut Vec<u8>, value: u32) {
+    let mut bytes = [0; 4];
+    LittleEndian::write_u32(&mut bytes, value);
+    buf.extend_from_slice(&bytes);
+}
+
+/// Writes a string prefixed with its length, strings longer than a u16 are cut short
+fn write_string(buf: &mut Vec<u8>, string: &str) {
+    let mut len = string.len().min(u16::MAX as usize);
+    while !string.is_char_boundary(len) {
+        len -= 1;
+    }
+
+    write_u16(buf, len as u16);
+    buf.extend_from_slice(&string.as_bytes()[..len]);
+}
+
+/// Writes a list of strings prefixed with the amount of strings
+fn write_strings(buf: &mut Vec<u8>, strings: &[String]) {
+    write_u16(buf, strings.len().min(u16::MAX as usize) as u16);
+    for string in strings.iter().take(u16::MAX as usize) {
+        write_string(buf, string);
+    }
+}
+
+/// Reads a little endian u16, advancing the buffer past it
+fn read_u16(buf: &mut &[u8]) -> anyhow::Result<u16> {
+    let Some((bytes, rest)) = buf.split_at_checked(2) else {
+        return Err(anyhow!("Packet not large enough for u16"));
+    };
+
+    *buf = rest;
+    Ok(LittleEndian::read_u16(bytes))
+}
+
+/// Reads a little endian u32, advancing the buffer past it
+fn read_u32(buf: &mut &[u8]) -> anyhow::Result<u32> {
+    let Some((bytes, rest)) = buf.split_at_checked(4) else {
+        return Err(anyhow!("Packet not large enough for u32"));
+    };
+
+    *buf = rest;
+    Ok(LittleEndian::read_u32(bytes))
+}
+
+/// Reads a string prefixed with its length, advancing the buffer past it
+fn read_string(buf: &mut &[u8]) -> anyhow::Result<String> {
+    let len = read_u16(buf)? as usize;
+    let Some((bytes, rest)) = buf.split_at_checked(len) else {
+        return Err(anyhow!("Packet not large enough for string"));
+    };
+
+    *buf = rest;
+    Ok(String::from_utf8(bytes.to_vec())?)
+}
+
+/// Reads a list of strings prefixed with the amount of strings, advancing the buffer past it
+fn read_strings(buf: &mut &[u8]) -> anyhow::Result<Vec<String>> {
+    let count = read_u16(buf)?;
+    (0..count).map(|_| read_string(buf)).collect()
+}
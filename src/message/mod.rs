@@ -0,0 +1,86 @@
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    client::{ConnectionId, NautClient},
+    codec::Codec,
+    events::EventContext,
+    packet::PacketDelivery,
+    server::NautServer,
+    socket::{NautSocket, SocketType},
+};
+
+#[cfg(feature = "derive")]
+pub use nautilus_sockets_derive::NautMessage;
+
+/// A type sent as a single event, naming the event, how it is delivered and how it is encoded in
+/// one place so the client and server cannot disagree on them. Usually derived
+///
+/// # Examples
+///
+/// ```ignore
+/// #[derive(Serialize, Deserialize, NautMessage)]
+/// #[naut(event = "move", delivery = ReliableSequenced)]
+/// struct Move {
+///     x: f32,
+///     y: f32,
+/// }
+/// ```
+pub trait NautMessage: Serialize + DeserializeOwned {
+    /// The event the message is sent as
+    const EVENT: &'static str;
+    /// How the message is delivered
+    const DELIVERY: PacketDelivery;
+    /// How the message is encoded
+    type Codec: Codec;
+}
+
+impl<'socket, S> NautSocket<'socket, S>
+where
+    S: SocketType<'socket>,
+{
+    /// Run a function as a callback when the [message](NautMessage) is received, see
+    /// [on typed](Self::on_typed)
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// server.on_msg(|_server, ctx, position: Move| {
+    ///     println!("{} moved to {}, {}", ctx.addr, position.x, position.y);
+    /// });
+    /// ```
+    pub fn on_msg<M, F>(&mut self, cb: F)
+    where
+        M: NautMessage,
+        F: Fn(&mut NautSocket<'socket, S>, EventContext, M) + Send + Sync + 'static,
+    {
+        self.on_typed_with::<M::Codec, M, F>(M::EVENT, cb);
+    }
+}
+
+impl NautSocket<'_, NautClient> {
+    /// Sends the [message](NautMessage) to the server we are connected to
+    pub fn send_msg<M>(&mut self, msg: &M) -> anyhow::Result<()>
+    where
+        M: NautMessage,
+    {
+        self.send_typed_with::<M::Codec, M>(M::EVENT, msg, M::DELIVERY)
+    }
+}
+
+impl NautSocket<'_, NautServer> {
+    /// Sends the [message](NautMessage) to the client
+    pub fn send_msg<M>(&mut self, msg: &M, client: ConnectionId) -> anyhow::Result<()>
+    where
+        M: NautMessage,
+    {
+        self.send_typed_with::<M::Codec, M>(M::EVENT, msg, M::DELIVERY, client)
+    }
+
+    /// Sends the [message](NautMessage) to every established connection
+    pub fn broadcast_msg<M>(&mut self, msg: &M) -> anyhow::Result<()>
+    where
+        M: NautMessage,
+    {
+        self.broadcast_typed_with::<M::Codec, M>(M::EVENT, msg, M::DELIVERY)
+    }
+}
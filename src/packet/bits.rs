@@ -0,0 +1,296 @@
+use std::f32::consts::FRAC_1_SQRT_2;
+
+use anyhow::anyhow;
+
+/// Packs values into as few bits as they need rather than whole bytes, for state sent often such
+/// as positions, rotations and input. Bits fill each byte from the lowest bit up, and the last
+/// byte is padded with zeroes
+///
+/// # Examples
+///
+/// ```
+/// # use nautilus_sockets::packet::{BitReader, BitWriter};
+/// let mut writer = BitWriter::new();
+/// writer
+///     .write_bool(true)
+///     .write_ranged(-3, -8, 8)
+///     .write_quantized(12.34, -100.0, 100.0, 0.01);
+///
+/// // 1 + 5 + 15 bits fit in 3 bytes
+/// assert_eq!(writer.bit_len(), 21);
+/// let payload = writer.into_inner();
+///
+/// let mut reader = BitReader::new(&payload);
+/// assert!(reader.read_bool()?);
+/// assert_eq!(reader.read_ranged(-8, 8)?, -3);
+/// assert!((reader.read_quantized(-100.0, 100.0, 0.01)? - 12.34).abs() <= 0.01);
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Default, Clone, Debug)]
+pub struct BitWriter {
+    buf: Vec<u8>,
+    bits: usize,
+}
+
+/// The bits needed to hold every value up to and including the max
+fn bits_for(max: u64) -> u32 {
+    u64::BITS - max.leading_zeros()
+}
+
+/// The amount of steps between the min and max at the precision
+fn quantized_steps(min: f32, max: f32, precision: f32) -> u64 {
+    assert!(
+        min < max && precision > 0.0,
+        "Quantizing needs a min below the max and a precision above 0"
+    );
+
+    ((max as f64 - min as f64) / precision as f64).ceil() as u64
+}
+
+/// The amount of steps a component of a quaternion is quantized to
+fn quaternion_steps(bits: u32) -> u64 {
+    assert!(
+        (1..=31).contains(&bits),
+        "A quaternion component needs between 1 and 31 bits"
+    );
+
+    (1 << bits) - 1
+}
+
+fn quantize(value: f32, min: f32, max: f32, steps: u64) -> u64 {
+    let value = value.clamp(min, max) as f64;
+    ((value - min as f64) / (max as f64 - min as f64) * steps as f64).round() as u64
+}
+
+fn dequantize(step: u64, min: f32, max: f32, steps: u64) -> f32 {
+    (min as f64 + step as f64 / steps as f64 * (max as f64 - min as f64)) as f32
+}
+
+impl BitWriter {
+    /// Creates an empty writer
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The amount of bits written
+    pub fn bit_len(&self) -> usize {
+        self.bits
+    }
+
+    /// The amount of bytes the bits written take once padded
+    pub fn len(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// Whether nothing has been written
+    pub fn is_empty(&self) -> bool {
+        self.bits == 0
+    }
+
+    /// The bytes written so far
+    pub fn as_slice(&self) -> &[u8] {
+        &self.buf
+    }
+
+    /// Consumes the writer, giving back the bytes written
+    pub fn into_inner(self) -> Vec<u8> {
+        self.buf
+    }
+
+    /// Runs the function on an empty writer, returning how many bits it wrote
+    ///
+    /// ```
+    /// # use nautilus_sockets::packet::BitWriter;
+    /// let bits = BitWriter::measure(|writer| {
+    ///     writer.write_quaternion([0.0, 0.0, 0.0, 1.0], 10);
+    /// });
+    /// assert_eq!(bits, 32);
+    /// ```
+    pub fn measure<F>(f: F) -> usize
+    where
+        F: FnOnce(&mut BitWriter),
+    {
+        let mut writer = BitWriter::new();
+        f(&mut writer);
+        writer.bit_len()
+    }
+
+    /// The bits an integer between the min and max takes when [ranged](Self::write_ranged)
+    pub fn bits_for_range(min: i64, max: i64) -> u32 {
+        assert!(min <= max, "A range needs a min that is not above the max");
+        bits_for(max.abs_diff(min))
+    }
+
+    /// The bits a float between the min and max takes when [quantized](Self::write_quantized)
+    /// at the precision
+    pub fn bits_for_quantized(min: f32, max: f32, precision: f32) -> u32 {
+        bits_for(quantized_steps(min, max, precision))
+    }
+
+    /// The bits a quaternion takes when [compressed](Self::write_quaternion) with the bits per
+    /// component
+    pub fn bits_for_quaternion(bits: u32) -> u32 {
+        quaternion_steps(bits);
+        2 + 3 * bits
+    }
+
+    /// Writes the lowest amount of bits of the value, up to 64
+    pub fn write_bits(&mut self, mut value: u64, mut bits: u32) -> &mut Self {
+        assert!(bits <= u64::BITS, "Cannot write more than 64 bits at once");
+
+        while bits > 0 {
+            let offset = (self.bits % 8) as u32;
+            if offset == 0 {
+                self.buf.push(0);
+            }
+
+            let taken = bits.min(8 - offset);
+            let mask = (1u64 << taken) - 1;
+            if let Some(byte) = self.buf.last_mut() {
+                *byte |= ((value & mask) << offset) as u8;
+            }
+
+            value = value.checked_shr(taken).unwrap_or(0);
+            bits -= taken;
+            self.bits += taken as usize;
+        }
+
+        self
+    }
+
+    /// Writes a bool as a single bit
+    pub fn write_bool(&mut self, value: bool) -> &mut Self {
+        self.write_bits(value as u64, 1)
+    }
+
+    /// Writes an integer in only the bits needed for the range, clamping it into the range
+    pub fn write_ranged(&mut self, value: i64, min: i64, max: i64) -> &mut Self {
+        let bits = Self::bits_for_range(min, max);
+        self.write_bits(value.clamp(min, max).abs_diff(min), bits)
+    }
+
+    /// Writes a float between the min and max rounded to the precision, clamping it into the range
+    pub fn write_quantized(&mut self, value: f32, min: f32, max: f32, precision: f32) -> &mut Self {
+        let steps = quantized_steps(min, max, precision);
+        self.write_bits(quantize(value, min, max, steps), bits_for(steps))
+    }
+
+    /// Writes a unit quaternion as `[x, y, z, w]` with smallest three compression. The largest
+    /// component is left out and rebuilt when read, and the other three are quantized to the bits
+    pub fn write_quaternion(&mut self, quaternion: [f32; 4], bits: u32) -> &mut Self {
+        let steps = quaternion_steps(bits);
+        let largest = (0..4)
+            .max_by(|a, b| quaternion[*a].abs().total_cmp(&quaternion[*b].abs()))
+            .unwrap_or_default();
+
+        // The quaternion and its negation are the same rotation, so the largest is kept positive
+        let sign = if quaternion[largest] < 0.0 { -1.0 } else { 1.0 };
+
+        self.write_bits(largest as u64, 2);
+        for (i, component) in quaternion.iter().enumerate() {
+            if i != largest {
+                let step = quantize(component * sign, -FRAC_1_SQRT_2, FRAC_1_SQRT_2, steps);
+                self.write_bits(step, bits);
+            }
+        }
+
+        self
+    }
+}
+
+impl From<BitWriter> for Vec<u8> {
+    fn from(writer: BitWriter) -> Self {
+        writer.buf
+    }
+}
+
+/// Reads a payload written by a [bit writer](BitWriter), every read is bounds checked and fails
+/// rather than panicking on a short or malformed packet
+#[derive(Clone, Copy, Debug)]
+pub struct BitReader<'a> {
+    buf: &'a [u8],
+    bits: usize,
+}
+
+impl<'a> BitReader<'a> {
+    /// Creates a reader over the bytes
+    pub fn new(buf: &'a [u8]) -> Self {
+        Self { buf, bits: 0 }
+    }
+
+    /// The amount of bits that have not been read yet, including the padding of the last byte
+    pub fn remaining_bits(&self) -> usize {
+        self.buf.len() * 8 - self.bits
+    }
+
+    /// Reads the amount of bits, up to 64
+    pub fn read_bits(&mut self, bits: u32) -> anyhow::Result<u64> {
+        assert!(bits <= u64::BITS, "Cannot read more than 64 bits at once");
+
+        if bits as usize > self.remaining_bits() {
+            return Err(anyhow!("Packet not large enough for {bits} bits"));
+        }
+
+        let mut value = 0;
+        let mut read = 0;
+        while read < bits {
+            let offset = (self.bits % 8) as u32;
+            let taken = (bits - read).min(8 - offset);
+            let mask = (1u64 << taken) - 1;
+
+            value |= ((self.buf[self.bits / 8] as u64 >> offset) & mask) << read;
+            read += taken;
+            self.bits += taken as usize;
+        }
+
+        Ok(value)
+    }
+
+    /// Reads a bool from a single bit
+    pub fn read_bool(&mut self) -> anyhow::Result<bool> {
+        Ok(self.read_bits(1)? == 1)
+    }
+
+    /// Reads an integer written by [write ranged](BitWriter::write_ranged), failing if it is
+    /// outside of the range
+    pub fn read_ranged(&mut self, min: i64, max: i64) -> anyhow::Result<i64> {
+        let offset = self.read_bits(BitWriter::bits_for_range(min, max))?;
+        if offset > max.abs_diff(min) {
+            return Err(anyhow!("Value is outside of the range {min} to {max}"));
+        }
+
+        Ok(min.wrapping_add_unsigned(offset))
+    }
+
+    /// Reads a float written by [write quantized](BitWriter::write_quantized), failing if it is
+    /// outside of the range
+    pub fn read_quantized(&mut self, min: f32, max: f32, precision: f32) -> anyhow::Result<f32> {
+        let steps = quantized_steps(min, max, precision);
+        let step = self.read_bits(bits_for(steps))?;
+        if step > steps {
+            return Err(anyhow!("Value is outside of the range {min} to {max}"));
+        }
+
+        Ok(dequantize(step, min, max, steps))
+    }
+
+    /// Reads a quaternion as `[x, y, z, w]` written by
+    /// [write quaternion](BitWriter::write_quaternion) with the same bits
+    pub fn read_quaternion(&mut self, bits: u32) -> anyhow::Result<[f32; 4]> {
+        let steps = quaternion_steps(bits);
+        let largest = self.read_bits(2)? as usize;
+
+        let mut quaternion = [0.0; 4];
+        let mut sum = 0.0;
+        for (i, component) in quaternion.iter_mut().enumerate() {
+            if i != largest {
+                let step = self.read_bits(bits)?;
+                *component = dequantize(step, -FRAC_1_SQRT_2, FRAC_1_SQRT_2, steps);
+                sum += *component * *component;
+            }
+        }
+
+        quaternion[largest] = (1.0 - sum).max(0.0).sqrt();
+        Ok(quaternion)
+    }
+}
@@ -0,0 +1,180 @@
+use anyhow::{anyhow, Ok};
+
+mod bits;
+mod reader;
+mod writer;
+
+pub use bits::{BitReader, BitWriter};
+pub use reader::NautReader;
+pub use writer::NautWriter;
+
+/// Sent reliably by the [server](crate::server::NautServer) to a client as soon as a connection
+/// is established, carrying the client's [connection token](crate::client::ConnectionToken) in
+/// its header
+pub const CONNECTION_ESTABLISHED_EVENT: &str = "naut::connected";
+
+/// The version of the nautilus protocol, reported to anything requesting the
+/// [server details](crate::details::ServerDetails). Version 2 added the
+/// [connection token](crate::client::ConnectionToken) to the header, growing it from 14 to 22
+/// bytes, so clients built for version 1 cannot talk to a version 2 socket
+pub const PROTOCOL_VERSION: u16 = 2;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) struct SocketDelivery;
+
+/// Describes how a packet will reach its target
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[repr(u16)]
+pub enum PacketDelivery {
+    /// A packet which has no guarantee of reaching its target, and if it doesn't it will be
+    /// forgotten
+    Unreliable = 0,
+    /// A packet which has no guarantee of reaching its target, and will be discarded if not the
+    /// latest packet in sequence or will be forgotten if it does not reach the target
+    UnreliableSequenced = 1,
+    /// A packet which will require an acknowledgement. If the package receives no ack it will be
+    /// resent to the target
+    Reliable = 2,
+    /// A packet which will require an acknowledgement. If the package receives no ack it will be
+    /// resent to the target, however the packet may be discared if not the latest packet
+    ReliableSequenced = 3,
+
+    #[allow(private_interfaces)]
+    /// The packet delivery type for an acknowledgement packet
+    AckDelivery(SocketDelivery) = 10,
+
+    /// In place packet delivery type to request the details of the server without establishing a
+    /// connection
+    #[allow(private_interfaces)]
+    DetailRequest(SocketDelivery) = 11,
+
+    /// The packet delivery type for the response to a detail request
+    #[allow(private_interfaces)]
+    DetailResponse(SocketDelivery) = 12,
+
+    /// The packet delivery type for a probe broadcast over the local network to discover servers
+    #[allow(private_interfaces)]
+    DiscoveryProbe(SocketDelivery) = 13,
+
+    /// The packet delivery type for telling an address why its connection was refused
+    #[allow(private_interfaces)]
+    Rejection(SocketDelivery) = 14,
+
+    /// The packet delivery type for telling a queued client its position in the wait queue, which
+    /// the client echoes back to show it is still waiting
+    #[allow(private_interfaces)]
+    QueuePosition(SocketDelivery) = 15,
+
+    /// The packet delivery type for a challenge a new connection must return before it is
+    /// established, proving it can receive packets at its address
+    #[allow(private_interfaces)]
+    Challenge(SocketDelivery) = 16,
+
+    /// The packet delivery type for returning a challenge, padded to the size the challenge asks
+    #[allow(private_interfaces)]
+    ChallengeResponse(SocketDelivery) = 17,
+}
+
+impl PacketDelivery {
+    /// Creates a packet delivery type for ack since it's a private interface
+    pub(crate) fn ack_delivery() -> Self {
+        Self::AckDelivery(SocketDelivery)
+    }
+
+    /// Creates a packet delivery type for detail request since it's a private interface
+    pub(crate) fn detail_request() -> Self {
+        Self::DetailRequest(SocketDelivery)
+    }
+
+    /// Creates a packet delivery type for detail response since it's a private interface
+    pub(crate) fn detail_response() -> Self {
+        Self::DetailResponse(SocketDelivery)
+    }
+
+    /// Creates a packet delivery type for discovery probe since it's a private interface
+    pub(crate) fn discovery_probe() -> Self {
+        Self::DiscoveryProbe(SocketDelivery)
+    }
+
+    /// Creates a packet delivery type for rejection since it's a private interface
+    pub(crate) fn rejection() -> Self {
+        Self::Rejection(SocketDelivery)
+    }
+
+    /// Creates a packet delivery type for queue position since it's a private interface
+    pub(crate) fn queue_position() -> Self {
+        Self::QueuePosition(SocketDelivery)
+    }
+
+    /// Creates a packet delivery type for challenge since it's a private interface
+    pub(crate) fn challenge() -> Self {
+        Self::Challenge(SocketDelivery)
+    }
+
+    /// Creates a packet delivery type for challenge response since it's a private interface
+    pub(crate) fn challenge_response() -> Self {
+        Self::ChallengeResponse(SocketDelivery)
+    }
+
+    /// Is a reliable delivery type
+    pub fn is_reliable(&self) -> bool {
+        *self == Self::Reliable || *self == Self::ReliableSequenced
+    }
+
+    /// Is an unreliable delivery type
+    pub fn is_unreliable(&self) -> bool {
+        *self == Self::UnreliableSequenced || *self == Self::Unreliable
+    }
+
+    /// Is a sequenced delivery type
+    pub fn is_sequenced(&self) -> bool {
+        *self == Self::ReliableSequenced || *self == Self::UnreliableSequenced
+    }
+}
+
+impl IntoPacketDelivery<u16> for PacketDelivery {
+    fn into_packet_delivery(value: u16) -> anyhow::Result<Self> {
+        match value {
+            0 => Ok(PacketDelivery::Unreliable),
+            1 => Ok(PacketDelivery::UnreliableSequenced),
+            2 => Ok(PacketDelivery::Reliable),
+            3 => Ok(PacketDelivery::ReliableSequenced),
+            10 => Ok(PacketDelivery::ack_delivery()),
+            11 => Ok(PacketDelivery::detail_request()),
+            12 => Ok(PacketDelivery::detail_response()),
+            13 => Ok(PacketDelivery::discovery_probe()),
+            14 => Ok(PacketDelivery::rejection()),
+            15 => Ok(PacketDelivery::queue_position()),
+            16 => Ok(PacketDelivery::challenge()),
+            17 => Ok(PacketDelivery::challenge_response()),
+            _ => Err(anyhow!(
+                "Cannot turn value {value} into type of PacketDelivery"
+            )),
+        }
+    }
+
+    fn packet_delivery_as(&self) -> anyhow::Result<u16> {
+        match self {
+            PacketDelivery::Unreliable => Ok(0),
+            PacketDelivery::UnreliableSequenced => Ok(1),
+            PacketDelivery::Reliable => Ok(2),
+            PacketDelivery::ReliableSequenced => Ok(3),
+            PacketDelivery::AckDelivery(SocketDelivery) => Ok(10),
+            PacketDelivery::DetailRequest(SocketDelivery) => Ok(11),
+            PacketDelivery::DetailResponse(SocketDelivery) => Ok(12),
+            PacketDelivery::DiscoveryProbe(SocketDelivery) => Ok(13),
+            PacketDelivery::Rejection(SocketDelivery) => Ok(14),
+            PacketDelivery::QueuePosition(SocketDelivery) => Ok(15),
+            PacketDelivery::Challenge(SocketDelivery) => Ok(16),
+            PacketDelivery::ChallengeResponse(SocketDelivery) => Ok(17),
+        }
+    }
+}
+
+pub trait IntoPacketDelivery<T> {
+    fn into_packet_delivery(value: T) -> anyhow::Result<Self>
+    where
+        Self: Sized;
+
+    fn packet_delivery_as(&self) -> anyhow::Result<T>;
+}
@@ -0,0 +1,187 @@
+use anyhow::anyhow;
+use byteorder::{ByteOrder, LittleEndian};
+
+/// Reads a payload written by a [writer](super::NautWriter), every read is bounds checked and
+/// fails rather than panicking on a short or malformed packet
+///
+/// # Examples
+///
+/// ```
+/// # use nautilus_sockets::packet::{NautReader, NautWriter};
+/// # let mut writer = NautWriter::new();
+/// # writer.write_u32(4).write_str("nautilus");
+/// # let packet = writer.into_inner();
+/// let mut reader = NautReader::new(&packet);
+/// let id = reader.read_u32()?;
+/// let name = reader.read_str()?;
+/// # assert_eq!((id, name), (4, "nautilus"));
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct NautReader<'a> {
+    buf: &'a [u8],
+}
+
+macro_rules! read_number {
+    ($($fn:ident => $ty:ty, $read:ident;)*) => {
+        $(
+            #[doc = concat!("Reads a little endian ", stringify!($ty))]
+            pub fn $fn(&mut self) -> anyhow::Result<$ty> {
+                Ok(LittleEndian::$read(self.take(size_of::<$ty>(), stringify!($ty))?))
+            }
+        )*
+    };
+}
+
+impl<'a> NautReader<'a> {
+    /// The most bytes a u64 [varint](Self::read_varint) takes
+    const MAX_VARINT_LEN: usize = 10;
+
+    /// Creates a reader over the bytes
+    pub fn new(buf: &'a [u8]) -> Self {
+        Self { buf }
+    }
+
+    /// The bytes that have not been read yet
+    pub fn remaining(&self) -> &'a [u8] {
+        self.buf
+    }
+
+    /// The amount of bytes that have not been read yet
+    pub fn len(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// Whether every byte has been read
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
+    /// Takes the amount of bytes from the front of the buffer, naming what was being read if
+    /// there are not enough
+    fn take(&mut self, len: usize, what: &str) -> anyhow::Result<&'a [u8]> {
+        let Some((bytes, rest)) = self.buf.split_at_checked(len) else {
+            return Err(anyhow!("Packet not large enough for {what}"));
+        };
+
+        self.buf = rest;
+        Ok(bytes)
+    }
+
+    /// Reads a u8
+    pub fn read_u8(&mut self) -> anyhow::Result<u8> {
+        Ok(self.take(1, "u8")?[0])
+    }
+
+    /// Reads an i8
+    pub fn read_i8(&mut self) -> anyhow::Result<i8> {
+        Ok(self.take(1, "i8")?[0] as i8)
+    }
+
+    /// Reads a bool from a single byte, failing if it is neither 0 nor 1
+    pub fn read_bool(&mut self) -> anyhow::Result<bool> {
+        match self.take(1, "bool")?[0] {
+            0 => Ok(false),
+            1 => Ok(true),
+            value => Err(anyhow!("Cannot turn value {value} into a bool")),
+        }
+    }
+
+    read_number! {
+        read_u16 => u16, read_u16;
+        read_u32 => u32, read_u32;
+        read_u64 => u64, read_u64;
+        read_i16 => i16, read_i16;
+        read_i32 => i32, read_i32;
+        read_i64 => i64, read_i64;
+        read_f32 => f32, read_f32;
+        read_f64 => f64, read_f64;
+    }
+
+    /// Reads an unsigned integer written by [write varint](super::NautWriter::write_varint),
+    /// failing if it does not fit in a u64
+    pub fn read_varint(&mut self) -> anyhow::Result<u64> {
+        let mut value = 0u64;
+        for i in 0..Self::MAX_VARINT_LEN {
+            let byte = self
+                .read_u8()
+                .map_err(|_| anyhow!("Packet not large enough for varint"))?;
+            let bits = (byte & 0x7f) as u64;
+
+            // The tenth byte only has room for the top bit of a u64
+            if i == Self::MAX_VARINT_LEN - 1 && bits > 1 {
+                break;
+            }
+
+            value |= bits << (i * 7);
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+
+        Err(anyhow!("Varint does not fit in a u64"))
+    }
+
+    /// Reads a signed integer written by
+    /// [write varint signed](super::NautWriter::write_varint_signed)
+    pub fn read_varint_signed(&mut self) -> anyhow::Result<i64> {
+        let value = self.read_varint()?;
+        Ok((value >> 1) as i64 ^ -((value & 1) as i64))
+    }
+
+    /// Reads the amount of bytes as they are, without a length
+    pub fn read_raw(&mut self, len: usize) -> anyhow::Result<&'a [u8]> {
+        self.take(len, "bytes")
+    }
+
+    /// Reads a length prefix, failing if there are fewer bytes left than the length as every
+    /// byte, string character or array value takes at least a byte
+    fn read_len(&mut self, what: &str) -> anyhow::Result<usize> {
+        let len = self.read_varint()?;
+        match usize::try_from(len) {
+            Ok(len) if len <= self.buf.len() => Ok(len),
+            _ => Err(anyhow!(
+                "Packet not large enough for {what} of length {len}"
+            )),
+        }
+    }
+
+    /// Reads bytes prefixed with their length
+    pub fn read_bytes(&mut self) -> anyhow::Result<&'a [u8]> {
+        let len = self.read_len("bytes")?;
+        self.take(len, "bytes")
+    }
+
+    /// Reads a string prefixed with its length, failing if it is not utf8
+    pub fn read_str(&mut self) -> anyhow::Result<&'a str> {
+        let len = self.read_len("string")?;
+        Ok(std::str::from_utf8(self.take(len, "string")?)?)
+    }
+
+    /// Reads values prefixed with the amount of values, each read by the function
+    pub fn read_array<T, F>(&mut self, mut f: F) -> anyhow::Result<Vec<T>>
+    where
+        F: FnMut(&mut Self) -> anyhow::Result<T>,
+    {
+        // The length comes from the packet, so it is checked against the bytes left before
+        // anything is allocated for it
+        let len = self.read_len("array")?;
+        let mut values = Vec::with_capacity(len);
+        for _ in 0..len {
+            values.push(f(self)?);
+        }
+
+        Ok(values)
+    }
+
+    /// Reads whether there is a value, followed by the value read by the function if there is one
+    pub fn read_option<T, F>(&mut self, f: F) -> anyhow::Result<Option<T>>
+    where
+        F: FnOnce(&mut Self) -> anyhow::Result<T>,
+    {
+        match self.read_bool()? {
+            true => Ok(Some(f(self)?)),
+            false => Ok(None),
+        }
+    }
+}
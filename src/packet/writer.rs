@@ -0,0 +1,161 @@
+use byteorder::{ByteOrder, LittleEndian};
+
+/// Builds a payload from little endian primitives, with strings, bytes and arrays prefixed by
+/// their length as a [varint](NautWriter::write_varint)
+///
+/// # Examples
+///
+/// ```
+/// # use nautilus_sockets::packet::NautWriter;
+/// let mut writer = NautWriter::new();
+/// writer.write_u32(4).write_str("nautilus").write_option(Some(&2.5), |writer, x| {
+///     writer.write_f32(*x);
+/// });
+///
+/// let payload = writer.into_inner();
+/// ```
+#[derive(Default, Clone, Debug)]
+pub struct NautWriter {
+    buf: Vec<u8>,
+}
+
+macro_rules! write_number {
+    ($($fn:ident => $ty:ty, $write:ident;)*) => {
+        $(
+            #[doc = concat!("Writes a little endian ", stringify!($ty))]
+            pub fn $fn(&mut self, value: $ty) -> &mut Self {
+                let mut bytes = [0; size_of::<$ty>()];
+                LittleEndian::$write(&mut bytes, value);
+                self.buf.extend_from_slice(&bytes);
+                self
+            }
+        )*
+    };
+}
+
+impl NautWriter {
+    /// Creates an empty writer
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates an empty writer that can hold the amount of bytes without reallocating
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            buf: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// The bytes written so far
+    pub fn as_slice(&self) -> &[u8] {
+        &self.buf
+    }
+
+    /// Consumes the writer, giving back the bytes written
+    pub fn into_inner(self) -> Vec<u8> {
+        self.buf
+    }
+
+    /// The amount of bytes written
+    pub fn len(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// Whether nothing has been written
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
+    /// Writes a u8
+    pub fn write_u8(&mut self, value: u8) -> &mut Self {
+        self.buf.push(value);
+        self
+    }
+
+    /// Writes an i8
+    pub fn write_i8(&mut self, value: i8) -> &mut Self {
+        self.write_u8(value as u8)
+    }
+
+    /// Writes a bool as a single byte
+    pub fn write_bool(&mut self, value: bool) -> &mut Self {
+        self.write_u8(value as u8)
+    }
+
+    write_number! {
+        write_u16 => u16, write_u16;
+        write_u32 => u32, write_u32;
+        write_u64 => u64, write_u64;
+        write_i16 => i16, write_i16;
+        write_i32 => i32, write_i32;
+        write_i64 => i64, write_i64;
+        write_f32 => f32, write_f32;
+        write_f64 => f64, write_f64;
+    }
+
+    /// Writes an unsigned integer in as few bytes as it needs, seven bits to a byte
+    pub fn write_varint(&mut self, mut value: u64) -> &mut Self {
+        while value >= 0x80 {
+            self.buf.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+
+        self.write_u8(value as u8)
+    }
+
+    /// Writes a signed integer as a [varint](Self::write_varint), zigzag encoded so small
+    /// negative numbers stay small
+    pub fn write_varint_signed(&mut self, value: i64) -> &mut Self {
+        self.write_varint(((value << 1) ^ (value >> 63)) as u64)
+    }
+
+    /// Writes the bytes as they are, without a length
+    pub fn write_raw(&mut self, bytes: &[u8]) -> &mut Self {
+        self.buf.extend_from_slice(bytes);
+        self
+    }
+
+    /// Writes the bytes prefixed with their length
+    pub fn write_bytes(&mut self, bytes: &[u8]) -> &mut Self {
+        self.write_varint(bytes.len() as u64).write_raw(bytes)
+    }
+
+    /// Writes the string prefixed with its length
+    pub fn write_str(&mut self, string: &str) -> &mut Self {
+        self.write_bytes(string.as_bytes())
+    }
+
+    /// Writes the values prefixed with the amount of values, each written by the function. Every
+    /// value has to write at least a byte, as a reader refuses more values than bytes left
+    pub fn write_array<T, F>(&mut self, values: &[T], mut f: F) -> &mut Self
+    where
+        F: FnMut(&mut Self, &T),
+    {
+        self.write_varint(values.len() as u64);
+        for value in values {
+            f(self, value);
+        }
+
+        self
+    }
+
+    /// Writes whether there is a value as a single byte, followed by the value written by the
+    /// function if there is one
+    pub fn write_option<T, F>(&mut self, value: Option<&T>, f: F) -> &mut Self
+    where
+        F: FnOnce(&mut Self, &T),
+    {
+        self.write_bool(value.is_some());
+        if let Some(value) = value {
+            f(self, value);
+        }
+
+        self
+    }
+}
+
+impl From<NautWriter> for Vec<u8> {
+    fn from(writer: NautWriter) -> Self {
+        writer.buf
+    }
+}
@@ -1,5 +1,26 @@
 use std::any::Any;
 
+use anyhow::anyhow;
+
+pub mod snapshot;
 pub mod storage;
 
-pub trait Persistent: Any + Send + Sync {}
+/// State shared between the callbacks and plugins of a socket. A persistent opts into being saved
+/// to the [snapshot](snapshot::SnapshotConfig) by giving a [snapshot name](Self::SNAPSHOT_NAME)
+/// and implementing both snapshot methods
+pub trait Persistent: Any + Send + Sync {
+    /// The name the persistent is saved under in the snapshot, which must be unique and stay the
+    /// same across versions. A persistent without one is never saved
+    const SNAPSHOT_NAME: Option<&'static str> = None;
+
+    /// Writes the persistent to the end of the buffer to be saved in the snapshot
+    fn write_snapshot(&self, _buf: &mut Vec<u8>) {}
+
+    /// Reads the persistent from the bytes written by [write snapshot](Self::write_snapshot)
+    fn read_snapshot(_buf: &[u8]) -> anyhow::Result<Self>
+    where
+        Self: Sized,
+    {
+        Err(anyhow!("Persistent does not support snapshots"))
+    }
+}
@@ -0,0 +1,161 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use anyhow::anyhow;
+use byteorder::{ByteOrder, LittleEndian};
+
+/// The config of where and how often the [persistents](super::Persistent) that opted into
+/// snapshots are saved, they are restored from the file as they are initialised
+#[derive(Clone, Debug)]
+pub struct SnapshotConfig {
+    /// The file the snapshot is restored from and saved to
+    pub path: PathBuf,
+    /// How often the snapshot is saved while the socket runs its events, only saved on demand and
+    /// when the socket is dropped if none
+    pub interval: Option<Duration>,
+}
+
+impl SnapshotConfig {
+    /// Creates a config saving to the file every minute
+    pub fn new<P>(path: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        Self {
+            path: path.into(),
+            interval: Some(Duration::from_secs(60)),
+        }
+    }
+}
+
+/// A snapshot file and the persistents restored from it
+pub(crate) struct SnapshotFile {
+    config: SnapshotConfig,
+    last_saved: Instant,
+    /// Persistents read from the file, kept so a persistent that is never initialised is not lost
+    /// from the file on the next save
+    restored: HashMap<String, Vec<u8>>,
+}
+
+impl SnapshotFile {
+    /// The first bytes of every snapshot file
+    const MAGIC: &'static [u8] = b"NAUTSNAP";
+    /// The version of the snapshot format, files of any other version are refused
+    const FORMAT_VERSION: u16 = 1;
+
+    /// Opens the snapshot file, restoring its persistents if it exists
+    pub(crate) fn open(config: SnapshotConfig) -> anyhow::Result<Self> {
+        let restored = match config.path.exists() {
+            true => Self::read(&fs::read(&config.path)?)?,
+            false => HashMap::new(),
+        };
+
+        Ok(Self {
+            config,
+            last_saved: Instant::now(),
+            restored,
+        })
+    }
+
+    /// Gets the saved bytes of the persistent with the name
+    pub(crate) fn restored(&self, name: &str) -> Option<&[u8]> {
+        self.restored.get(name).map(Vec::as_slice)
+    }
+
+    /// Forgets the saved bytes of the persistent with the name, so it is not saved again
+    pub(crate) fn forget(&mut self, name: &str) {
+        self.restored.remove(name);
+    }
+
+    /// Checks if the [interval](SnapshotConfig::interval) has passed since the last save
+    pub(crate) fn is_due(&self) -> bool {
+        self.config
+            .interval
+            .is_some_and(|interval| self.last_saved.elapsed() >= interval)
+    }
+
+    /// Saves the persistents alongside any restored ones that were never initialised. The file is
+    /// written to a temporary file first so a failed save never leaves a partial snapshot behind
+    pub(crate) fn save(&mut self, persistents: Vec<(&str, Vec<u8>)>) -> anyhow::Result<()> {
+        self.last_saved = Instant::now();
+
+        let mut entries: HashMap<&str, &[u8]> = self
+            .restored
+            .iter()
+            .map(|(name, bytes)| (name.as_str(), bytes.as_slice()))
+            .collect();
+        for (name, bytes) in persistents.iter() {
+            entries.insert(name, bytes);
+        }
+
+        let mut buf = Self::MAGIC.to_vec();
+        Self::write_u16(&mut buf, Self::FORMAT_VERSION);
+        Self::write_u32(&mut buf, entries.len() as u32);
+        for (name, bytes) in entries {
+            Self::write_u32(&mut buf, name.len() as u32);
+            buf.extend_from_slice(name.as_bytes());
+            Self::write_u32(&mut buf, bytes.len() as u32);
+            buf.extend_from_slice(bytes);
+        }
+
+        let temp_path = self.config.path.with_extension("tmp");
+        fs::write(&temp_path, buf)?;
+        fs::rename(temp_path, &self.config.path)?;
+
+        Ok(())
+    }
+
+    /// Reads every persistent from the contents of a snapshot file
+    fn read(mut buf: &[u8]) -> anyhow::Result<HashMap<String, Vec<u8>>> {
+        let Some(rest) = buf.strip_prefix(Self::MAGIC) else {
+            return Err(anyhow!("Not a snapshot file"));
+        };
+        buf = rest;
+
+        let version = LittleEndian::read_u16(Self::take(&mut buf, 2)?);
+        if version != Self::FORMAT_VERSION {
+            return Err(anyhow!(
+                "Snapshot format version {version} is not supported, expected {}",
+                Self::FORMAT_VERSION
+            ));
+        }
+
+        let count = LittleEndian::read_u32(Self::take(&mut buf, 4)?);
+        let mut restored = HashMap::new();
+        for _ in 0..count {
+            let name_len = LittleEndian::read_u32(Self::take(&mut buf, 4)?) as usize;
+            let name = String::from_utf8(Self::take(&mut buf, name_len)?.to_vec())?;
+            let len = LittleEndian::read_u32(Self::take(&mut buf, 4)?) as usize;
+            restored.insert(name, Self::take(&mut buf, len)?.to_vec());
+        }
+
+        Ok(restored)
+    }
+
+    /// Takes the amount of bytes from the front of the buffer
+    fn take<'a>(buf: &mut &'a [u8], len: usize) -> anyhow::Result<&'a [u8]> {
+        if buf.len() < len {
+            return Err(anyhow!("Snapshot file is truncated"));
+        }
+
+        let (taken, rest) = buf.split_at(len);
+        *buf = rest;
+        Ok(taken)
+    }
+
+    fn write_u16(buf: &mut Vec<u8>, value: u16) {
+        let mut bytes = [0; 2];
+        LittleEndian::write_u16(&mut bytes, value);
+        buf.extend_from_slice(&bytes);
+    }
+
+    fn write_u32(buf: &mut Vec<u8>, value: u32) {
+        let mut bytes = [0; 4];
+        LittleEndian::write_u32(&mut bytes, value);
+        buf.extend_from_slice(&bytes);
+    }
+}
@@ -1,51 +1,323 @@
 use std::{
-    any::TypeId,
+    any::{Any, TypeId},
     collections::HashMap,
-    sync::{Arc, RwLock},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, PoisonError, RwLock,
+    },
 };
 
-use super::Persistent;
+use anyhow::anyhow;
 
+use super::{
+    snapshot::{SnapshotConfig, SnapshotFile},
+    Persistent,
+};
+
+/// Writes a stored persistent for the snapshot
+type SnapshotWriter = fn(&(dyn Any + Send + Sync)) -> Vec<u8>;
+
+/// A stored [persistent](Persistent) alongside the tick it was last changed on
+struct PersistentEntry {
+    value: Arc<dyn Any + Send + Sync>,
+    changed: AtomicU64,
+    /// The snapshot name and writer of persistents that opted into snapshots
+    snapshot: Option<(&'static str, SnapshotWriter)>,
+}
+
+/// Holds one [persistent](Persistent) value of each type behind its own lock. A lock poisoned by a
+/// callback panicking while holding it is recovered by the scoped helpers, as the value is still
+/// there even if the callback left it half updated.
+///
+/// Every write through the storage stamps the persistent with the next change tick, unless it is
+/// an [update](Self::update_persistent) reporting nothing changed. Writes made directly through
+/// the lock of [get persistent](Self::get_persistent) are only seen once they are
+/// [marked as changed](Self::mark_changed).
+///
+/// With [snapshots enabled](Self::enable_snapshots) persistents are restored as they are
+/// initialised, and saved once more when the storage is dropped
 pub(crate) struct PersistentStorage {
-    pub data: HashMap<TypeId, Arc<RwLock<dyn Persistent>>>,
+    data: HashMap<TypeId, PersistentEntry>,
+    tick: AtomicU64,
+    snapshot: Option<SnapshotFile>,
 }
 
 impl PersistentStorage {
     pub(crate) fn new() -> PersistentStorage {
         PersistentStorage {
             data: HashMap::new(),
+            tick: AtomicU64::new(0),
+            snapshot: None,
         }
     }
 
     pub(crate) fn get_persistent<P>(&self) -> Option<Arc<RwLock<P>>>
     where
-        P: Persistent + 'static,
+        P: Persistent,
+    {
+        let persistent = Arc::clone(&self.data.get(&TypeId::of::<P>())?.value);
+        persistent.downcast::<RwLock<P>>().ok()
+    }
+
+    pub(crate) fn insert_persistent<P>(&mut self, persistent: P)
+    where
+        P: Persistent,
+    {
+        self.insert_entry(Arc::new(RwLock::new(persistent)));
+    }
+
+    /// Inserts the persistent restored from the snapshot, or its default if it was never saved.
+    /// The default is still inserted if it fails to restore
+    pub(crate) fn init_persistent<P>(&mut self) -> anyhow::Result<()>
+    where
+        P: Persistent + Default,
     {
-        let type_id = TypeId::of::<P>();
-        let persistent = Arc::clone(self.data.get(&type_id)?);
-        let raw: *const RwLock<dyn Persistent> = Arc::into_raw(persistent);
-        let raw: *const RwLock<P> = raw.cast();
+        let (persistent, result) = self.restore_or_default::<P>();
+        self.insert_persistent(persistent);
+        result
+    }
 
-        unsafe {
-            let persistent = Arc::from_raw(raw);
-            Some(persistent)
+    pub(crate) fn remove_persistent<P>(&mut self) -> Option<Arc<RwLock<P>>>
+    where
+        P: Persistent,
+    {
+        if let Some((snapshot, name)) = self.snapshot.as_mut().zip(P::SNAPSHOT_NAME) {
+            snapshot.forget(name);
         }
+
+        let persistent = self.data.remove(&TypeId::of::<P>())?.value;
+        persistent.downcast::<RwLock<P>>().ok()
     }
 
-    pub(crate) fn insert_persistent<P>(&mut self, persistent: P)
+    pub(crate) fn contains_persistent<P>(&self) -> bool
+    where
+        P: Persistent,
+    {
+        self.data.contains_key(&TypeId::of::<P>())
+    }
+
+    /// Gets the persistent, [initialising](Self::init_persistent) it first if there is none
+    pub(crate) fn get_or_init_persistent<P>(&mut self) -> (Arc<RwLock<P>>, anyhow::Result<()>)
+    where
+        P: Persistent + Default,
+    {
+        if let Some(persistent) = self.get_persistent() {
+            return (persistent, Ok(()));
+        }
+
+        let (persistent, result) = self.restore_or_default::<P>();
+        let persistent = Arc::new(RwLock::new(persistent));
+        self.insert_entry(persistent.clone());
+        (persistent, result)
+    }
+
+    pub(crate) fn with_persistent<P, F, R>(&self, f: F) -> Option<R>
+    where
+        P: Persistent,
+        F: FnOnce(&mut P) -> R,
+    {
+        let result = self.write_persistent(f)?;
+        self.mark_changed(TypeId::of::<P>());
+        Some(result)
+    }
+
+    /// Runs the function with the persistent without stamping it with a change tick
+    fn write_persistent<P, F, R>(&self, f: F) -> Option<R>
+    where
+        P: Persistent,
+        F: FnOnce(&mut P) -> R,
+    {
+        let persistent = self.get_persistent::<P>()?;
+        let mut persistent = persistent.write().unwrap_or_else(PoisonError::into_inner);
+        Some(f(&mut persistent))
+    }
+
+    /// Runs the function with the persistent, stamping it with the next change tick if the
+    /// function reports it changed
+    pub(crate) fn update_persistent<P, F>(&self, f: F) -> Option<bool>
+    where
+        P: Persistent,
+        F: FnOnce(&mut P) -> bool,
+    {
+        let changed = self.write_persistent(f)?;
+        if changed {
+            self.mark_changed(TypeId::of::<P>());
+        }
+
+        Some(changed)
+    }
+
+    pub(crate) fn with_persistent_ref<P, F, R>(&self, f: F) -> Option<R>
     where
         P: Persistent,
+        F: FnOnce(&P) -> R,
     {
-        let type_id = TypeId::of::<P>();
-        self.data.insert(type_id, Arc::new(RwLock::new(persistent)));
+        let persistent = self.get_persistent::<P>()?;
+        let persistent = persistent.read().unwrap_or_else(PoisonError::into_inner);
+        Some(f(&persistent))
+    }
+
+    /// Stamps the persistent of the type with the next change tick
+    pub(crate) fn mark_changed(&self, type_id: TypeId) {
+        let Some(entry) = self.data.get(&type_id) else {
+            return;
+        };
+
+        entry.changed.store(self.next_tick(), Ordering::Relaxed);
+    }
+
+    /// Gets the tick the persistent of the type was last changed on
+    pub(crate) fn change_tick(&self, type_id: TypeId) -> Option<u64> {
+        Some(self.data.get(&type_id)?.changed.load(Ordering::Relaxed))
+    }
+
+    /// Opens the snapshot file, restoring persistents from it as they are initialised
+    pub(crate) fn enable_snapshots(&mut self, config: SnapshotConfig) -> anyhow::Result<()> {
+        self.snapshot = Some(SnapshotFile::open(config)?);
+        Ok(())
+    }
+
+    /// Saves every persistent that opted into snapshots to the snapshot file
+    pub(crate) fn save_snapshot(&mut self) -> anyhow::Result<()> {
+        let Some(snapshot) = self.snapshot.as_mut() else {
+            return Err(anyhow!("Snapshots are not enabled"));
+        };
+
+        let persistents = self
+            .data
+            .values()
+            .filter_map(|entry| {
+                let (name, writer) = entry.snapshot?;
+                Some((name, writer(&*entry.value)))
+            })
+            .collect();
+
+        snapshot.save(persistents)
+    }
+
+    /// Saves the snapshot if its interval has passed
+    pub(crate) fn save_snapshot_if_due(&mut self) -> anyhow::Result<()> {
+        if !self.snapshot.as_ref().is_some_and(SnapshotFile::is_due) {
+            return Ok(());
+        }
+
+        self.save_snapshot()
     }
 
-    pub(crate) fn init_persistent<P>(&mut self)
+    /// Reads the persistent from the snapshot, falling back to its default
+    fn restore_or_default<P>(&self) -> (P, anyhow::Result<()>)
     where
         P: Persistent + Default,
     {
-        let type_id = TypeId::of::<P>();
-        self.data
-            .insert(type_id, Arc::new(RwLock::new(P::default())));
+        let Some((name, bytes)) = P::SNAPSHOT_NAME.and_then(|name| {
+            let bytes = self.snapshot.as_ref()?.restored(name)?;
+            Some((name, bytes))
+        }) else {
+            return (P::default(), Ok(()));
+        };
+
+        match P::read_snapshot(bytes) {
+            Ok(persistent) => (persistent, Ok(())),
+            Err(e) => (
+                P::default(),
+                Err(anyhow!("Failed to restore {name} from the snapshot: {e}")),
+            ),
+        }
+    }
+
+    fn insert_entry<P>(&mut self, value: Arc<RwLock<P>>)
+    where
+        P: Persistent,
+    {
+        let entry = PersistentEntry {
+            value,
+            changed: AtomicU64::new(self.next_tick()),
+            snapshot: P::SNAPSHOT_NAME.map(|name| (name, write_snapshot::<P> as SnapshotWriter)),
+        };
+
+        self.data.insert(TypeId::of::<P>(), entry);
+    }
+
+    fn next_tick(&self) -> u64 {
+        self.tick.fetch_add(1, Ordering::Relaxed) + 1
+    }
+}
+
+impl Drop for PersistentStorage {
+    fn drop(&mut self) {
+        if self.snapshot.is_some() {
+            let _ = self.save_snapshot();
+        }
+    }
+}
+
+/// Writes the stored persistent of the type for the snapshot
+fn write_snapshot<P>(value: &(dyn Any + Send + Sync)) -> Vec<u8>
+where
+    P: Persistent,
+{
+    let mut buf = Vec::new();
+    if let Some(persistent) = value.downcast_ref::<RwLock<P>>() {
+        let persistent = persistent.read().unwrap_or_else(PoisonError::into_inner);
+        persistent.write_snapshot(&mut buf);
+    }
+
+    buf
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct Counter(u32);
+
+    impl Persistent for Counter {}
+
+    #[test]
+    fn writes_move_the_change_tick_unless_an_update_reports_no_change() {
+        let mut storage = PersistentStorage::new();
+        storage.insert_persistent(Counter::default());
+        let tick = || storage.change_tick(TypeId::of::<Counter>()).unwrap();
+
+        let first_tick = tick();
+        storage.with_persistent(|counter: &mut Counter| counter.0 += 1);
+        let written_tick = tick();
+        assert!(written_tick > first_tick);
+
+        // Reading never moves it
+        storage.with_persistent_ref(|counter: &Counter| counter.0);
+        assert_eq!(tick(), written_tick);
+
+        assert_eq!(
+            storage.update_persistent(|counter: &mut Counter| counter.0 == 0),
+            Some(false)
+        );
+        assert_eq!(tick(), written_tick);
+
+        assert_eq!(
+            storage.update_persistent(|counter: &mut Counter| {
+                counter.0 += 1;
+                true
+            }),
+            Some(true)
+        );
+        assert!(tick() > written_tick);
+        assert_eq!(
+            storage.with_persistent_ref(|counter: &Counter| counter.0),
+            Some(2)
+        );
+
+        // Writes through the lock are only seen once marked
+        let second_tick = tick();
+        storage
+            .get_persistent::<Counter>()
+            .unwrap()
+            .write()
+            .unwrap()
+            .0 += 1;
+        assert_eq!(tick(), second_tick);
+        storage.mark_changed(TypeId::of::<Counter>());
+        assert!(tick() > second_tick);
     }
 }
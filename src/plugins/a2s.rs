@@ -0,0 +1,415 @@
+use std::{
+    collections::hash_map::RandomState,
+    hash::BuildHasher,
+    net::SocketAddr,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use byteorder::{ByteOrder, LittleEndian};
+
+use crate::{persistent::Persistent, server::NautServer, socket::NautSocket};
+
+use super::SocketPlugin;
+
+/// Every A2S packet that fits in a single datagram starts with this prefix
+pub const A2S_SINGLE_PACKET_PREFIX: [u8; 4] = [0xFF, 0xFF, 0xFF, 0xFF];
+
+const A2S_INFO_REQUEST: u8 = 0x54;
+const A2S_PLAYER_REQUEST: u8 = 0x55;
+const A2S_RULES_REQUEST: u8 = 0x56;
+const A2S_CHALLENGE_REQUEST: u8 = 0x57;
+
+const A2S_INFO_RESPONSE: u8 = 0x49;
+const A2S_PLAYER_RESPONSE: u8 = 0x44;
+const A2S_RULES_RESPONSE: u8 = 0x45;
+const A2S_CHALLENGE_RESPONSE: u8 = 0x41;
+
+/// The payload every A2S_INFO request carries
+const A2S_INFO_PAYLOAD: &[u8] = b"Source Engine Query\0";
+
+/// The largest response we will send, as we do not split responses over multiple packets
+const A2S_MAX_PACKET_SIZE: usize = 1400;
+
+/// The details of the game reported in an A2S_INFO response, the name and player counts are
+/// filled in from the [server](NautServer)
+#[derive(Clone, Debug)]
+pub struct A2sInfo {
+    /// The map the server has currently loaded
+    pub map: String,
+    /// The name of the folder containing the game files
+    pub folder: String,
+    /// The full name of the game
+    pub game: String,
+    /// The steam application id of the game
+    pub app_id: u16,
+    /// The amount of bots on the server
+    pub bots: u8,
+    /// `b'd'` for a dedicated server, `b'l'` for a non-dedicated server, `b'p'` for a proxy
+    pub server_type: u8,
+    /// `b'l'` for linux, `b'w'` for windows, `b'm'` for mac
+    pub environment: u8,
+    /// Whether the server requires a password
+    pub password_protected: bool,
+    /// Whether the server uses VAC
+    pub vac: bool,
+    /// The version of the game
+    pub version: String,
+    /// The game port of the server, if it should be reported
+    pub port: Option<u16>,
+}
+
+impl Default for A2sInfo {
+    fn default() -> Self {
+        let environment = if cfg!(target_os = "windows") {
+            b'w'
+        } else if cfg!(target_os = "macos") {
+            b'm'
+        } else {
+            b'l'
+        };
+
+        Self {
+            map: String::new(),
+            folder: String::new(),
+            game: String::new(),
+            app_id: 0,
+            bots: 0,
+            server_type: b'd',
+            environment,
+            password_protected: false,
+            vac: false,
+            version: String::from(env!("CARGO_PKG_VERSION")),
+            port: None,
+        }
+    }
+}
+
+/// A player reported in an A2S_PLAYER response
+#[derive(Clone, Debug, Default)]
+pub struct A2sPlayer {
+    /// The name of the player
+    pub name: String,
+    /// The player's score
+    pub score: i32,
+    /// How long the player has been connected
+    pub duration: Duration,
+}
+
+pub(crate) type A2sInfoCallback = dyn Fn(&NautServer) -> A2sInfo + Send + Sync;
+pub(crate) type A2sPlayersCallback = dyn Fn(&NautServer) -> Vec<A2sPlayer> + Send + Sync;
+pub(crate) type A2sRulesCallback = dyn Fn(&NautServer) -> Vec<(String, String)> + Send + Sync;
+
+/// A plugin that answers Source engine A2S queries (A2S_INFO, A2S_PLAYER and A2S_RULES) on the
+/// same socket as the [server](NautServer), so it can be listed in third party server browsers.
+/// Packets are recognised by the [A2S prefix](A2S_SINGLE_PACKET_PREFIX) and never reach the
+/// nautilus packet handling
+///
+/// # Examples
+///
+/// ```ignore
+/// server.register_plugin(
+///     A2sQueryPlugin::new(|_server| A2sInfo {
+///         map: String::from("de_dust2"),
+///         game: String::from("My Game"),
+///         ..Default::default()
+///     })
+///     .with_rules(|_server| vec![(String::from("friendly_fire"), String::from("1"))]),
+/// );
+/// ```
+pub struct A2sQueryPlugin {
+    info: Arc<A2sInfoCallback>,
+    players: Option<Arc<A2sPlayersCallback>>,
+    rules: Option<Arc<A2sRulesCallback>>,
+}
+
+impl A2sQueryPlugin {
+    /// How long a challenge number handed to an address remains valid, a challenge is accepted
+    /// for up to twice as long
+    pub const CHALLENGE_LIFETIME: Duration = Duration::from_secs(30);
+
+    /// Creates a new A2S plugin which answers A2S_INFO with the [game details](A2sInfo)
+    pub fn new<F>(info: F) -> Self
+    where
+        F: Fn(&NautServer) -> A2sInfo + Send + Sync + 'static,
+    {
+        Self {
+            info: Arc::new(info),
+            players: None,
+            rules: None,
+        }
+    }
+
+    /// Answers A2S_PLAYER with the [players](A2sPlayer), otherwise no players are reported
+    pub fn with_players<F>(mut self, players: F) -> Self
+    where
+        F: Fn(&NautServer) -> Vec<A2sPlayer> + Send + Sync + 'static,
+    {
+        self.players = Some(Arc::new(players));
+        self
+    }
+
+    /// Answers A2S_RULES with the rules as name and value pairs, otherwise no rules are reported
+    pub fn with_rules<F>(mut self, rules: F) -> Self
+    where
+        F: Fn(&NautServer) -> Vec<(String, String)> + Send + Sync + 'static,
+    {
+        self.rules = Some(Arc::new(rules));
+        self
+    }
+}
+
+/// Hands out the challenge numbers, a query is only answered once the address proves it can
+/// receive packets by returning its challenge. A challenge is derived from the address and the
+/// current window of time, so nothing is stored for the addresses that query us
+#[derive(Default)]
+pub struct A2sChallenges {
+    /// Seeds the challenges, so they cannot be forged without seeing our packets
+    secret: RandomState,
+}
+
+impl Persistent for A2sChallenges {}
+
+impl A2sChallenges {
+    /// Gets the challenge number for an address in the current window
+    fn challenge_for(&self, addr: SocketAddr) -> u32 {
+        self.challenge(&addr, Self::current_window())
+    }
+
+    /// Checks if the challenge was handed out to the address in this window or the last one
+    fn is_valid(&self, addr: &SocketAddr, challenge: u32) -> bool {
+        let window = Self::current_window();
+        challenge == self.challenge(addr, window)
+            || challenge == self.challenge(addr, window.saturating_sub(1))
+    }
+
+    /// Derives the challenge of an address for a window of time
+    fn challenge(&self, addr: &SocketAddr, window: u64) -> u32 {
+        let challenge = self.secret.hash_one((addr, window)) as u32;
+
+        // -1 is used by clients to request a challenge, so it can never be a valid one
+        if challenge == u32::MAX {
+            return 0;
+        }
+
+        challenge
+    }
+
+    /// Gets the window of time challenges are currently handed out for
+    fn current_window() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_secs() / A2sQueryPlugin::CHALLENGE_LIFETIME.as_secs())
+            .unwrap_or_default()
+    }
+}
+
+impl SocketPlugin<'_, NautServer> for A2sQueryPlugin {
+    fn register(&self, socket: &mut NautSocket<'_, NautServer>) {
+        socket.init_persistent::<A2sChallenges>();
+
+        let info = Arc::clone(&self.info);
+        let players = self.players.clone();
+        let rules = self.rules.clone();
+        socket.on_raw_packet(move |socket, addr, packet| {
+            let Some(request) = packet.strip_prefix(&A2S_SINGLE_PACKET_PREFIX) else {
+                return false;
+            };
+
+            let Some((header, body)) = request.split_first() else {
+                return true;
+            };
+
+            let response = match *header {
+                A2S_INFO_REQUEST => {
+                    let Some(challenge) = body.strip_prefix(A2S_INFO_PAYLOAD) else {
+                        return true;
+                    };
+
+                    challenged_response(socket, addr, challenge, |server| {
+                        info_response(server, &info(server))
+                    })
+                }
+                A2S_PLAYER_REQUEST => challenged_response(socket, addr, body, |server| {
+                    let players = players.as_ref().map(|cb| cb(server)).unwrap_or_default();
+                    players_response(&players)
+                }),
+                A2S_RULES_REQUEST => challenged_response(socket, addr, body, |server| {
+                    let rules = rules.as_ref().map(|cb| cb(server)).unwrap_or_default();
+                    rules_response(&rules)
+                }),
+                A2S_CHALLENGE_REQUEST => challenged_response(socket, addr, &[], |_| Vec::new()),
+                _ => return true,
+            };
+
+            // A challenge goes to an address that has not proven itself, so it is guarded like any
+            // other response to an unverified address
+            match response {
+                Some(Challenged::Response(response)) => {
+                    let _ = socket.socket().send_to(&response, addr);
+                }
+                Some(Challenged::Challenge(challenge)) => {
+                    let _ = socket.send_guarded(addr, &challenge, Some(packet.len()));
+                }
+                None => {}
+            }
+
+            true
+        });
+    }
+}
+
+/// A response to a query, depending on whether the query carried the address' challenge
+enum Challenged {
+    /// The address proved it can receive packets, so it is sent the response
+    Response(Vec<u8>),
+    /// The address is handed its challenge
+    Challenge(Vec<u8>),
+}
+
+/// Builds the response to a query if it carries the address' challenge, otherwise builds a
+/// challenge response handing the address its challenge
+fn challenged_response<F>(
+    socket: &NautSocket<'_, NautServer>,
+    addr: SocketAddr,
+    challenge: &[u8],
+    response: F,
+) -> Option<Challenged>
+where
+    F: FnOnce(&NautServer) -> Vec<u8>,
+{
+    let challenges = socket.get_persistent::<A2sChallenges>()?;
+    let challenges = challenges.read().ok()?;
+
+    if challenge.len() >= 4 && challenges.is_valid(&addr, LittleEndian::read_u32(challenge)) {
+        return Some(Challenged::Response(response(socket.server())));
+    }
+
+    let mut buf = A2S_SINGLE_PACKET_PREFIX.to_vec();
+    buf.push(A2S_CHALLENGE_RESPONSE);
+    write_u32(&mut buf, challenges.challenge_for(addr));
+
+    Some(Challenged::Challenge(buf))
+}
+
+/// Builds an A2S_INFO response
+fn info_response(server: &NautServer, info: &A2sInfo) -> Vec<u8> {
+    let mut buf = A2S_SINGLE_PACKET_PREFIX.to_vec();
+    buf.push(A2S_INFO_RESPONSE);
+    // The protocol version of the source engine
+    buf.push(17);
+    write_string(&mut buf, server.get_server_name());
+    write_string(&mut buf, &info.map);
+    write_string(&mut buf, &info.folder);
+    write_string(&mut buf, &info.game);
+
+    let mut app_id = [0; 2];
+    LittleEndian::write_u16(&mut app_id, info.app_id);
+    buf.extend_from_slice(&app_id);
+
+    // A2S only has room for a byte, so larger servers report themselves as full
+    buf.push(server.get_current_connections().min(u8::MAX as u16) as u8);
+    buf.push(server.get_max_connections().min(u8::MAX as u16) as u8);
+    buf.push(info.bots);
+    buf.push(info.server_type);
+    buf.push(info.environment);
+    buf.push(info.password_protected as u8);
+    buf.push(info.vac as u8);
+    write_string(&mut buf, &info.version);
+
+    // Extra data flag, 0x80 marks that the game port follows
+    match info.port {
+        Some(port) => {
+            buf.push(0x80);
+            let mut port_buf = [0; 2];
+            LittleEndian::write_u16(&mut port_buf, port);
+            buf.extend_from_slice(&port_buf);
+        }
+        None => buf.push(0),
+    }
+
+    buf
+}
+
+/// Builds an A2S_PLAYER response, players that do not fit into a single packet are left out
+fn players_response(players: &[A2sPlayer]) -> Vec<u8> {
+    let mut buf = A2S_SINGLE_PACKET_PREFIX.to_vec();
+    buf.push(A2S_PLAYER_RESPONSE);
+    buf.push(0);
+
+    let mut count: u8 = 0;
+    for player in players.iter() {
+        let mut entry = vec![count];
+        write_string(&mut entry, &player.name);
+        write_u32(&mut entry, player.score as u32);
+        write_u32(&mut entry, player.duration.as_secs_f32().to_bits());
+
+        if count == u8::MAX || buf.len() + entry.len() > A2S_MAX_PACKET_SIZE {
+            break;
+        }
+
+        buf.extend_from_slice(&entry);
+        count += 1;
+    }
+
+    buf[5] = count;
+    buf
+}
+
+/// Builds an A2S_RULES response, rules that do not fit into a single packet are left out
+fn rules_response(rules: &[(String, String)]) -> Vec<u8> {
+    let mut buf = A2S_SINGLE_PACKET_PREFIX.to_vec();
+    buf.push(A2S_RULES_RESPONSE);
+    buf.extend_from_slice(&[0, 0]);
+
+    let mut count: u16 = 0;
+    for (name, value) in rules.iter() {
+        let mut entry = Vec::new();
+        write_string(&mut entry, name);
+        write_string(&mut entry, value);
+
+        if count == u16::MAX || buf.len() + entry.len() > A2S_MAX_PACKET_SIZE {
+            break;
+        }
+
+        buf.extend_from_slice(&entry);
+        count += 1;
+    }
+
+    LittleEndian::write_u16(&mut buf[5..7], count);
+    buf
+}
+
+/// Writes a null terminated string, A2S strings cannot contain nulls so anything after one is
+/// left out
+fn write_string(buf: &mut Vec<u8>, string: &str) {
+    let bytes = string.as_bytes();
+    let end = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+    buf.extend_from_slice(&bytes[..end]);
+    buf.push(0);
+}
+
+/// Writes a little endian u32
+fn write_u32(buf: &mut Vec<u8>, value: u32) {
+    let mut bytes = [0; 4];
+    LittleEndian::write_u32(&mut bytes, value);
+    buf.extend_from_slice(&bytes);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn challenge_is_only_valid_for_its_address() {
+        let challenges = A2sChallenges::default();
+        let addr: SocketAddr = "127.0.0.1:27015".parse().unwrap();
+        let other: SocketAddr = "127.0.0.1:27016".parse().unwrap();
+
+        let challenge = challenges.challenge_for(addr);
+        assert_eq!(challenge, challenges.challenge_for(addr));
+        assert!(challenges.is_valid(&addr, challenge));
+        assert!(!challenges.is_valid(&other, challenge));
+        assert!(!A2sChallenges::default().is_valid(&addr, challenge));
+    }
+}
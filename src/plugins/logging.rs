@@ -29,6 +29,14 @@ impl SocketPlugin<'_, NautServer> for LoggingPlugin {
 
 impl SocketPlugin<'_, NautClient> for LoggingPlugin {
     fn register(&self, socket: &mut crate::prelude::NautSocket<'_, NautClient>) {
+        socket.on_poll(move |client| {
+            let stdout = io::stdout();
+            let mut handle = stdout.lock();
+            for event in client.client().iter_client_events() {
+                let _ = writeln!(handle, "[LOG][CLIENT EVENT] {event:?}");
+            }
+        });
+
         socket.on_poll(move |server| {
             let stdout = io::stdout();
             let mut handle = stdout.lock();
@@ -1,3 +1,4 @@
+pub mod a2s;
 pub mod logging;
 
 use crate::socket::{NautSocket, SocketType};
@@ -0,0 +1,211 @@
+use anyhow::anyhow;
+use byteorder::{ByteOrder, LittleEndian};
+
+use crate::{
+    client::{ConnectionId, NautClient},
+    events::EventContext,
+    packet::PacketDelivery,
+    server::NautServer,
+    socket::{events::SocketEvent, NautSocket, SocketType},
+};
+
+/// An event of a protocol, usually generated from a protocol definition file by
+/// `nautilus-sockets-codegen` alongside matching encoders and decoders for other languages
+pub trait ProtocolEvent: Sized {
+    /// The event the payload is sent as
+    const EVENT: &'static str;
+    /// How the event is delivered
+    const DELIVERY: PacketDelivery;
+
+    /// Writes the payload to the end of the buffer
+    fn encode(&self, buf: &mut Vec<u8>);
+
+    /// Reads the payload from the front of the buffer, advancing it past the payload
+    fn decode(buf: &mut &[u8]) -> anyhow::Result<Self>;
+}
+
+/// A field of a [protocol event](ProtocolEvent). Numbers are little endian, bools are a single
+/// byte, and strings and lists are prefixed with their length as a u32, like the event of a packet
+pub trait ProtocolValue: Sized {
+    /// Writes the value to the end of the buffer
+    fn write(&self, buf: &mut Vec<u8>);
+
+    /// Reads the value from the front of the buffer, advancing it past the value
+    fn read(buf: &mut &[u8]) -> anyhow::Result<Self>;
+}
+
+/// Takes the amount of bytes from the front of the buffer
+fn take<'a>(buf: &mut &'a [u8], len: usize) -> anyhow::Result<&'a [u8]> {
+    let Some((bytes, rest)) = buf.split_at_checked(len) else {
+        return Err(anyhow!("Packet not large enough for {len} bytes"));
+    };
+
+    *buf = rest;
+    Ok(bytes)
+}
+
+/// Writes the length of a string or list. Nothing longer than a u32 could fit into a packet, so
+/// such a length is written as the most a u32 holds and the value fails to decode
+fn write_len(len: usize, buf: &mut Vec<u8>) {
+    u32::try_from(len).unwrap_or(u32::MAX).write(buf);
+}
+
+/// Reads the length of a string or list, failing if there are fewer bytes left than the length
+/// as every character or value takes at least a byte
+fn read_len(buf: &mut &[u8]) -> anyhow::Result<usize> {
+    let len = u32::read(buf)? as usize;
+    match len <= buf.len() {
+        true => Ok(len),
+        false => Err(anyhow!("Packet not large enough for a length of {len}")),
+    }
+}
+
+macro_rules! impl_protocol_number {
+    ($($ty:ty => $write:ident, $read:ident;)*) => {
+        $(
+            impl ProtocolValue for $ty {
+                fn write(&self, buf: &mut Vec<u8>) {
+                    let mut bytes = [0; size_of::<$ty>()];
+                    LittleEndian::$write(&mut bytes, *self);
+                    buf.extend_from_slice(&bytes);
+                }
+
+                fn read(buf: &mut &[u8]) -> anyhow::Result<Self> {
+                    Ok(LittleEndian::$read(take(buf, size_of::<$ty>())?))
+                }
+            }
+        )*
+    };
+}
+
+impl_protocol_number! {
+    u16 => write_u16, read_u16;
+    u32 => write_u32, read_u32;
+    u64 => write_u64, read_u64;
+    i16 => write_i16, read_i16;
+    i32 => write_i32, read_i32;
+    i64 => write_i64, read_i64;
+    f32 => write_f32, read_f32;
+    f64 => write_f64, read_f64;
+}
+
+impl ProtocolValue for u8 {
+    fn write(&self, buf: &mut Vec<u8>) {
+        buf.push(*self);
+    }
+
+    fn read(buf: &mut &[u8]) -> anyhow::Result<Self> {
+        Ok(take(buf, 1)?[0])
+    }
+}
+
+impl ProtocolValue for i8 {
+    fn write(&self, buf: &mut Vec<u8>) {
+        buf.push(*self as u8);
+    }
+
+    fn read(buf: &mut &[u8]) -> anyhow::Result<Self> {
+        Ok(take(buf, 1)?[0] as i8)
+    }
+}
+
+impl ProtocolValue for bool {
+    fn write(&self, buf: &mut Vec<u8>) {
+        buf.push(*self as u8);
+    }
+
+    fn read(buf: &mut &[u8]) -> anyhow::Result<Self> {
+        Ok(take(buf, 1)?[0] != 0)
+    }
+}
+
+impl ProtocolValue for String {
+    fn write(&self, buf: &mut Vec<u8>) {
+        write_len(self.len(), buf);
+        buf.extend_from_slice(self.as_bytes());
+    }
+
+    fn read(buf: &mut &[u8]) -> anyhow::Result<Self> {
+        let len = read_len(buf)?;
+        Ok(String::from_utf8(take(buf, len)?.to_vec())?)
+    }
+}
+
+impl<T> ProtocolValue for Vec<T>
+where
+    T: ProtocolValue,
+{
+    fn write(&self, buf: &mut Vec<u8>) {
+        write_len(self.len(), buf);
+        for value in self {
+            value.write(buf);
+        }
+    }
+
+    fn read(buf: &mut &[u8]) -> anyhow::Result<Self> {
+        let len = read_len(buf)?;
+        (0..len).map(|_| T::read(buf)).collect()
+    }
+}
+
+impl<'socket, S> NautSocket<'socket, S>
+where
+    S: SocketType<'socket>,
+{
+    /// Run a function as a callback when the [protocol event](ProtocolEvent) is received. A
+    /// packet that fails to decode never reaches the callback and pushes a
+    /// [read packet fail](SocketEvent::ReadPacketFail) event instead
+    pub fn on_event<E, F>(&mut self, cb: F)
+    where
+        E: ProtocolEvent,
+        F: Fn(&mut NautSocket<'socket, S>, EventContext, E) + Send + Sync + 'static,
+    {
+        self.on(E::EVENT, move |socket, ctx| {
+            let mut packet = ctx.payload;
+            match E::decode(&mut packet) {
+                Ok(event) => cb(socket, ctx, event),
+                Err(e) => socket
+                    .socket_events
+                    .push(SocketEvent::ReadPacketFail(format!(
+                        "Failed to decode {} from {}: {e}",
+                        E::EVENT,
+                        ctx.addr
+                    ))),
+            }
+        });
+    }
+}
+
+impl NautSocket<'_, NautClient> {
+    /// Sends the [protocol event](ProtocolEvent) to the server we are connected to
+    pub fn send_event<E>(&mut self, event: &E) -> anyhow::Result<()>
+    where
+        E: ProtocolEvent,
+    {
+        let mut buf = Vec::new();
+        event.encode(&mut buf);
+        self.send(E::EVENT, &buf, E::DELIVERY)
+    }
+}
+
+impl NautSocket<'_, NautServer> {
+    /// Sends the [protocol event](ProtocolEvent) to the client
+    pub fn send_event<E>(&mut self, event: &E, client: ConnectionId) -> anyhow::Result<()>
+    where
+        E: ProtocolEvent,
+    {
+        let mut buf = Vec::new();
+        event.encode(&mut buf);
+        self.send(E::EVENT, &buf, E::DELIVERY, client)
+    }
+
+    /// Sends the [protocol event](ProtocolEvent) to every established connection
+    pub fn broadcast_event<E>(&mut self, event: &E)
+    where
+        E: ProtocolEvent,
+    {
+        let mut buf = Vec::new();
+        event.encode(&mut buf);
+        self.broadcast(E::EVENT, &buf, E::DELIVERY);
+    }
+}
@@ -0,0 +1,696 @@
+use std::{
+    collections::{hash_map::RandomState, HashMap},
+    hash::BuildHasher,
+    marker::PhantomData,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    client::{ConnectionId, NautClient},
+    packet::{NautReader, NautWriter, PacketDelivery},
+    persistent::Persistent,
+    plugins::SocketPlugin,
+    server::{NautServer, ServerEvent},
+    socket::{events::SocketEvent, NautSocket, SocketType},
+};
+
+/// Sent reliably by the server with a part of an update to a [replicated](Replicated) persistent
+pub const REPLICATION_UPDATE_EVENT: &str = "naut::replication::update";
+
+/// The size of the buffer the socket polls packets into, every part of an update must fit
+const MAX_PACKET_SIZE: usize = 1024;
+/// The bytes a part of an update is written with before its spans, after the name of the
+/// persistent: the session, base, version, index, count, length and span count
+const PART_HEADER_SIZE: usize = 8 + 4 + 4 + 2 + 2 + 4 + 2;
+/// The bytes each span costs on top of its own bytes, its offset and length
+const SPAN_HEADER_SIZE: usize = 8;
+/// Unchanged runs shorter than this between two changes are sent anyway rather than starting a
+/// new span, as each span costs 8 bytes
+const MIN_SPAN_GAP: usize = 8;
+/// The base version of an update that replaces the replica rather than changing it
+const FULL_UPDATE: u32 = u32::MAX;
+/// How long an update waits on the rest of its parts, or on the update before it, before it is
+/// dropped
+const PENDING_UPDATE_LIFETIME: Duration = Duration::from_secs(30);
+
+/// A [persistent](Persistent) the server sends to every client, once in full when they connect
+/// and as a diff whenever it changes. Register the [replication plugin](ReplicationPlugin) for
+/// the type on both the server and the client
+pub trait Replicated: Persistent + Default {
+    /// The name the persistent is replicated under, which must be unique and the same on the
+    /// server and the client
+    const REPLICATION_NAME: &'static str;
+
+    /// Writes the persistent to the end of the buffer to be sent to clients
+    fn write_replica(&self, buf: &mut Vec<u8>);
+
+    /// Reads the persistent from the bytes written by [write replica](Self::write_replica)
+    fn read_replica(buf: &[u8]) -> anyhow::Result<Self>;
+}
+
+/// A plugin replicating a [persistent](Replicated) from the server to its clients. On the server
+/// every change to the persistent is sent to clients once per poll. On the client the persistent
+/// is kept in its [replica](Replica), which can only be read with
+/// [with replica](NautSocket::with_replica), and [changes](NautSocket::on_persistent_changed) to
+/// the replica can be listened to. A name too long to fit an update into a packet is refused
+/// with a [replication fail](SocketEvent::ReplicationFail) event
+pub struct ReplicationPlugin<P> {
+    phantom: PhantomData<P>,
+}
+
+impl<P> Default for ReplicationPlugin<P> {
+    fn default() -> Self {
+        Self {
+            phantom: PhantomData,
+        }
+    }
+}
+
+/// The last state of a replicated persistent sent to clients
+struct ReplicationSource<P> {
+    /// Tells apart updates from different runs of the server, whose versions start over
+    session: u64,
+    version: u32,
+    bytes: Vec<u8>,
+    seen_tick: u64,
+    phantom: PhantomData<P>,
+}
+
+impl<P> Default for ReplicationSource<P> {
+    fn default() -> Self {
+        Self {
+            session: RandomState::new().hash_one(Instant::now()),
+            version: 0,
+            bytes: Vec::new(),
+            seen_tick: 0,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<P> Persistent for ReplicationSource<P> where P: Replicated {}
+
+impl<P> SocketPlugin<'_, NautServer> for ReplicationPlugin<P>
+where
+    P: Replicated,
+{
+    fn register(&self, socket: &mut NautSocket<'_, NautServer>) {
+        if !check_replication_name::<P, _>(socket) {
+            return;
+        }
+
+        socket.get_or_init_persistent::<P>();
+        socket.init_persistent::<ReplicationSource<P>>();
+
+        socket.on_poll(|socket| {
+            send_changes::<P>(socket);
+
+            let connected: Vec<ConnectionId> = socket
+                .server()
+                .iter_server_events()
+                .filter_map(|event| match event {
+                    ServerEvent::OnClientConnected(id) => Some(*id),
+                    _ => None,
+                })
+                .collect();
+
+            if connected.is_empty() {
+                return;
+            }
+
+            let Some(parts) = socket.with_persistent_ref(|source: &ReplicationSource<P>| {
+                let spans = vec![(0, source.bytes.as_slice())];
+                update_packets::<P>(source, FULL_UPDATE, &spans)
+            }) else {
+                return;
+            };
+
+            for id in connected {
+                for part in parts.iter() {
+                    let _ =
+                        socket.send(REPLICATION_UPDATE_EVENT, part, PacketDelivery::Reliable, id);
+                }
+            }
+        });
+    }
+}
+
+/// Sends the changes to the persistent since it was last sent to every client
+fn send_changes<P>(socket: &mut NautSocket<'_, NautServer>)
+where
+    P: Replicated,
+{
+    let Some(tick) = socket.persistent_change_tick::<P>() else {
+        return;
+    };
+
+    let seen_tick = socket.with_persistent_ref(|source: &ReplicationSource<P>| source.seen_tick);
+    if seen_tick.is_none_or(|seen_tick| seen_tick == tick) {
+        return;
+    }
+
+    let Some(bytes) = socket.with_persistent_ref(|persistent: &P| {
+        let mut buf = Vec::new();
+        persistent.write_replica(&mut buf);
+        buf
+    }) else {
+        return;
+    };
+
+    let parts = socket.with_persistent(|source: &mut ReplicationSource<P>| {
+        source.seen_tick = tick;
+        if source.bytes == bytes {
+            return Vec::new();
+        }
+
+        let base = source.version;
+        let old_bytes = std::mem::replace(&mut source.bytes, bytes);
+        source.version = source.version.wrapping_add(1) % FULL_UPDATE;
+        update_packets::<P>(source, base, &diff(&old_bytes, &source.bytes))
+    });
+
+    for part in parts.unwrap_or_default() {
+        socket.broadcast(REPLICATION_UPDATE_EVENT, &part, PacketDelivery::Reliable);
+    }
+}
+
+/// Finds the spans of the new bytes that differ from the old bytes, anything past the length of
+/// the new bytes is cut separately
+fn diff<'a>(old: &[u8], new: &'a [u8]) -> Vec<(usize, &'a [u8])> {
+    let mut spans: Vec<(usize, usize)> = Vec::new();
+    let mut i = 0;
+    while i < new.len() {
+        if old.get(i) == Some(&new[i]) {
+            i += 1;
+            continue;
+        }
+
+        let start = i;
+        while i < new.len() && old.get(i) != Some(&new[i]) {
+            i += 1;
+        }
+
+        match spans.last_mut() {
+            Some((_, end)) if start - *end < MIN_SPAN_GAP => *end = i,
+            _ => spans.push((start, i)),
+        }
+    }
+
+    spans
+        .into_iter()
+        .map(|(start, end)| (start, &new[start..end]))
+        .collect()
+}
+
+/// Writes the spans as the parts of an update from the base version to the current version of
+/// the source, each fitting into a single packet
+fn update_packets<P>(
+    source: &ReplicationSource<P>,
+    base: u32,
+    spans: &[(usize, &[u8])],
+) -> Vec<Vec<u8>>
+where
+    P: Replicated,
+{
+    // Spans longer than a part are split across several parts, each span costs its offset and
+    // length on top of its bytes
+    let Some(max_part_size) = max_part_size(P::REPLICATION_NAME) else {
+        return Vec::new();
+    };
+    let max_chunk_size = max_part_size - SPAN_HEADER_SIZE;
+    let mut parts: Vec<Vec<(usize, &[u8])>> = vec![Vec::new()];
+    let mut part_size = 0;
+    for &(offset, bytes) in spans {
+        for (i, chunk) in bytes.chunks(max_chunk_size).enumerate() {
+            if part_size + chunk.len() + SPAN_HEADER_SIZE > max_part_size {
+                parts.push(Vec::new());
+                part_size = 0;
+            }
+
+            part_size += chunk.len() + SPAN_HEADER_SIZE;
+            if let Some(part) = parts.last_mut() {
+                part.push((offset + i * max_chunk_size, chunk));
+            }
+        }
+    }
+
+    let count = parts.len() as u16;
+    parts
+        .into_iter()
+        .enumerate()
+        .map(|(index, spans)| {
+            let mut writer = NautWriter::with_capacity(MAX_PACKET_SIZE);
+            writer
+                .write_str(P::REPLICATION_NAME)
+                .write_u64(source.session)
+                .write_u32(base)
+                .write_u32(source.version)
+                .write_u16(index as u16)
+                .write_u16(count)
+                .write_u32(source.bytes.len() as u32)
+                .write_u16(spans.len() as u16);
+            for (offset, bytes) in spans {
+                writer
+                    .write_u32(offset as u32)
+                    .write_u32(bytes.len() as u32)
+                    .write_raw(bytes);
+            }
+
+            writer.into_inner()
+        })
+        .collect()
+}
+
+/// The most bytes of spans sent in a single part of an update to the persistent of the name,
+/// leaving room in the packet for its header, the padded event name and the part header. None if
+/// the name leaves no room for a span
+fn max_part_size(name: &str) -> Option<usize> {
+    let event_len = REPLICATION_UPDATE_EVENT.len().next_multiple_of(4);
+    let name_len = NautWriter::new().write_str(name).len();
+    MAX_PACKET_SIZE
+        .checked_sub(NautSocket::<NautServer>::PACKET_PADDING + event_len + PART_HEADER_SIZE)?
+        .checked_sub(name_len)
+        .filter(|size| *size > SPAN_HEADER_SIZE)
+}
+
+/// Checks the name of the persistent leaves room in a packet for its updates, pushing a
+/// [replication fail](SocketEvent::ReplicationFail) event if it does not
+fn check_replication_name<'socket, P, S>(socket: &mut NautSocket<'socket, S>) -> bool
+where
+    P: Replicated,
+    S: SocketType<'socket>,
+{
+    if max_part_size(P::REPLICATION_NAME).is_some() {
+        return true;
+    }
+
+    socket
+        .socket_events
+        .push(SocketEvent::ReplicationFail(format!(
+            "Replication name {} is too long to fit an update into a packet",
+            P::REPLICATION_NAME
+        )));
+    false
+}
+
+/// Bytes received from the server to be written to the replica at an offset
+type Span = (u32, Vec<u8>);
+
+/// A part of an update received from the server
+struct UpdatePart {
+    session: u64,
+    base: u32,
+    version: u32,
+    index: u16,
+    count: u16,
+    len: u32,
+    spans: Vec<Span>,
+}
+
+impl UpdatePart {
+    /// Reads a part from the reader, after the name of the persistent it updates
+    fn read(reader: &mut NautReader) -> anyhow::Result<Self> {
+        let session = reader.read_u64()?;
+        let base = reader.read_u32()?;
+        let version = reader.read_u32()?;
+        let index = reader.read_u16()?;
+        let count = reader.read_u16()?;
+        let len = reader.read_u32()?;
+
+        let span_count = reader.read_u16()?;
+        let mut spans = Vec::with_capacity(span_count as usize);
+        for _ in 0..span_count {
+            let offset = reader.read_u32()?;
+            let span_len = reader.read_u32()? as usize;
+            spans.push((offset, reader.read_raw(span_len)?.to_vec()));
+        }
+
+        Ok(Self {
+            session,
+            base,
+            version,
+            index,
+            count,
+            len,
+            spans,
+        })
+    }
+}
+
+/// An update waiting on the rest of its parts, or on the update before it
+struct PendingUpdate {
+    base: u32,
+    len: u32,
+    parts: Vec<Option<Vec<Span>>>,
+    /// When the first part of the update was received
+    received_at: Instant,
+}
+
+/// The client's copy of a [replicated](Replicated) persistent, kept up to date by the
+/// [replication plugin](ReplicationPlugin). Updates are applied to its bytes in order before the
+/// persistent is read from them. Only the server writes it, so it can only be read
+pub struct Replica<P> {
+    persistent: P,
+    session: Option<u64>,
+    version: u32,
+    bytes: Vec<u8>,
+    pending: HashMap<(u64, u32, bool), PendingUpdate>,
+}
+
+impl<P> Persistent for Replica<P> where P: Replicated {}
+
+impl<P> Replica<P>
+where
+    P: Replicated,
+{
+    /// Creates a replica holding the default of the persistent until the first update arrives
+    fn new() -> Self {
+        Self {
+            persistent: P::default(),
+            session: None,
+            version: 0,
+            bytes: Vec::new(),
+            pending: HashMap::new(),
+        }
+    }
+
+    /// Gets the latest copy of the persistent received from the server
+    pub fn get(&self) -> &P {
+        &self.persistent
+    }
+
+    /// Receives a part of an update, returning whether it brought the replica up to a newer
+    /// version
+    fn receive(&mut self, part: UpdatePart) -> anyhow::Result<bool> {
+        let full = part.base == FULL_UPDATE;
+        if self.session == Some(part.session) && part.version <= self.version {
+            return Ok(false);
+        }
+
+        let pending = self
+            .pending
+            .entry((part.session, part.version, full))
+            .or_insert_with(|| PendingUpdate {
+                base: part.base,
+                len: part.len,
+                parts: vec![None; part.count as usize],
+                received_at: Instant::now(),
+            });
+
+        if let Some(slot) = pending.parts.get_mut(part.index as usize) {
+            *slot = Some(part.spans);
+        }
+
+        let mut applied = false;
+        while let Some(key) = self.next_ready() {
+            let Some(update) = self.pending.remove(&key) else {
+                break;
+            };
+
+            let (session, version, full) = key;
+            if full {
+                self.bytes.clear();
+            }
+
+            self.bytes.resize(update.len as usize, 0);
+            for (offset, bytes) in update.parts.into_iter().flatten().flatten() {
+                let offset = offset as usize;
+                if let Some(target) = self.bytes.get_mut(offset..offset + bytes.len()) {
+                    target.copy_from_slice(&bytes);
+                }
+            }
+
+            self.session = Some(session);
+            self.version = version;
+            applied = true;
+        }
+
+        if !applied {
+            return Ok(false);
+        }
+
+        let (session, version) = (self.session, self.version);
+        self.pending
+            .retain(|(pending_session, pending_version, _), _| {
+                Some(*pending_session) == session && *pending_version > version
+            });
+
+        self.persistent = P::read_replica(&self.bytes)?;
+        Ok(true)
+    }
+
+    /// Drops updates that never received the rest of their parts, or the update before them
+    fn remove_expired(&mut self) {
+        self.pending
+            .retain(|_, update| update.received_at.elapsed() < PENDING_UPDATE_LIFETIME);
+    }
+
+    /// Finds an update with every part received that applies to the replica, a full update of
+    /// another session starts the replica over
+    fn next_ready(&self) -> Option<(u64, u32, bool)> {
+        self.pending
+            .iter()
+            .filter(|(_, update)| update.parts.iter().all(Option::is_some))
+            .map(|(key, update)| (*key, update))
+            .find(
+                |((session, version, full), update)| match self.session == Some(*session) {
+                    true if *full => *version > self.version,
+                    true => update.base == self.version,
+                    false => *full,
+                },
+            )
+            .map(|(key, _)| key)
+    }
+}
+
+impl<P> SocketPlugin<'_, NautClient> for ReplicationPlugin<P>
+where
+    P: Replicated,
+{
+    fn register(&self, socket: &mut NautSocket<'_, NautClient>) {
+        if !check_replication_name::<P, _>(socket) {
+            return;
+        }
+
+        socket.insert_persistent(Replica::<P>::new());
+
+        socket.on(REPLICATION_UPDATE_EVENT, |socket, ctx| {
+            // Only the server we are connected to may update our replicas
+            if socket.get_server_address() != Some(&ctx.addr) {
+                return;
+            }
+
+            let mut reader = NautReader::new(ctx.payload);
+            let part = reader
+                .read_str()
+                .and_then(|name| match name == P::REPLICATION_NAME {
+                    true => UpdatePart::read(&mut reader).map(Some),
+                    // Updates to other replicated persistents are left to their own plugins
+                    false => Ok(None),
+                });
+
+            let part = match part {
+                Ok(Some(part)) => part,
+                Ok(None) => return,
+                Err(e) => {
+                    socket
+                        .socket_events
+                        .push(SocketEvent::ReadPacketFail(e.to_string()));
+                    return;
+                }
+            };
+
+            // Only a part completing a newer version changes the replica
+            let mut error = None;
+            socket.update_persistent(|replica: &mut Replica<P>| {
+                replica.receive(part).unwrap_or_else(|e| {
+                    error = Some(e);
+                    false
+                })
+            });
+
+            if let Some(e) = error {
+                socket
+                    .socket_events
+                    .push(SocketEvent::ReadPacketFail(e.to_string()));
+            }
+        });
+
+        socket.on_poll(|socket| {
+            socket.update_persistent(|replica: &mut Replica<P>| {
+                replica.remove_expired();
+                false
+            });
+        });
+    }
+}
+
+impl NautSocket<'_, NautClient> {
+    /// Runs a function with a reference to the latest copy of the [replicated](Replicated)
+    /// persistent received from the server, returning its result or none if the
+    /// [replication plugin](ReplicationPlugin) for the type is not registered
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let round = client.with_replica(|scores: &Scores| scores.round);
+    /// ```
+    pub fn with_replica<P, F, R>(&self, f: F) -> Option<R>
+    where
+        P: Replicated,
+        F: FnOnce(&P) -> R,
+    {
+        self.with_persistent_ref(|replica: &Replica<P>| f(replica.get()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default, Debug, PartialEq)]
+    struct Board(Vec<u8>);
+
+    impl Persistent for Board {}
+
+    impl Replicated for Board {
+        const REPLICATION_NAME: &'static str = "test::board_with_a_rather_long_replication_name";
+
+        fn write_replica(&self, buf: &mut Vec<u8>) {
+            buf.extend_from_slice(&self.0);
+        }
+
+        fn read_replica(buf: &[u8]) -> anyhow::Result<Self> {
+            Ok(Self(buf.to_vec()))
+        }
+    }
+
+    /// Sends the board to the source, returning the parts of the update
+    fn update(source: &mut ReplicationSource<Board>, board: &Board) -> Vec<Vec<u8>> {
+        let mut bytes = Vec::new();
+        board.write_replica(&mut bytes);
+
+        let base = source.version;
+        let old_bytes = std::mem::replace(&mut source.bytes, bytes);
+        source.version += 1;
+        update_packets(source, base, &diff(&old_bytes, &source.bytes))
+    }
+
+    /// The parts of the full update a client is sent when it connects
+    fn full_update(source: &ReplicationSource<Board>) -> Vec<Vec<u8>> {
+        update_packets(source, FULL_UPDATE, &[(0, source.bytes.as_slice())])
+    }
+
+    /// Gives every part to the replica, returning whether any of them brought it to a newer
+    /// version
+    fn receive(replica: &mut Replica<Board>, parts: &[Vec<u8>]) -> bool {
+        let mut updated = false;
+        for part in parts {
+            let mut reader = NautReader::new(part);
+            assert_eq!(reader.read_str().unwrap(), Board::REPLICATION_NAME);
+            updated |= replica
+                .receive(UpdatePart::read(&mut reader).unwrap())
+                .unwrap();
+        }
+
+        updated
+    }
+
+    #[test]
+    fn diff_merges_nearby_changes() {
+        let old = vec![0; 64];
+        let mut new = old.clone();
+        new[2] = 1;
+        new[5] = 1;
+        new[40] = 1;
+
+        assert_eq!(diff(&old, &new), vec![(2, &new[2..6]), (40, &new[40..41])]);
+        assert!(diff(&old, &old).is_empty());
+    }
+
+    #[test]
+    fn diff_covers_length_changes() {
+        let old = vec![0; 8];
+        let grown = vec![0, 0, 0, 0, 0, 0, 0, 0, 3, 4];
+        assert_eq!(diff(&old, &grown), vec![(8, &grown[8..])]);
+
+        // Shrinking is carried by the length of the update rather than a span
+        assert!(diff(&old, &old[..4]).is_empty());
+    }
+
+    #[test]
+    fn parts_fit_into_a_packet() {
+        let mut source = ReplicationSource::<Board>::default();
+        let board = Board((0..5000).map(|i| i as u8).collect());
+        let parts = update(&mut source, &board);
+        assert!(parts.len() > 1);
+
+        let event_len = REPLICATION_UPDATE_EVENT.len().next_multiple_of(4);
+        let packet_len =
+            |part: &Vec<u8>| NautSocket::<NautServer>::PACKET_PADDING + event_len + part.len();
+
+        // Every part but the last is filled right up to the size of the packet
+        assert!(parts.iter().all(|part| packet_len(part) <= MAX_PACKET_SIZE));
+        assert_eq!(packet_len(&parts[0]), MAX_PACKET_SIZE);
+    }
+
+    #[test]
+    fn name_leaving_no_room_for_a_span_is_refused() {
+        assert!(max_part_size(Board::REPLICATION_NAME).is_some());
+        assert!(max_part_size(&"a".repeat(900)).is_some());
+        assert!(max_part_size(&"a".repeat(1000)).is_none());
+        assert!(max_part_size(&"a".repeat(usize::from(u16::MAX) + 1)).is_none());
+    }
+
+    #[test]
+    fn replica_follows_full_and_partial_updates() {
+        let mut source = ReplicationSource::<Board>::default();
+        let mut replica = Replica::<Board>::new();
+
+        let mut board = Board((0..3000).map(|i| (i % 251) as u8).collect());
+        update(&mut source, &board);
+        let parts = full_update(&source);
+        assert!(parts.len() > 1);
+        assert!(receive(&mut replica, &parts));
+        assert_eq!(replica.get(), &board);
+
+        board.0[10] = 0;
+        board.0[2500] = 0;
+        board.0.truncate(2800);
+        let parts = update(&mut source, &board);
+        assert_eq!(parts.len(), 1);
+        assert!(receive(&mut replica, &parts));
+        assert_eq!(replica.get(), &board);
+
+        // Parts arriving out of order are held until the update is complete
+        board.0.extend((0..2000).map(|i| i as u8));
+        let mut parts = update(&mut source, &board);
+        parts.reverse();
+        assert!(receive(&mut replica, &parts));
+        assert_eq!(replica.get(), &board);
+
+        // Resent parts of a version already applied change nothing
+        assert!(!receive(&mut replica, &parts));
+    }
+
+    #[test]
+    fn incomplete_updates_expire() {
+        let mut source = ReplicationSource::<Board>::default();
+        let mut replica = Replica::<Board>::new();
+
+        let board = Board((0..3000).map(|i| i as u8).collect());
+        let parts = update(&mut source, &board);
+        assert!(!receive(&mut replica, &parts[..1]));
+        assert_eq!(replica.pending.len(), 1);
+
+        replica.remove_expired();
+        assert_eq!(replica.pending.len(), 1);
+
+        for update in replica.pending.values_mut() {
+            update.received_at -= PENDING_UPDATE_LIFETIME;
+        }
+        replica.remove_expired();
+        assert!(replica.pending.is_empty());
+        assert_eq!(replica.get(), &Board::default());
+    }
+}
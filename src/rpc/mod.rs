@@ -0,0 +1,512 @@
+use std::{
+    collections::{hash_map::RandomState, HashMap},
+    fmt::Display,
+    hash::BuildHasher,
+    net::SocketAddr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use anyhow::anyhow;
+
+use crate::{
+    client::{ConnectionId, NautClient},
+    events::EventContext,
+    packet::{NautReader, NautWriter, PacketDelivery},
+    server::NautServer,
+    socket::{events::SocketEvent, NautSocket, SocketType},
+};
+
+/// Sent reliably with the correlation id, method and payload of a [request](NautSocket::on_request)
+pub const RPC_REQUEST_EVENT: &str = "naut::rpc::request";
+/// Sent reliably back to the sender of a request with its correlation id and the response
+pub const RPC_RESPONSE_EVENT: &str = "naut::rpc::response";
+
+/// How long the response to a request is kept to answer resends of the request, which arrive
+/// when our acknowledgement of it was lost
+const ANSWERED_REQUEST_LIFETIME: Duration = Duration::from_secs(30);
+
+/// Handles a request, returning the response sent back to the requester
+pub(crate) type RequestHandler<T> =
+    dyn Fn(&mut T, EventContext) -> anyhow::Result<Vec<u8>> + Send + Sync;
+/// Run once with the response to a request, or the error if it failed or timed out
+pub(crate) type ResponseCallback<T> = dyn FnOnce(&mut T, anyhow::Result<Vec<u8>>) + Send + Sync;
+
+/// Who a request is sent to or received from. A connection is known by its id rather than its
+/// address, so requests to and from it carry on when it migrates to another address
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+enum RpcPeer {
+    Connection(ConnectionId),
+    Address(SocketAddr),
+}
+
+impl RpcPeer {
+    /// The peer an event came from
+    fn of(ctx: &EventContext) -> Self {
+        match ctx.connection_id {
+            Some(id) => Self::Connection(id),
+            None => Self::Address(ctx.addr),
+        }
+    }
+}
+
+impl Display for RpcPeer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Connection(id) => write!(f, "connection {id}"),
+            Self::Address(addr) => write!(f, "{addr}"),
+        }
+    }
+}
+
+/// A request that has been sent and is waiting on a response
+struct PendingRequest<T> {
+    method: String,
+    target: RpcPeer,
+    sent_at: Instant,
+    timeout: Duration,
+    callback: Box<ResponseCallback<T>>,
+}
+
+/// A request that has been handled, kept so a resend of it gets the same response without the
+/// handler running again
+struct AnsweredRequest {
+    /// Tells a resend apart from a new request reusing the correlation id, such as from a
+    /// requester that has started over
+    request_hash: u64,
+    response: Vec<u8>,
+    answered_at: Instant,
+}
+
+/// Keeps the request handlers and the requests waiting on a response
+pub(crate) struct RpcManager<'socket, S>
+where
+    S: SocketType<'socket>,
+{
+    handlers: HashMap<String, Arc<RequestHandler<NautSocket<'socket, S>>>>,
+    pending: HashMap<u32, PendingRequest<NautSocket<'socket, S>>>,
+    answered: HashMap<(RpcPeer, u32), AnsweredRequest>,
+    hasher: RandomState,
+    next_id: u32,
+}
+
+impl<'socket, S> RpcManager<'socket, S>
+where
+    S: SocketType<'socket>,
+{
+    pub(crate) fn new() -> Self {
+        Self {
+            handlers: HashMap::new(),
+            pending: HashMap::new(),
+            answered: HashMap::new(),
+            hasher: RandomState::new(),
+            next_id: 0,
+        }
+    }
+
+    /// Gets a correlation id that no pending request is using
+    fn new_request_id(&mut self) -> u32 {
+        loop {
+            self.next_id = self.next_id.wrapping_add(1);
+            if !self.pending.contains_key(&self.next_id) {
+                return self.next_id;
+            }
+        }
+    }
+}
+
+/// A response is a bool for whether the request succeeded, followed by the payload or the error
+fn response_packet(id: u32, response: &anyhow::Result<Vec<u8>>) -> Vec<u8> {
+    let mut writer = NautWriter::new();
+    writer.write_u32(id).write_bool(response.is_ok());
+    match response {
+        Ok(payload) => writer.write_raw(payload),
+        Err(e) => writer.write_str(&e.to_string()),
+    };
+
+    writer.into_inner()
+}
+
+impl<'socket, S> NautSocket<'socket, S>
+where
+    S: SocketType<'socket>,
+{
+    /// Listens for requests and responses, registered when the socket is created so requests
+    /// made from inside other callbacks are still answered
+    pub(crate) fn register_rpc_events(&mut self) {
+        self.on(RPC_REQUEST_EVENT, |socket, ctx| {
+            let mut reader = NautReader::new(ctx.payload);
+            let (Ok(id), Ok(method)) = (reader.read_u32(), reader.read_str()) else {
+                socket
+                    .socket_events
+                    .push(SocketEvent::ReadPacketFail(format!(
+                        "Malformed request from {}",
+                        ctx.addr
+                    )));
+                return;
+            };
+
+            // A resent request is answered with the response already sent, as the handler may
+            // not be safe to run twice
+            let requester = RpcPeer::of(&ctx);
+            let request_hash = socket.rpc.hasher.hash_one(ctx.payload);
+            if let Some(answered) = socket.rpc.answered.get(&(requester, id)) {
+                if answered.request_hash == request_hash {
+                    let packet = answered.response.clone();
+                    let _ = ctx.reply(
+                        socket,
+                        RPC_RESPONSE_EVENT,
+                        &packet,
+                        PacketDelivery::Reliable,
+                    );
+                    return;
+                }
+            }
+
+            // The handler only sees the payload after the correlation id and method
+            let request = EventContext {
+                payload: reader.remaining(),
+                ..ctx
+            };
+            let response = match socket.rpc.handlers.get(method).cloned() {
+                Some(handler) => handler(socket, request),
+                None => Err(anyhow!("There is no handler for request {method}")),
+            };
+
+            let packet = response_packet(id, &response);
+            let _ = ctx.reply(
+                socket,
+                RPC_RESPONSE_EVENT,
+                &packet,
+                PacketDelivery::Reliable,
+            );
+
+            socket.rpc.answered.insert(
+                (requester, id),
+                AnsweredRequest {
+                    request_hash,
+                    response: packet,
+                    answered_at: Instant::now(),
+                },
+            );
+        });
+
+        self.on(RPC_RESPONSE_EVENT, |socket, ctx| {
+            let mut reader = NautReader::new(ctx.payload);
+            let (Ok(id), Ok(succeeded)) = (reader.read_u32(), reader.read_bool()) else {
+                socket
+                    .socket_events
+                    .push(SocketEvent::ReadPacketFail(format!(
+                        "Malformed response from {}",
+                        ctx.addr
+                    )));
+                return;
+            };
+
+            // Ignores responses to requests that timed out, or were never sent to this peer
+            let Some(pending) = socket.rpc.pending.remove(&id) else {
+                return;
+            };
+
+            if pending.target != RpcPeer::of(&ctx) {
+                socket.rpc.pending.insert(id, pending);
+                return;
+            }
+
+            let response = match succeeded {
+                true => Ok(reader.remaining().to_vec()),
+                false => match reader.read_str() {
+                    Ok(e) => Err(anyhow!("Request {} failed: {e}", pending.method)),
+                    Err(e) => Err(e),
+                },
+            };
+
+            (pending.callback)(socket, response);
+        });
+    }
+
+    /// Run a function whenever the method is requested, returning the response sent back to the
+    /// requester or an error the requester receives instead. A method has a single handler, so
+    /// registering another replaces it
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// server.on_request("get_inventory", |_server, ctx| {
+    ///     let Some(inventory) = ctx.connection_id.and_then(|id| inventories.get(&id)) else {
+    ///         return Err(anyhow!("No inventory for {}", ctx.addr));
+    ///     };
+    ///
+    ///     Ok(inventory.to_bytes())
+    /// });
+    /// ```
+    pub fn on_request<F>(&mut self, method: &str, handler: F)
+    where
+        F: Fn(&mut NautSocket<'socket, S>, EventContext) -> anyhow::Result<Vec<u8>>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.rpc
+            .handlers
+            .insert(method.to_string(), Arc::new(handler));
+    }
+
+    /// Sends a request reliably to the address of the peer, running the callback once the
+    /// response arrives from the peer
+    fn request_by_addr<F>(
+        &mut self,
+        method: &str,
+        buf: &[u8],
+        addr: SocketAddr,
+        target: RpcPeer,
+        timeout: Duration,
+        cb: F,
+    ) -> anyhow::Result<()>
+    where
+        F: FnOnce(&mut NautSocket<'socket, S>, anyhow::Result<Vec<u8>>) + Send + Sync + 'static,
+    {
+        let id = self.rpc.new_request_id();
+        let mut writer = NautWriter::with_capacity(buf.len() + method.len() + 6);
+        writer.write_u32(id).write_str(method).write_raw(buf);
+
+        self.send_by_addr(
+            RPC_REQUEST_EVENT,
+            writer.as_slice(),
+            PacketDelivery::Reliable,
+            addr.to_string(),
+        )?;
+
+        self.rpc.pending.insert(
+            id,
+            PendingRequest {
+                method: method.to_string(),
+                target,
+                sent_at: Instant::now(),
+                timeout,
+                callback: Box::new(cb),
+            },
+        );
+
+        Ok(())
+    }
+
+    /// Fails every request that has gone unanswered for longer than its timeout, and forgets the
+    /// responses kept for resent requests once they are old enough
+    pub(crate) fn expire_requests(&mut self) {
+        self.rpc
+            .answered
+            .retain(|_, answered| answered.answered_at.elapsed() < ANSWERED_REQUEST_LIFETIME);
+
+        let expired: Vec<u32> = self
+            .rpc
+            .pending
+            .iter()
+            .filter(|(_, pending)| pending.sent_at.elapsed() >= pending.timeout)
+            .map(|(id, _)| *id)
+            .collect();
+
+        for id in expired {
+            let Some(pending) = self.rpc.pending.remove(&id) else {
+                continue;
+            };
+
+            let e = anyhow!(
+                "Request {} to {} timed out after {:?}",
+                pending.method,
+                pending.target,
+                pending.timeout
+            );
+            (pending.callback)(self, Err(e));
+        }
+    }
+}
+
+impl<'socket> NautSocket<'socket, NautClient> {
+    /// Sends a request for the method to the server we are connected to, running the callback
+    /// with the response, or with an error if the server failed to handle it or it did not
+    /// answer within the timeout
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// client.request("get_inventory", &[], Duration::from_secs(2), |_client, response| {
+    ///     match response {
+    ///         Ok(inventory) => println!("inventory {inventory:?}"),
+    ///         Err(e) => println!("{e}"),
+    ///     }
+    /// })?;
+    /// ```
+    pub fn request<F>(
+        &mut self,
+        method: &str,
+        buf: &[u8],
+        timeout: Duration,
+        cb: F,
+    ) -> anyhow::Result<()>
+    where
+        F: FnOnce(&mut NautSocket<'socket, NautClient>, anyhow::Result<Vec<u8>>)
+            + Send
+            + Sync
+            + 'static,
+    {
+        let Some(addr) = self.get_server_address().copied() else {
+            return Err(anyhow!("Not connected to a server"));
+        };
+
+        self.request_by_addr(method, buf, addr, RpcPeer::Address(addr), timeout, cb)
+    }
+}
+
+impl<'socket> NautSocket<'socket, NautServer> {
+    /// Sends a request for the method to the client, running the callback with the response, or
+    /// with an error if the client failed to handle it or it did not answer within the timeout
+    pub fn request<F>(
+        &mut self,
+        method: &str,
+        buf: &[u8],
+        client: ConnectionId,
+        timeout: Duration,
+        cb: F,
+    ) -> anyhow::Result<()>
+    where
+        F: FnOnce(&mut NautSocket<'socket, NautServer>, anyhow::Result<Vec<u8>>)
+            + Send
+            + Sync
+            + 'static,
+    {
+        let Some(addr) = self.inner.get_client_addr(&client).copied() else {
+            return Err(anyhow!(
+                "There is no associated address with this client id"
+            ));
+        };
+
+        self.request_by_addr(method, buf, addr, RpcPeer::Connection(client), timeout, cb)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    };
+
+    use crate::server::config::ServerConfig;
+
+    use super::*;
+
+    /// Runs the callbacks of the event as if the connection sent it from the address
+    fn emit(
+        server: &mut NautSocket<NautServer>,
+        event: &str,
+        addr: SocketAddr,
+        connection_id: ConnectionId,
+        payload: &[u8],
+    ) {
+        let ctx = EventContext {
+            addr,
+            connection_id: Some(connection_id),
+            delivery: PacketDelivery::Reliable,
+            sequence: None,
+            ack: None,
+            received_at: Instant::now(),
+            payload,
+        };
+
+        let callbacks = server.event_emitter.event_callbacks[event].clone();
+        for callback in callbacks {
+            callback(server, ctx);
+        }
+    }
+
+    /// A server with a connection at the address, returning the id of the connection
+    fn server_with_connection(addr: SocketAddr) -> (NautSocket<'static, NautServer>, ConnectionId) {
+        let mut server =
+            NautSocket::<NautServer>::new("127.0.0.1:0", ServerConfig::default()).unwrap();
+        let id = server.inner.establish_new_connection(addr, false).unwrap();
+        server.inner.add_outbound_address(addr);
+
+        (server, id)
+    }
+
+    #[test]
+    fn response_is_matched_to_a_connection_that_migrated() {
+        let (old_addr, new_addr) = (
+            "127.0.0.1:4000".parse().unwrap(),
+            "127.0.0.1:4001".parse().unwrap(),
+        );
+        let (mut server, id) = server_with_connection(old_addr);
+
+        let responses = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&responses);
+        server
+            .request(
+                "ping",
+                &[],
+                id,
+                Duration::from_secs(5),
+                move |_server, response| {
+                    recorded.lock().unwrap().push(response.unwrap());
+                },
+            )
+            .unwrap();
+
+        // Another connection answering at the old address is not who the request was sent to
+        let mut response = NautWriter::new();
+        response.write_u32(1).write_bool(true).write_raw(b"pong");
+        let other = ConnectionId::new(id.index() + 1, 0);
+        emit(
+            &mut server,
+            RPC_RESPONSE_EVENT,
+            old_addr,
+            other,
+            response.as_slice(),
+        );
+        assert!(responses.lock().unwrap().is_empty());
+
+        emit(
+            &mut server,
+            RPC_RESPONSE_EVENT,
+            new_addr,
+            id,
+            response.as_slice(),
+        );
+        assert_eq!(*responses.lock().unwrap(), [b"pong".to_vec()]);
+    }
+
+    #[test]
+    fn request_resent_after_migrating_is_not_handled_again() {
+        let (old_addr, new_addr) = (
+            "127.0.0.1:4000".parse().unwrap(),
+            "127.0.0.1:4001".parse().unwrap(),
+        );
+        let (mut server, id) = server_with_connection(old_addr);
+        server.inner.add_outbound_address(new_addr);
+
+        let handled = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&handled);
+        server.on_request("count", move |_server, _ctx| {
+            counter.fetch_add(1, Ordering::Relaxed);
+            Ok(Vec::new())
+        });
+
+        let mut request = NautWriter::new();
+        request.write_u32(7).write_str("count");
+        emit(
+            &mut server,
+            RPC_REQUEST_EVENT,
+            old_addr,
+            id,
+            request.as_slice(),
+        );
+        emit(
+            &mut server,
+            RPC_REQUEST_EVENT,
+            new_addr,
+            id,
+            request.as_slice(),
+        );
+        assert_eq!(handled.load(Ordering::Relaxed), 1);
+    }
+}
@@ -0,0 +1,377 @@
+use std::{
+    fmt::Display,
+    fs,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    path::Path,
+    str::FromStr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::anyhow;
+
+use crate::persistent::Persistent;
+
+/// A range of [ip addresses](IpAddr) written in CIDR notation, such as `10.0.0.0/8`
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct IpRange {
+    addr: IpAddr,
+    prefix_len: u8,
+}
+
+impl IpRange {
+    /// Creates a range of every address sharing the first `prefix_len` bits with the address, the
+    /// remaining bits are cleared so equal ranges compare equal
+    pub fn new(addr: IpAddr, prefix_len: u8) -> anyhow::Result<Self> {
+        let addr = addr.to_canonical();
+        let max_prefix_len = match addr {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+
+        if prefix_len > max_prefix_len {
+            return Err(anyhow!("Prefix length {prefix_len} is too long for {addr}"));
+        }
+
+        Ok(Self {
+            addr: Self::mask(addr, prefix_len),
+            prefix_len,
+        })
+    }
+
+    /// Creates a range containing only the address
+    pub fn single(addr: IpAddr) -> Self {
+        let addr = addr.to_canonical();
+        let prefix_len = match addr {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+
+        Self { addr, prefix_len }
+    }
+
+    /// Checks if the address is within the range
+    pub fn contains(&self, addr: &IpAddr) -> bool {
+        let addr = addr.to_canonical();
+        addr.is_ipv4() == self.addr.is_ipv4() && Self::mask(addr, self.prefix_len) == self.addr
+    }
+
+    /// Clears every bit of the address after the first `prefix_len` bits
+    fn mask(addr: IpAddr, prefix_len: u8) -> IpAddr {
+        match addr {
+            IpAddr::V4(addr) => {
+                let mask = u32::MAX.checked_shl(32 - prefix_len as u32).unwrap_or(0);
+                IpAddr::V4(Ipv4Addr::from(u32::from(addr) & mask))
+            }
+            IpAddr::V6(addr) => {
+                let mask = u128::MAX.checked_shl(128 - prefix_len as u32).unwrap_or(0);
+                IpAddr::V6(Ipv6Addr::from(u128::from(addr) & mask))
+            }
+        }
+    }
+}
+
+impl FromStr for IpRange {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((addr, prefix_len)) = s.split_once('/') else {
+            return Ok(Self::single(IpAddr::from_str(s)?));
+        };
+
+        Self::new(IpAddr::from_str(addr)?, u8::from_str(prefix_len)?)
+    }
+}
+
+impl Display for IpRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix_len)
+    }
+}
+
+/// An [ip range](IpRange) on the [ban list](BanList), either banned or allowed
+#[derive(Clone, Debug)]
+pub struct BanEntry {
+    /// The addresses the entry covers
+    pub range: IpRange,
+    /// When the entry stops applying, never if none
+    pub expires: Option<SystemTime>,
+    /// Why the entry was added
+    pub reason: String,
+}
+
+impl BanEntry {
+    /// Checks if the entry has stopped applying
+    pub fn is_expired(&self) -> bool {
+        self.expires
+            .is_some_and(|expires| expires <= SystemTime::now())
+    }
+
+    /// Checks if the entry applies to the address
+    fn applies_to(&self, addr: &IpAddr) -> bool {
+        !self.is_expired() && self.range.contains(addr)
+    }
+}
+
+/// Decides which addresses may reach the [server](crate::server::NautServer). An address is
+/// blocked if it is banned, or if there are allowed addresses and it is not one of them
+#[derive(Clone, Debug, Default)]
+pub struct BanList {
+    bans: Vec<BanEntry>,
+    allowed: Vec<BanEntry>,
+}
+
+impl BanList {
+    /// The first line of every saved ban list
+    const HEADER: &'static str = "# nautilus ban list";
+
+    /// Creates an empty ban list which blocks nothing
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Checks if the address is blocked from reaching the server
+    pub fn is_blocked(&self, addr: &IpAddr) -> bool {
+        if self.bans.iter().any(|entry| entry.applies_to(addr)) {
+            return true;
+        }
+
+        let allow_list_active = self.allowed.iter().any(|entry| !entry.is_expired());
+        allow_list_active && !self.allowed.iter().any(|entry| entry.applies_to(addr))
+    }
+
+    /// Bans the range, for the duration if given otherwise forever
+    pub fn ban(&mut self, range: IpRange, duration: Option<Duration>, reason: &str) {
+        self.unban(&range);
+        self.bans.push(BanEntry {
+            range,
+            expires: duration.map(|duration| SystemTime::now() + duration),
+            reason: reason.to_string(),
+        });
+    }
+
+    /// Lifts the ban on the range, returning whether it was banned
+    pub fn unban(&mut self, range: &IpRange) -> bool {
+        let len = self.bans.len();
+        self.bans.retain(|entry| entry.range != *range);
+        self.bans.len() != len
+    }
+
+    /// Adds the range to the allow list, for the duration if given otherwise forever. Once the
+    /// allow list has an entry only allowed addresses may reach the server
+    pub fn allow(&mut self, range: IpRange, duration: Option<Duration>, reason: &str) {
+        self.disallow(&range);
+        self.allowed.push(BanEntry {
+            range,
+            expires: duration.map(|duration| SystemTime::now() + duration),
+            reason: reason.to_string(),
+        });
+    }
+
+    /// Removes the range from the allow list, returning whether it was allowed
+    pub fn disallow(&mut self, range: &IpRange) -> bool {
+        let len = self.allowed.len();
+        self.allowed.retain(|entry| entry.range != *range);
+        self.allowed.len() != len
+    }
+
+    /// Gets an iterator to every [ban](BanEntry), including expired ones
+    pub fn iter_bans(&self) -> std::slice::Iter<'_, BanEntry> {
+        self.bans.iter()
+    }
+
+    /// Gets an iterator to every allowed [range](BanEntry), including expired ones
+    pub fn iter_allowed(&self) -> std::slice::Iter<'_, BanEntry> {
+        self.allowed.iter()
+    }
+
+    /// Forgets every entry that has expired, returning whether there were any
+    pub fn remove_expired(&mut self) -> bool {
+        let len = self.bans.len() + self.allowed.len();
+        self.bans.retain(|entry| !entry.is_expired());
+        self.allowed.retain(|entry| !entry.is_expired());
+        self.bans.len() + self.allowed.len() != len
+    }
+
+    /// Loads a ban list from a file written by [save](Self::save)
+    pub fn load<P>(path: P) -> anyhow::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    /// Saves the ban list to a file, one entry per line as `ban|allow <range> <expiry> <reason>`
+    /// where the expiry is in seconds since the unix epoch or `-` for never. The file is written
+    /// to a temporary file first so a failed save never leaves a partial ban list behind
+    pub fn save<P>(&self, path: P) -> anyhow::Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let temp_path = path.with_extension("tmp");
+        fs::write(&temp_path, self.contents()?)?;
+        fs::rename(temp_path, path)?;
+
+        Ok(())
+    }
+
+    /// Parses a ban list from the contents of a saved ban list
+    fn parse(contents: &str) -> anyhow::Result<Self> {
+        let mut ban_list = Self::new();
+        for (line_num, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let entry = Self::parse_line(line)
+                .map_err(|e| anyhow!("Invalid ban list line {}: {e}", line_num + 1))?;
+            match entry {
+                (true, entry) => ban_list.bans.push(entry),
+                (false, entry) => ban_list.allowed.push(entry),
+            }
+        }
+
+        ban_list.remove_expired();
+        Ok(ban_list)
+    }
+
+    /// Writes the contents of a saved ban list, leaving out expired entries
+    fn contents(&self) -> anyhow::Result<String> {
+        let mut contents = format!("{}\n", Self::HEADER);
+        let bans = self.bans.iter().map(|entry| ("ban", entry));
+        let allowed = self.allowed.iter().map(|entry| ("allow", entry));
+        for (kind, entry) in bans.chain(allowed) {
+            if entry.is_expired() {
+                continue;
+            }
+
+            let expires = match entry.expires {
+                Some(expires) => expires.duration_since(UNIX_EPOCH)?.as_secs().to_string(),
+                None => String::from("-"),
+            };
+
+            let reason = entry.reason.replace(['\n', '\r'], " ");
+            contents.push_str(&format!("{kind} {} {expires} {reason}\n", entry.range));
+        }
+
+        Ok(contents)
+    }
+
+    /// Parses a line of a saved ban list, returning whether it is a ban alongside the entry
+    fn parse_line(line: &str) -> anyhow::Result<(bool, BanEntry)> {
+        let mut parts = line.splitn(4, ' ');
+        let is_ban = match parts.next() {
+            Some("ban") => true,
+            Some("allow") => false,
+            kind => return Err(anyhow!("Unknown entry kind {kind:?}")),
+        };
+
+        let range = IpRange::from_str(parts.next().ok_or(anyhow!("Missing range"))?)?;
+        let expires = match parts.next().ok_or(anyhow!("Missing expiry"))? {
+            "-" => None,
+            secs => Some(UNIX_EPOCH + Duration::from_secs(u64::from_str(secs)?)),
+        };
+
+        Ok((
+            is_ban,
+            BanEntry {
+                range,
+                expires,
+                reason: parts.next().unwrap_or_default().to_string(),
+            },
+        ))
+    }
+}
+
+/// A ban list kept as a persistent is saved to the snapshot in the same format as its file
+impl Persistent for BanList {
+    const SNAPSHOT_NAME: Option<&'static str> = Some("naut::ban_list");
+
+    fn write_snapshot(&self, buf: &mut Vec<u8>) {
+        // An expiry before the unix epoch is the only failure, which cannot be saved either way
+        buf.extend_from_slice(self.contents().unwrap_or_default().as_bytes());
+    }
+
+    fn read_snapshot(buf: &[u8]) -> anyhow::Result<Self> {
+        Self::parse(std::str::from_utf8(buf)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(s: &str) -> IpAddr {
+        IpAddr::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn cidr_is_parsed_and_masked() {
+        let range = IpRange::from_str("10.1.2.3/8").unwrap();
+        assert_eq!(range, IpRange::from_str("10.0.0.0/8").unwrap());
+        assert_eq!(range.to_string(), "10.0.0.0/8");
+
+        assert_eq!(
+            IpRange::from_str("10.1.2.3").unwrap().to_string(),
+            "10.1.2.3/32"
+        );
+        assert_eq!(
+            IpRange::from_str("2001:db8::1/32").unwrap().to_string(),
+            "2001:db8::/32"
+        );
+        assert_eq!(
+            IpRange::from_str("0.0.0.0/0").unwrap().to_string(),
+            "0.0.0.0/0"
+        );
+
+        assert!(IpRange::from_str("10.0.0.0/33").is_err());
+        assert!(IpRange::from_str("::/129").is_err());
+        assert!(IpRange::from_str("10.0.0/8").is_err());
+        assert!(IpRange::from_str("10.0.0.0/").is_err());
+    }
+
+    #[test]
+    fn cidr_matches_only_addresses_within_it() {
+        let range = IpRange::from_str("192.168.4.0/22").unwrap();
+        assert!(range.contains(&ip("192.168.4.0")));
+        assert!(range.contains(&ip("192.168.7.255")));
+        assert!(!range.contains(&ip("192.168.8.0")));
+        assert!(!range.contains(&ip("192.168.3.255")));
+
+        let everything = IpRange::from_str("0.0.0.0/0").unwrap();
+        assert!(everything.contains(&ip("255.255.255.255")));
+        assert!(!everything.contains(&ip("::1")));
+
+        let range = IpRange::from_str("2001:db8::/32").unwrap();
+        assert!(range.contains(&ip("2001:db8:ffff::1")));
+        assert!(!range.contains(&ip("2001:db9::1")));
+    }
+
+    #[test]
+    fn ipv4_mapped_addresses_match_ipv4_ranges() {
+        let range = IpRange::from_str("10.0.0.0/8").unwrap();
+        assert!(range.contains(&ip("::ffff:10.9.8.7")));
+        assert_eq!(
+            IpRange::from_str("::ffff:10.9.8.7").unwrap(),
+            IpRange::single(ip("10.9.8.7"))
+        );
+    }
+
+    #[test]
+    fn expired_entries_are_removed() {
+        let mut ban_list = BanList::new();
+        ban_list.ban(
+            IpRange::single(ip("10.0.0.1")),
+            Some(Duration::ZERO),
+            "expired",
+        );
+        ban_list.ban(IpRange::single(ip("10.0.0.2")), None, "forever");
+        assert!(!ban_list.is_blocked(&ip("10.0.0.1")));
+        assert!(ban_list.is_blocked(&ip("10.0.0.2")));
+
+        assert!(ban_list.remove_expired());
+        assert_eq!(ban_list.iter_bans().count(), 1);
+        assert!(!ban_list.remove_expired());
+    }
+}
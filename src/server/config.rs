@@ -1,18 +1,122 @@
-use std::time::Duration;
+use std::{net::Ipv4Addr, path::PathBuf, time::Duration};
+
+use crate::persistent::snapshot::SnapshotConfig;
+
+use super::rate_limit::{RateLimit, RateLimitConfig};
 
 /// The config of how the [server](crate::server::NautServer) should be structured
 pub struct ServerConfig {
+    /// The name reported to anything requesting the [server details](crate::details::ServerDetails)
+    pub server_name: String,
     /// The max amount of connections the server will process
-    pub max_connections: u8,
+    pub max_connections: u16,
+    /// How many of the [max connections](Self::max_connections) are kept for connections the
+    /// server's admission callback marks as privileged
+    pub reserved_slots: u16,
+    /// The max amount of connections from a single ip address
+    pub max_connections_per_ip: Option<u16>,
+    /// The max amount of connections from a single network, a /24 prefix for ipv4 addresses and a
+    /// /64 prefix for ipv6 addresses
+    pub max_connections_per_prefix: Option<u16>,
+    /// Holds clients over capacity in a queue until a slot is free, otherwise they are rejected
+    pub wait_queue: Option<WaitQueueConfig>,
     /// How long it takes for the server to free an idling client
     pub idle_connection_time: Duration,
+    /// Whether a client may keep its connection when its address changes, recognised by the
+    /// [connection token](crate::client::ConnectionToken) its packets carry. The connection is
+    /// only moved once its new address has answered a challenge, so a replayed token cannot
+    /// redirect its packets
+    pub allow_connection_migration: bool,
+    /// Whether the server answers discovery probes broadcast over the local network, the server
+    /// must be bound to an unspecified address such as `0.0.0.0` to receive them
+    pub discovery_enabled: bool,
+    /// A multicast group the server joins to receive discovery probes sent to that group, only
+    /// joined when [discovery is enabled](Self::discovery_enabled)
+    pub discovery_multicast_group: Option<Ipv4Addr>,
+    /// A file the [ban list](crate::server::ban::BanList) is loaded from when the server is
+    /// created and saved to whenever a client is banned
+    pub ban_list_file: Option<PathBuf>,
+    /// Limits how fast connections can send packets and addresses can attempt new connections,
+    /// nothing is limited without one
+    pub rate_limit: Option<RateLimitConfig>,
+    /// How the server avoids being used to reflect traffic at spoofed addresses
+    pub reflection: ReflectionConfig,
+    /// A file [persistents](crate::persistent::Persistent) that opted into snapshots are restored
+    /// from and saved to, so they survive restarts
+    pub snapshot: Option<SnapshotConfig>,
 }
 
 impl Default for ServerConfig {
     fn default() -> Self {
         Self {
+            server_name: String::from("Nautilus Server"),
             max_connections: 128,
+            reserved_slots: 0,
+            max_connections_per_ip: None,
+            max_connections_per_prefix: None,
+            wait_queue: None,
             idle_connection_time: Duration::from_secs(20),
+            allow_connection_migration: true,
+            discovery_enabled: false,
+            discovery_multicast_group: None,
+            ban_list_file: None,
+            rate_limit: None,
+            reflection: ReflectionConfig::default(),
+            snapshot: None,
+        }
+    }
+}
+
+/// The config of how the [server's](crate::server::NautServer) wait queue should behave
+#[derive(Clone, Copy, Debug)]
+pub struct WaitQueueConfig {
+    /// The max amount of clients held in the queue, any more are rejected
+    pub max_size: usize,
+    /// How often queued clients are told their position in the queue
+    pub position_interval: Duration,
+    /// How long a queued client can go without responding before it is removed from the queue
+    pub timeout: Duration,
+}
+
+impl Default for WaitQueueConfig {
+    fn default() -> Self {
+        Self {
+            max_size: 256,
+            position_interval: Duration::from_secs(2),
+            timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+/// The config of how the [server](crate::server::NautServer) avoids being used to reflect traffic
+/// at spoofed addresses. An address is unverified until it proves it can receive the server's
+/// packets, either by answering a challenge or by sending back its
+/// [connection token](crate::client::ConnectionToken), and is never sent a response larger than
+/// the request that triggered it
+#[derive(Clone, Copy, Debug)]
+pub struct ReflectionConfig {
+    /// Whether new connections must answer a challenge padded to the
+    /// [min connection request size](Self::min_connection_request_size) before they are
+    /// established, so every established connection is verified.
+    /// [Clients](crate::client::NautClient) answer challenges on their own. Clients generated
+    /// for other languages do not, and without a challenge such a client is only sent replies to
+    /// its own packets, no larger than them, until it returns its connection token. Its first
+    /// packet must then be large enough to carry the acknowledgement and handshake back. A
+    /// connection that has [migrated](ServerConfig::allow_connection_migration) is challenged
+    /// either way
+    pub require_challenge: bool,
+    /// The size a client must pad its challenge answer to, capped by the clients at 1024 bytes
+    pub min_connection_request_size: u16,
+    /// How many bytes may be sent to unverified addresses in total, unlimited if none
+    pub unverified_send_limit: Option<RateLimit>,
+}
+
+impl Default for ReflectionConfig {
+    fn default() -> Self {
+        Self {
+            require_challenge: true,
+            min_connection_request_size: 256,
+            unverified_send_limit: Some(RateLimit::per_second(64 * 1024)),
         }
     }
 }
@@ -1,49 +1,119 @@
+pub mod ban;
 pub mod config;
+mod queue;
+pub mod rate_limit;
+pub(crate) mod reflection;
+pub mod rejection;
 use std::{
-    collections::{HashMap, VecDeque},
+    any::Any,
+    collections::{BTreeSet, HashMap, HashSet, VecDeque},
     marker::PhantomData,
-    net::{SocketAddr, ToSocketAddrs, UdpSocket},
+    net::{IpAddr, Ipv4Addr, SocketAddr, ToSocketAddrs, UdpSocket},
+    path::PathBuf,
+    sync::Arc,
     time::{Duration, Instant},
 };
 
 use anyhow::anyhow;
+use ban::{BanList, IpRange};
 use byteorder::{ByteOrder, LittleEndian};
 use config::ServerConfig;
+use queue::WaitQueue;
+use rate_limit::{RateLimitKind, RateLimitPolicy, RateLimitStats, RateLimiter};
+use reflection::ReflectionGuard;
+use rejection::RejectionReason;
 
 use crate::{
     acknowledgement::{manager::AcknowledgementManager, packet::AckNumber},
-    client::ConnectionId,
-    connection::EstablishedConnection,
-    events::EventEmitter,
-    packet::{IntoPacketDelivery, PacketDelivery},
+    client::{ConnectionId, ConnectionToken},
+    connection::{generate_connection_token, EstablishedConnection},
+    details::ServerDetails,
+    events::{EventContext, EventEmitter},
+    packet::{IntoPacketDelivery, PacketDelivery, CONNECTION_ESTABLISHED_EVENT, PROTOCOL_VERSION},
     persistent::storage::PersistentStorage,
+    rpc::RpcManager,
     sequence::SequenceNumber,
     socket::{events::SocketEvent, NautSocket, SocketType},
 };
 
+/// Supplies the application defined payload of the [server details](ServerDetails)
+pub(crate) type DetailCallback = dyn Fn(&NautServer) -> Vec<u8> + Send + Sync;
+/// Decides whether a new connection is privileged from its address and the event and bytes of
+/// its first packet
+pub(crate) type AdmissionCallback = dyn Fn(&NautServer, SocketAddr, &str, &[u8]) -> bool + Send + Sync;
+
 // Incremental Id
 pub struct NautServer {
-    max_connections: u8,
+    server_name: String,
+    max_connections: u16,
+    reserved_slots: u16,
+    max_connections_per_ip: Option<u16>,
+    max_connections_per_prefix: Option<u16>,
 
     connection_addr_to_id: HashMap<SocketAddr, ConnectionId>,
-    connection_id_to_addr: HashMap<ConnectionId, SocketAddr>,
+    connection_token_to_id: HashMap<ConnectionToken, ConnectionId>,
     connections: HashMap<ConnectionId, EstablishedConnection>,
-
-    time_outs: HashMap<ConnectionId, Instant>,
-
-    next_id: ConnectionId,
+    /// Connections the admission callback marked as privileged
+    privileged_connections: HashSet<ConnectionId>,
+    /// The amount of connections from each ip address
+    connections_per_ip: HashMap<IpAddr, usize>,
+    /// The amount of connections from each network
+    connections_per_prefix: HashMap<IpRange, usize>,
+
+    /// Connections that have not yet been sent their [connection token](ConnectionToken)
+    pending_handshakes: Vec<ConnectionId>,
+    /// Queued clients that could not be promoted and must be told why
+    pending_rejections: Vec<(SocketAddr, RejectionReason)>,
+
+    /// Connections ordered by when they were last heard from, so idle connections are found
+    /// without visiting every connection
+    idle_deadlines: BTreeSet<(Instant, ConnectionId)>,
+
+    next_index: u16,
+    /// Freed connection ids already moved on to their next generation
     freed_ids: VecDeque<ConnectionId>,
 
     idle_connection_timeout: Duration,
+    allow_connection_migration: bool,
+    discovery_enabled: bool,
 
     server_events: VecDeque<ServerEvent>,
+
+    detail_callback: Option<Arc<DetailCallback>>,
+    admission_callback: Option<Arc<AdmissionCallback>>,
+
+    wait_queue: WaitQueue,
+
+    ban_list: BanList,
+    ban_list_file: Option<PathBuf>,
+    /// Whether the ban list may have changed since connected clients were checked against it
+    ban_list_changed: bool,
+
+    rate_limiter: RateLimiter,
+    reflection: ReflectionGuard,
+    /// The address of the packet being handled and how many more bytes may be sent back to it
+    /// while it is unverified, so an unverified address is never sent more than it sent us
+    reply_budget: Option<(SocketAddr, usize)>,
+    /// Addresses we send to of our own accord, such as a master server, so sending to them is
+    /// never a reflection
+    outbound_addresses: HashSet<SocketAddr>,
 }
 
 impl NautServer {
     pub fn new(config: ServerConfig) -> Self {
         Self {
+            server_name: config.server_name,
             max_connections: config.max_connections,
+            reserved_slots: config.reserved_slots,
+            max_connections_per_ip: config.max_connections_per_ip,
+            max_connections_per_prefix: config.max_connections_per_prefix,
+            wait_queue: WaitQueue::new(config.wait_queue),
             idle_connection_timeout: config.idle_connection_time,
+            allow_connection_migration: config.allow_connection_migration,
+            discovery_enabled: config.discovery_enabled,
+            ban_list_file: config.ban_list_file,
+            rate_limiter: RateLimiter::new(config.rate_limit),
+            reflection: ReflectionGuard::new(config.reflection),
             ..Default::default()
         }
     }
@@ -55,7 +125,7 @@ impl NautServer {
 
     /// Gets the [client's id](ConnectionId) from an [address](SocketAddr)
     pub fn get_client_addr(&self, id: &ConnectionId) -> Option<&SocketAddr> {
-        self.connection_id_to_addr.get(id)
+        Some(&self.connections.get(id)?.addr)
     }
 
     /// Gets the [client's address](SocketAddr) from an [id](ConnectionId)
@@ -68,25 +138,51 @@ impl NautServer {
         self.server_events.iter()
     }
 
+    /// Gets the name reported in the [server details](ServerDetails)
+    pub fn get_server_name(&self) -> &str {
+        &self.server_name
+    }
+
+    /// Gets the [details](ServerDetails) reported to anything requesting them
+    pub fn get_server_details(&self) -> ServerDetails {
+        let payload = self
+            .detail_callback
+            .as_ref()
+            .map(|cb| cb(self))
+            .unwrap_or_default();
+
+        ServerDetails {
+            name: self.server_name.clone(),
+            current_connections: self.get_current_connections() as u32,
+            max_connections: self.get_max_connections() as u32,
+            protocol_version: PROTOCOL_VERSION,
+            payload,
+            latency: Duration::ZERO,
+        }
+    }
+
     /// Gets the max amount of connections the server can handle
-    pub fn get_max_connections(&self) -> u8 {
+    pub fn get_max_connections(&self) -> u16 {
         self.max_connections
     }
 
     /// Gets the current amount of established connections
-    pub fn get_current_connections(&self) -> u8 {
-        self.connections.len() as u8
+    pub fn get_current_connections(&self) -> u16 {
+        self.connections.len() as u16
     }
 
-    /// Checks if a client has not sent a packet for the (idle time)[Self::idle_connection_timeout]
+    /// Checks if a client has not sent a packet for the (idle time)[Self::idle_connection_timeout],
+    /// only visiting the connections that have idled
     pub(crate) fn any_client_needs_freeing(&self) -> Option<Vec<ConnectionId>> {
-        let mut ids = Vec::new();
-        for (id, time) in self.time_outs.iter() {
-            if Instant::now().duration_since(*time) < self.idle_connection_timeout {
-                continue;
-            }
-            ids.push(*id);
-        }
+        let now = Instant::now();
+        let ids: Vec<ConnectionId> = self
+            .idle_deadlines
+            .iter()
+            .take_while(|(last_seen, _)| {
+                now.duration_since(*last_seen) >= self.idle_connection_timeout
+            })
+            .map(|(_, id)| *id)
+            .collect();
 
         if ids.is_empty() {
             return None;
@@ -95,63 +191,435 @@ impl NautServer {
         Some(ids)
     }
 
+    /// Records that a client has just been heard from, pushing back when it idles
+    pub(crate) fn touch_client(&mut self, id: ConnectionId) {
+        let Some(connection) = self.connections.get_mut(&id) else {
+            return;
+        };
+
+        self.idle_deadlines.remove(&(connection.last_seen, id));
+        connection.last_seen = Instant::now();
+        self.idle_deadlines.insert((connection.last_seen, id));
+    }
+
     /// Frees a client up to the server
     pub(crate) fn free_client(&mut self, id: ConnectionId) {
-        self.freed_ids.push_back(id);
-        let Some(addr) = self.connection_id_to_addr.remove(&id) else {
+        let Some(connection) = self.connections.remove(&id) else {
             println!("Failed to find address of idle'd client with id: {id}");
             return;
         };
 
-        self.connection_addr_to_id.remove(&addr);
-        self.time_outs.remove(&id);
-        self.connections.remove(&id);
+        self.freed_ids.push_back(id.next_generation());
+        self.connection_addr_to_id.remove(&connection.addr);
+        self.connection_token_to_id.remove(&connection.token);
+        self.idle_deadlines.remove(&(connection.last_seen, id));
+        self.uncount_address(connection.addr.ip());
+        self.privileged_connections.remove(&id);
+        self.rate_limiter.remove_connection(&id);
+
+        // The released slot can go to someone waiting in the queue
+        self.promote_queued_clients();
+    }
+
+    /// Gets a reference to the [ban list](BanList)
+    pub fn ban_list(&self) -> &BanList {
+        &self.ban_list
+    }
+
+    /// Gets a mutable reference to the [ban list](BanList), changes are only written to the
+    /// [ban list file](ServerConfig::ban_list_file) by [saving](Self::save_ban_list). Connected
+    /// clients the changes block are disconnected when the server next runs its events
+    pub fn ban_list_mut(&mut self) -> &mut BanList {
+        self.ban_list_changed = true;
+        &mut self.ban_list
+    }
+
+    /// Saves the [ban list](BanList) to the [ban list file](ServerConfig::ban_list_file)
+    pub fn save_ban_list(&self) -> anyhow::Result<()> {
+        let path = self
+            .ban_list_file
+            .as_ref()
+            .ok_or(anyhow!("The server has no ban list file"))?;
+
+        self.ban_list.save(path)
+    }
+
+    /// Gets the [rate limit counters](RateLimitStats) across every connection
+    pub fn rate_limit_stats(&self) -> &RateLimitStats {
+        self.rate_limiter.stats()
+    }
+
+    /// Gets the [rate limit counters](RateLimitStats) of a single client
+    pub fn client_rate_limit_stats(&self, id: &ConnectionId) -> Option<&RateLimitStats> {
+        self.rate_limiter.connection_stats(id)
+    }
+
+    /// Gets the data of the type attached to a client with
+    /// [insert connection data](Self::insert_connection_data)
+    pub fn connection_data<T>(&self, id: &ConnectionId) -> Option<&T>
+    where
+        T: Any + Send + Sync,
+    {
+        self.connections.get(id)?.data()
+    }
+
+    /// Gets a mutable reference to the data of the type attached to a client
+    pub fn connection_data_mut<T>(&mut self, id: &ConnectionId) -> Option<&mut T>
+    where
+        T: Any + Send + Sync,
+    {
+        self.connections.get_mut(id)?.data_mut()
+    }
+
+    /// Gets the data of the type attached to the client at an [address](SocketAddr)
+    pub fn connection_data_by_addr<T>(&self, addr: &SocketAddr) -> Option<&T>
+    where
+        T: Any + Send + Sync,
+    {
+        self.connection_data(self.connection_addr_to_id.get(addr)?)
+    }
+
+    /// Gets a mutable reference to the data of the type attached to the client at an
+    /// [address](SocketAddr)
+    pub fn connection_data_by_addr_mut<T>(&mut self, addr: &SocketAddr) -> Option<&mut T>
+    where
+        T: Any + Send + Sync,
+    {
+        let id = *self.connection_addr_to_id.get(addr)?;
+        self.connection_data_mut(&id)
+    }
+
+    /// Attaches data to a client, one value of each type, returning the data of the same type it
+    /// replaced. The data is dropped when the client is freed
+    pub fn insert_connection_data<T>(
+        &mut self,
+        id: &ConnectionId,
+        data: T,
+    ) -> anyhow::Result<Option<T>>
+    where
+        T: Any + Send + Sync,
+    {
+        let connection = self
+            .connections
+            .get_mut(id)
+            .ok_or(anyhow!("There is no connection with this client id"))?;
+
+        Ok(connection.insert_data(data))
+    }
+
+    /// Removes the data of the type from a client
+    pub fn remove_connection_data<T>(&mut self, id: &ConnectionId) -> Option<T>
+    where
+        T: Any + Send + Sync,
+    {
+        self.connections.get_mut(id)?.remove_data()
+    }
+
+    /// Checks if a client has proven it can receive packets at its address, by answering a
+    /// challenge or sending back its [connection token](ConnectionToken). Until then only a
+    /// limited amount is sent to it
+    pub fn is_client_verified(&self, id: &ConnectionId) -> bool {
+        self.connections
+            .get(id)
+            .is_some_and(|connection| connection.verified)
+    }
+
+    /// Checks if an address belongs to a verified connection, or recently answered a challenge
+    pub(crate) fn is_address_verified(&self, addr: &SocketAddr) -> bool {
+        self.connection_addr_to_id
+            .get(addr)
+            .is_some_and(|id| self.is_client_verified(id))
+            || self.reflection.is_address_verified(addr)
+    }
+
+    /// Allows packets to be sent to an address we contact of our own accord, such as a master
+    /// server, without it first proving it can receive them
+    pub(crate) fn add_outbound_address(&mut self, addr: SocketAddr) {
+        self.outbound_addresses.insert(addr);
+    }
+
+    /// Checks if a packet carrying the [token](ConnectionToken) from an unknown address is a
+    /// connection trying to move to that address
+    fn is_migrating(&self, token: ConnectionToken) -> bool {
+        self.allow_connection_migration && self.connection_token_to_id.contains_key(&token)
+    }
+
+    /// Gets the position of an [address](SocketAddr) in the wait queue, starting from 1
+    pub fn get_queue_position(&self, addr: &SocketAddr) -> Option<usize> {
+        self.wait_queue.position(addr)
+    }
+
+    /// Gets the amount of clients waiting in the queue
+    pub fn get_queue_len(&self) -> usize {
+        self.wait_queue.clients.len()
+    }
+
+    /// Checks if a client was marked as privileged by the admission callback
+    pub fn is_client_privileged(&self, id: &ConnectionId) -> bool {
+        self.privileged_connections.contains(id)
+    }
+
+    /// Checks if there is a slot free for a new connection, only privileged connections may take
+    /// the [reserved slots](ServerConfig::reserved_slots)
+    pub fn has_free_slot(&self, privileged: bool) -> bool {
+        if self.connections.len() >= self.max_connections as usize {
+            return false;
+        }
+
+        if privileged {
+            return true;
+        }
+
+        // Privileged connections fill the reserved slots first, so they only take up public slots
+        // once the reserved slots are used up
+        let reserved_slots = self.reserved_slots as usize;
+        let public_slots = (self.max_connections as usize).saturating_sub(reserved_slots);
+        let used_reserved_slots = self.privileged_connections.len().min(reserved_slots);
+
+        self.connections.len() - used_reserved_slots < public_slots
+    }
+
+    /// Gets the amount of connections from an [ip address](IpAddr)
+    pub fn get_connections_from_ip(&self, ip: &IpAddr) -> usize {
+        self.connections_per_ip
+            .get(&ip.to_canonical())
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Gets the network an ip address is counted under for the
+    /// [max connections per prefix](ServerConfig::max_connections_per_prefix)
+    fn connection_prefix(ip: IpAddr) -> IpRange {
+        let prefix_len = if ip.to_canonical().is_ipv4() { 24 } else { 64 };
+        IpRange::new(ip, prefix_len).unwrap_or(IpRange::single(ip))
+    }
+
+    /// Checks if the ip address, or the network it belongs to, already has the max amount of
+    /// connections
+    fn is_address_at_limit(&self, ip: IpAddr) -> bool {
+        let ip_at_limit = self
+            .max_connections_per_ip
+            .is_some_and(|max| self.get_connections_from_ip(&ip) >= max as usize);
+
+        let prefix_at_limit = self.max_connections_per_prefix.is_some_and(|max| {
+            self.connections_per_prefix
+                .get(&Self::connection_prefix(ip))
+                .is_some_and(|count| *count >= max as usize)
+        });
+
+        ip_at_limit || prefix_at_limit
+    }
+
+    /// Counts a new connection from the ip address
+    fn count_address(&mut self, ip: IpAddr) {
+        *self.connections_per_ip.entry(ip.to_canonical()).or_default() += 1;
+        *self
+            .connections_per_prefix
+            .entry(Self::connection_prefix(ip))
+            .or_default() += 1;
+    }
+
+    /// Stops counting a connection from the ip address
+    fn uncount_address(&mut self, ip: IpAddr) {
+        if let Some(count) = self.connections_per_ip.get_mut(&ip.to_canonical()) {
+            *count -= 1;
+            if *count == 0 {
+                self.connections_per_ip.remove(&ip.to_canonical());
+            }
+        }
+
+        let prefix = Self::connection_prefix(ip);
+        if let Some(count) = self.connections_per_prefix.get_mut(&prefix) {
+            *count -= 1;
+            if *count == 0 {
+                self.connections_per_prefix.remove(&prefix);
+            }
+        }
+    }
+
+    /// Checks with the admission callback whether a new connection is privileged
+    pub(crate) fn is_privileged(&self, addr: SocketAddr, event: &str, bytes: &[u8]) -> bool {
+        self.admission_callback
+            .as_ref()
+            .is_some_and(|cb| cb(self, addr, event, bytes))
+    }
+
+    /// Establishes connections with queued clients for as long as there are free slots
+    pub(crate) fn promote_queued_clients(&mut self) {
+        loop {
+            let free_slot = self.has_free_slot(false);
+            let free_reserved_slot = self.has_free_slot(true);
+
+            let Some(client) = self.wait_queue.take_next(|privileged| {
+                if privileged {
+                    free_reserved_slot
+                } else {
+                    free_slot
+                }
+            }) else {
+                return;
+            };
+
+            // The client may have been banned while it waited
+            if self.ban_list.is_blocked(&client.addr.ip()) {
+                self.pending_rejections
+                    .push((client.addr, RejectionReason::Banned));
+                continue;
+            }
+
+            if let Err(reason) = self.establish_new_connection(client.addr, client.privileged) {
+                self.pending_rejections.push((client.addr, reason));
+            }
+        }
+    }
+
+    /// Removes queued clients that have stopped responding and pushes a
+    /// [queued client timeout event](ServerEvent::OnQueuedClientTimeout) to the server events
+    /// queue for each
+    pub(crate) fn remove_timed_out_queued_clients(&mut self) {
+        for addr in self.wait_queue.remove_timed_out() {
+            self.server_events
+                .push_back(ServerEvent::OnQueuedClientTimeout(addr));
+        }
     }
 
     /// Closes a connection with a client and pushes a [client disconnected event](ServerEvent::OnClientDisconnected)
     /// to the server events queue
     pub fn close_connection_with_client(&mut self, id: ConnectionId) {
-        self.free_client(id);
         self.server_events
             .push_back(ServerEvent::OnClientDisconnected(id));
+        self.free_client(id);
     }
 
     /// Establishes a new connection to a new [socket address](SocketAddr) and pushes a
-    /// [client connected event](ServerEvent::OnClientConnected) to the server events queue
-    pub(crate) fn establish_new_connection(&mut self, addr: SocketAddr) {
+    /// [client connected event](ServerEvent::OnClientConnected) to the server events queue,
+    /// unless the connection must be refused. A privileged connection may take a reserved slot
+    pub(crate) fn establish_new_connection(
+        &mut self,
+        addr: SocketAddr,
+        privileged: bool,
+    ) -> Result<ConnectionId, RejectionReason> {
+        // Checked first so a client that could never connect is not held in the wait queue
+        if self.is_address_at_limit(addr.ip()) {
+            return Err(RejectionReason::TooManyConnectionsFromAddress);
+        }
+
+        if !self.has_free_slot(privileged) {
+            return Err(RejectionReason::ServerFull);
+        }
+
         // Gets a new client id
         let client_id = {
             if let Some(client_id) = self.freed_ids.pop_front() {
                 client_id
             } else {
-                let client_id = self.next_id;
-                self.next_id += 1;
+                let client_id = ConnectionId::new(self.next_index, 0);
+                self.next_index += 1;
                 client_id
             }
         };
 
+        let mut connection = EstablishedConnection::new(addr);
+        connection.token = generate_connection_token();
+        connection.verified = self.reflection.is_address_verified(&addr);
+        self.reflection.remove_verified_address(&addr);
+
         self.connection_addr_to_id.insert(addr, client_id);
-        self.connection_id_to_addr.insert(client_id, addr);
-        self.connections
-            .insert(client_id, EstablishedConnection::new(addr));
+        self.idle_deadlines.insert((connection.last_seen, client_id));
+        self.count_address(addr.ip());
+        self.connection_token_to_id
+            .insert(connection.token, client_id);
+        self.connections.insert(client_id, connection);
+        if privileged {
+            self.privileged_connections.insert(client_id);
+        }
+        self.pending_handshakes.push(client_id);
 
         self.server_events
             .push_back(ServerEvent::OnClientConnected(client_id));
+
+        Ok(client_id)
+    }
+
+    /// Moves the connection identified by the [token](ConnectionToken) to a new
+    /// [address](SocketAddr) and pushes a [client migrated event](ServerEvent::OnClientMigrated) to
+    /// the server events queue, returning the address it was moved from. A token could have been
+    /// sent from anywhere, so the connection is only moved to an address that has answered a
+    /// challenge, and never to one that already has the max amount of connections
+    pub(crate) fn migrate_connection(
+        &mut self,
+        token: ConnectionToken,
+        addr: SocketAddr,
+    ) -> Option<SocketAddr> {
+        if !self.allow_connection_migration || !self.reflection.is_address_verified(&addr) {
+            return None;
+        }
+
+        // The new address must not already belong to another connection
+        if self.connection_addr_to_id.contains_key(&addr) {
+            return None;
+        }
+
+        let id = *self.connection_token_to_id.get(&token)?;
+        let old_addr = self.connections.get(&id)?.addr;
+
+        // The connection stops counting towards its old address first, so moving within the same
+        // ip address or network never pushes it over the limit
+        self.uncount_address(old_addr.ip());
+        if self.is_address_at_limit(addr.ip()) {
+            self.count_address(old_addr.ip());
+            return None;
+        }
+
+        self.count_address(addr.ip());
+        self.connection_addr_to_id.remove(&old_addr);
+        self.connection_addr_to_id.insert(addr, id);
+
+        let connection = self.connections.get_mut(&id)?;
+        connection.addr = addr;
+        connection.verified = true;
+        self.reflection.remove_verified_address(&addr);
+
+        self.server_events
+            .push_back(ServerEvent::OnClientMigrated(id, old_addr, addr));
+
+        Some(old_addr)
     }
 }
 
 impl Default for NautServer {
     fn default() -> Self {
         Self {
+            server_name: String::from("Nautilus Server"),
             max_connections: 128,
+            reserved_slots: 0,
+            max_connections_per_ip: None,
+            max_connections_per_prefix: None,
             connections: Default::default(),
+            privileged_connections: Default::default(),
+            connections_per_ip: Default::default(),
+            connections_per_prefix: Default::default(),
             connection_addr_to_id: Default::default(),
-            connection_id_to_addr: Default::default(),
-            time_outs: Default::default(),
-            next_id: Default::default(),
+            connection_token_to_id: Default::default(),
+            pending_handshakes: Vec::new(),
+            pending_rejections: Vec::new(),
+            idle_deadlines: BTreeSet::new(),
+            next_index: 0,
             freed_ids: VecDeque::new(),
             idle_connection_timeout: Duration::from_secs(20),
+            allow_connection_migration: true,
+            discovery_enabled: false,
             server_events: VecDeque::new(),
+            detail_callback: None,
+            admission_callback: None,
+            wait_queue: WaitQueue::default(),
+            ban_list: BanList::new(),
+            ban_list_file: None,
+            ban_list_changed: false,
+            rate_limiter: RateLimiter::default(),
+            reflection: ReflectionGuard::default(),
+            reply_budget: None,
+            outbound_addresses: HashSet::new(),
         }
     }
 }
@@ -166,9 +634,26 @@ impl<'socket> NautSocket<'socket, NautServer> {
         let socket = UdpSocket::bind(addr)?;
         socket.set_nonblocking(true)?;
 
-        let server = NautServer::new(config);
+        // Nothing in the group is answered without discovery, so it is not joined
+        if let Some(group) = config
+            .discovery_multicast_group
+            .filter(|_| config.discovery_enabled)
+        {
+            socket.join_multicast_v4(&group, &Ipv4Addr::UNSPECIFIED)?;
+        }
+
+        let mut persistent = PersistentStorage::new();
+        if let Some(snapshot) = config.snapshot.clone() {
+            persistent.enable_snapshots(snapshot)?;
+        }
+
+        let mut server = NautServer::new(config);
+        if let Some(path) = server.ban_list_file.as_ref().filter(|path| path.exists()) {
+            server.ban_list = BanList::load(path)?;
+        }
+
         let event_emitter = EventEmitter::new();
-        Ok(Self {
+        let mut naut_socket = Self {
             socket,
             packet_queue: VecDeque::new(),
             inner: server,
@@ -176,8 +661,12 @@ impl<'socket> NautSocket<'socket, NautServer> {
             ack_manager: AcknowledgementManager::new(),
             phantom: PhantomData,
             socket_events: Vec::new(),
-            persistent: PersistentStorage::new(),
-        })
+            persistent,
+            rpc: RpcManager::new(),
+        };
+
+        naut_socket.register_rpc_events();
+        Ok(naut_socket)
     }
 
     /// Gets a reference to the [server](NautServer)
@@ -190,6 +679,24 @@ impl<'socket> NautSocket<'socket, NautServer> {
         &mut self.inner
     }
 
+    /// Gets the data of the type attached to a client, see
+    /// [connection data](NautServer::connection_data)
+    pub fn connection_data<T>(&self, id: &ConnectionId) -> Option<&T>
+    where
+        T: Any + Send + Sync,
+    {
+        self.inner.connection_data(id)
+    }
+
+    /// Gets a mutable reference to the data of the type attached to a client, see
+    /// [connection data mut](NautServer::connection_data_mut)
+    pub fn connection_data_mut<T>(&mut self, id: &ConnectionId) -> Option<&mut T>
+    where
+        T: Any + Send + Sync,
+    {
+        self.inner.connection_data_mut(id)
+    }
+
     /// Gets the packets from the packet queue and will handle returning
     /// [ack packets](crate::acknowledgement::packet::AckPacket), resolving sequenced packets, emitting
     /// listening events, establishing new connections and disconnecting idling clients
@@ -197,17 +704,43 @@ impl<'socket> NautSocket<'socket, NautServer> {
         // Disconnect idle clients
         if let Some(ids_to_free) = self.inner.any_client_needs_freeing() {
             for id in ids_to_free.iter() {
-                self.inner.free_client(*id);
-
                 self.inner
                     .server_events
                     .push_back(ServerEvent::OnClientTimeout(*id));
+
+                // Nothing will acknowledge the packets still being resent to a timed out client
+                if let Some(addr) = self.inner.get_client_addr(id).copied() {
+                    self.ack_manager.remove_packets_to(&addr);
+                }
+
+                self.inner.free_client(*id);
             }
         }
 
-        let event_emitter = std::mem::take(&mut self.event_emitter);
+        self.update_wait_queue();
+        self.update_ban_list();
+        self.inner.rate_limiter.remove_idle_buckets();
+        self.inner.reflection.remove_expired();
+        self.save_snapshot_if_due();
+        self.expire_requests();
+
+        let mut event_emitter = std::mem::take(&mut self.event_emitter);
         let event_emitter_ref = &event_emitter;
-        while let Some((addr, packet)) = self.oldest_packet_in_queue() {
+        while let Some((addr, packet, received_at)) = self.oldest_packet_in_queue() {
+            // Blocked addresses are dropped before we do anything else with their packets
+            if self.inner.ban_list.is_blocked(&addr.ip()) {
+                continue;
+            }
+
+            // Until the sender is verified, everything sent back to it in reply to this packet
+            // must fit within the size of the packet
+            self.inner.reply_budget = Some((addr, packet.len()));
+
+            // Lets other protocols sharing the socket take the packet before we parse it
+            if event_emitter_ref.emit_raw_packet_events(self, addr, &packet) {
+                continue;
+            }
+
             let Some(delivery_type) = Self::get_delivery_type_from_packet(&packet) else {
                 self.socket_events
                     .push(SocketEvent::ReadPacketFail("No delivery type".to_string()));
@@ -233,11 +766,165 @@ impl<'socket> NautSocket<'socket, NautServer> {
                 continue;
             }
 
+            // Detail requests are answered without establishing a connection
+            if delivery_type == PacketDelivery::detail_request() {
+                if let Err(e) = self.send_server_details(addr, &packet) {
+                    self.socket_events
+                        .push(SocketEvent::SendPacketFail(e.to_string()));
+                }
+
+                continue;
+            }
+
+            // An address returning its challenge has proven it can receive our packets
+            if delivery_type == PacketDelivery::challenge_response() {
+                self.inner
+                    .reflection
+                    .receive_challenge_response(addr, &packet);
+                continue;
+            }
+
+            // A queued client echoing its position is still waiting
+            if delivery_type == PacketDelivery::queue_position() {
+                self.inner.wait_queue.refresh(&addr);
+                continue;
+            }
+
+            // Discovery probes are answered the same way as detail requests, but only when the
+            // server wants to be found
+            if delivery_type == PacketDelivery::discovery_probe() {
+                if !self.inner.discovery_enabled {
+                    continue;
+                }
+
+                if let Err(e) = self.send_server_details(addr, &packet) {
+                    self.socket_events
+                        .push(SocketEvent::SendPacketFail(e.to_string()));
+                }
+
+                continue;
+            }
+
             // Check size here instead of in poll as ack packets do not fit into padding
             if packet.len() < Self::PACKET_PADDING {
                 continue;
             }
 
+            // An unknown address carrying a known token is an existing client whose address has
+            // changed, so we move the connection over instead of establishing a new one. The
+            // token alone could be replayed from a spoofed address, so the new address must
+            // answer a challenge before anything is sent to it
+            if !self.inner.connection_addr_to_id.contains_key(&addr) {
+                if let Some(token) = Self::get_connection_token_from_packet(&packet)
+                    .filter(|token| self.inner.is_migrating(*token))
+                {
+                    if !self.inner.reflection.is_address_verified(&addr) {
+                        if let Err(e) = self.send_challenge(addr, packet.len()) {
+                            self.socket_events
+                                .push(SocketEvent::SendPacketFail(e.to_string()));
+                        }
+
+                        continue;
+                    }
+
+                    if let Some(old_addr) = self.inner.migrate_connection(token, addr) {
+                        self.ack_manager.retarget_packets(&old_addr, &addr);
+                    }
+                }
+            }
+
+            let Ok(event) = Self::get_event_from_packet(&packet) else {
+                self.socket_events.push(SocketEvent::ReadPacketFail(format!(
+                    "Failed to read the event from {addr}, it may be using a protocol version \
+                     other than {PROTOCOL_VERSION}"
+                )));
+                continue;
+            };
+
+            // Establishes a connection with a client if not already established, a client we
+            // refuse is told why rather than having its packet acknowledged
+            let mut established = false;
+            if !self.inner.connection_addr_to_id.contains_key(&addr) {
+                // Anything a queued client sends is dropped until it is promoted
+                if self.inner.wait_queue.refresh(&addr) {
+                    continue;
+                }
+
+                // An address must prove it can receive our packets before anything larger than a
+                // challenge is sent to it
+                if self.inner.reflection.requires_challenge()
+                    && !self.inner.reflection.is_address_verified(&addr)
+                {
+                    if let Err(e) = self.send_challenge(addr, packet.len()) {
+                        self.socket_events
+                            .push(SocketEvent::SendPacketFail(e.to_string()));
+                    }
+
+                    continue;
+                }
+
+                // An address attempting too many connections is turned away
+                if let Some(kind) = self.inner.rate_limiter.check_new_connection(addr.ip()) {
+                    self.inner
+                        .server_events
+                        .push_back(ServerEvent::OnRateLimitExceeded(addr, kind));
+
+                    if self.inner.rate_limiter.policy() != RateLimitPolicy::Warn {
+                        continue;
+                    }
+                }
+
+                let bytes = Self::get_packet_bytes(&packet).unwrap_or(Default::default());
+                let privileged = self.inner.is_privileged(addr, &event, &bytes);
+
+                if let Err(reason) = self.inner.establish_new_connection(addr, privileged) {
+                    // A full server holds the client in the wait queue if there is room
+                    let position = match reason {
+                        RejectionReason::ServerFull => {
+                            self.inner.wait_queue.enqueue(addr, privileged)
+                        }
+                        _ => None,
+                    };
+
+                    match position {
+                        Some(position) => self
+                            .inner
+                            .server_events
+                            .push_back(ServerEvent::OnClientQueued(addr, position)),
+                        None => self.reject_connection(addr, reason),
+                    }
+
+                    continue;
+                }
+
+                self.send_pending_handshakes();
+                established = true;
+            }
+
+            let Some(client) = self.inner.connection_addr_to_id.get(&addr).copied() else {
+                continue;
+            };
+
+            // A client carrying its token has received our handshake, so it owns its address
+            if Self::get_connection_token_from_packet(&packet)
+                == Some(self.inner.connection_token(&addr))
+            {
+                if let Some(connection) = self.inner.connections.get_mut(&client) {
+                    connection.verified = true;
+                }
+            }
+
+            // A client sending too much is not acknowledged, so it backs off and resends later
+            if let Some(kind) = self
+                .inner
+                .rate_limiter
+                .check_packet(client, &event, packet.len())
+            {
+                if !self.handle_rate_limit_exceeded(client, addr, kind) {
+                    continue;
+                }
+            }
+
             // Send a packet  to acknowledge the sender we have recieved their packet
             if delivery_type.is_reliable() {
                 if let Err(e) = self.send_ack_packet(addr, &packet) {
@@ -246,9 +933,11 @@ impl<'socket> NautSocket<'socket, NautServer> {
                 }
             }
 
-            let Ok(event) = Self::get_event_from_packet(&packet) else {
-                continue;
-            };
+            // Packets held back from an unverified client are only resent in reply to its own,
+            // such as a handshake it never received
+            if !self.inner.is_client_verified(&client) && !established {
+                self.resend_ack_packets_to(&addr);
+            }
 
             if delivery_type.is_sequenced() {
                 let Some(seq_num) = Self::get_seq_from_packet(&packet) else {
@@ -274,29 +963,27 @@ impl<'socket> NautSocket<'socket, NautServer> {
                 };
             }
 
-            // Just ignore the packet and dont establish connection as its maxed out
-            if self.inner.connections.len() >= self.inner.max_connections as usize {
-                continue;
-            }
-
-            // Establishes a connection with a client if not already established
-            if !self.inner.connection_addr_to_id.contains_key(&addr) {
-                self.inner.establish_new_connection(addr);
-            }
-
-            let Some(client) = self.inner.connection_addr_to_id.get(&addr) else {
-                continue;
-            };
-
-            let client = *client;
-            self.inner.time_outs.insert(client, Instant::now());
+            self.inner.touch_client(client);
 
             let bytes = Self::get_packet_bytes(&packet).unwrap_or(Default::default());
-            event_emitter_ref.emit_event(&event, self, (addr, &bytes));
+            let ctx = EventContext {
+                addr,
+                connection_id: Some(client),
+                delivery: delivery_type,
+                sequence: Self::get_seq_if_sequenced(delivery_type, &packet),
+                ack: Self::get_ack_if_reliable(delivery_type, &packet),
+                received_at,
+                payload: &bytes,
+            };
+            event_emitter_ref.emit_event(&event, self, ctx);
         }
 
+        // Nothing is sent to an unverified address unless it is a reply
+        self.inner.reply_budget = None;
+
         // Emit all polled events
         event_emitter.emit_polled_events(self);
+        event_emitter.emit_persistent_changed_events(self);
 
         // Clear server events this time around
         self.inner.server_events.clear();
@@ -308,10 +995,264 @@ impl<'socket> NautSocket<'socket, NautServer> {
         self.event_emitter = event_emitter;
     }
 
+    /// Run a function to supply the application defined payload of the
+    /// [server details](ServerDetails) everytime they are requested. Details larger than the
+    /// [padded request](ServerDetails::PADDED_REQUEST_SIZE) are only sent to verified addresses
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// // Reports the current map to server browsers
+    /// server.on_detail_request(|_server| "de_dust2".as_bytes().to_vec());
+    /// ```
+    pub fn on_detail_request<F>(&mut self, cb: F)
+    where
+        F: Fn(&NautServer) -> Vec<u8> + Send + Sync + 'static,
+    {
+        self.inner.detail_callback = Some(Arc::new(cb));
+    }
+
+    /// Answers a detail request or discovery probe with the [server details](ServerDetails)
+    pub(crate) fn send_server_details(
+        &mut self,
+        addr: SocketAddr,
+        packet: &[u8],
+    ) -> anyhow::Result<()> {
+        let Some(nonce) = ServerDetails::get_nonce_from_packet(packet) else {
+            return Err(anyhow!("No nonce in detail request"));
+        };
+
+        let response = self.inner.get_server_details().response_packet(nonce)?;
+        self.send_guarded(addr, &response, Some(packet.len()))
+    }
+
+    /// Sends a packet outside of a connection. A packet to an unverified address is held back if
+    /// it is larger than the request that triggered it or the
+    /// [unverified send limit](config::ReflectionConfig::unverified_send_limit) is reached
+    pub(crate) fn send_guarded(
+        &mut self,
+        addr: SocketAddr,
+        packet: &[u8],
+        request_len: Option<usize>,
+    ) -> anyhow::Result<()> {
+        if !self.inner.is_address_verified(&addr)
+            && !self
+                .inner
+                .reflection
+                .may_send_unverified(packet.len(), request_len)
+        {
+            return Err(anyhow!(
+                "Packet of {} bytes to unverified address {addr} was held back",
+                packet.len()
+            ));
+        }
+
+        self.socket.send_to(packet, addr)?;
+
+        Ok(())
+    }
+
+    /// Sends a challenge to an address attempting a connection
+    pub(crate) fn send_challenge(&mut self, addr: SocketAddr, request_len: usize) -> anyhow::Result<()> {
+        let challenge = self.inner.reflection.challenge_packet(&addr)?;
+        self.send_guarded(addr, &challenge, Some(request_len))
+    }
+
+    /// Run a function to decide whether a new connection is privileged, given its address and the
+    /// event and bytes of its first packet. Only privileged connections may take the
+    /// [reserved slots](ServerConfig::reserved_slots)
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// // Admins join with a password in their first packet
+    /// server.on_admission(|_server, _addr, event, bytes| {
+    ///     event == "join" && bytes == b"admin-password"
+    /// });
+    /// ```
+    pub fn on_admission<F>(&mut self, cb: F)
+    where
+        F: Fn(&NautServer, SocketAddr, &str, &[u8]) -> bool + Send + Sync + 'static,
+    {
+        self.inner.admission_callback = Some(Arc::new(cb));
+    }
+
+    /// Times out queued clients that stopped responding, promotes queued clients into free slots
+    /// and tells the rest their position in the queue
+    pub(crate) fn update_wait_queue(&mut self) {
+        self.inner.remove_timed_out_queued_clients();
+        self.inner.promote_queued_clients();
+        self.send_pending_handshakes();
+        self.send_pending_rejections();
+
+        for (addr, position) in self.inner.wait_queue.take_due_notifications() {
+            if let Err(e) = self.send_queue_position_packet(addr, position) {
+                self.socket_events
+                    .push(SocketEvent::SendPacketFail(e.to_string()));
+            }
+        }
+    }
+
+    /// Sends a queue position packet carrying the position to the address
+    fn send_queue_position_packet(
+        &mut self,
+        addr: SocketAddr,
+        position: usize,
+    ) -> anyhow::Result<()> {
+        let mut buf = vec![0; Self::DELIVERY_TYPE_BUF + 4];
+
+        // Write that its a queue position to the packet
+        LittleEndian::write_u16(
+            &mut buf
+                [Self::DELIVERY_TYPE_OFFSET..Self::DELIVERY_TYPE_OFFSET + Self::DELIVERY_TYPE_BUF],
+            PacketDelivery::queue_position().packet_delivery_as()?,
+        );
+        LittleEndian::write_u32(&mut buf[Self::DELIVERY_TYPE_BUF..], position as u32);
+
+        self.send_guarded(addr, &buf, None)
+    }
+
+    /// Bans a client's ip address, for the duration if given otherwise forever, and closes its
+    /// connection after telling it it has been banned. The [ban list](BanList) is saved if the
+    /// server has a [ban list file](ServerConfig::ban_list_file)
+    pub fn ban_client(
+        &mut self,
+        id: ConnectionId,
+        duration: Option<Duration>,
+        reason: &str,
+    ) -> anyhow::Result<()> {
+        let addr = *self
+            .inner
+            .get_client_addr(&id)
+            .ok_or(anyhow!("There is no associated address with this client id"))?;
+
+        self.inner
+            .ban_list
+            .ban(IpRange::single(addr.ip()), duration, reason);
+        self.disconnect_banned_client(id, addr);
+
+        if self.inner.ban_list_file.is_some() {
+            self.inner.save_ban_list()?;
+        }
+
+        Ok(())
+    }
+
+    /// Closes a client's connection after telling it it has been banned
+    fn disconnect_banned_client(&mut self, id: ConnectionId, addr: SocketAddr) {
+        let _ = self.send_rejection_packet(addr, RejectionReason::Banned);
+        self.ack_manager.remove_packets_to(&addr);
+        self.inner.close_connection_with_client(id);
+    }
+
+    /// Forgets expired entries of the [ban list](BanList), saving its file if any expired, and
+    /// disconnects every connected client that a change to the ban list has blocked
+    fn update_ban_list(&mut self) {
+        if self.inner.ban_list.remove_expired() && self.inner.ban_list_file.is_some() {
+            if let Err(e) = self.inner.save_ban_list() {
+                self.socket_events.push(SocketEvent::BanListFail(e.to_string()));
+            }
+        }
+
+        if !std::mem::take(&mut self.inner.ban_list_changed) {
+            return;
+        }
+
+        let blocked: Vec<(ConnectionId, SocketAddr)> = self
+            .inner
+            .connections
+            .iter()
+            .filter(|(_, connection)| self.inner.ban_list.is_blocked(&connection.addr.ip()))
+            .map(|(id, connection)| (*id, connection.addr))
+            .collect();
+
+        for (id, addr) in blocked {
+            self.disconnect_banned_client(id, addr);
+        }
+    }
+
+    /// Pushes a [rate limit exceeded event](ServerEvent::OnRateLimitExceeded) and applies the
+    /// [policy](RateLimitPolicy), returning whether the packet should still be processed
+    fn handle_rate_limit_exceeded(
+        &mut self,
+        client: ConnectionId,
+        addr: SocketAddr,
+        kind: RateLimitKind,
+    ) -> bool {
+        self.inner
+            .server_events
+            .push_back(ServerEvent::OnRateLimitExceeded(addr, kind));
+
+        match self.inner.rate_limiter.policy() {
+            RateLimitPolicy::Drop => false,
+            RateLimitPolicy::Warn => true,
+            RateLimitPolicy::Disconnect => {
+                self.inner.rate_limiter.record_disconnect(&client, addr.ip());
+                let _ = self.send_rejection_packet(addr, RejectionReason::RateLimited);
+                self.ack_manager.remove_packets_to(&addr);
+                self.inner.close_connection_with_client(client);
+                false
+            }
+        }
+    }
+
+    /// Tells an address why its connection was refused and pushes a
+    /// [connection rejected event](ServerEvent::OnConnectionRejected) to the server events queue
+    pub(crate) fn reject_connection(&mut self, addr: SocketAddr, reason: RejectionReason) {
+        self.inner
+            .server_events
+            .push_back(ServerEvent::OnConnectionRejected(addr, reason));
+
+        if let Err(e) = self.send_rejection_packet(addr, reason) {
+            self.socket_events
+                .push(SocketEvent::SendPacketFail(e.to_string()));
+        }
+    }
+
+    /// Sends a rejection packet carrying the [reason](RejectionReason) to the address
+    fn send_rejection_packet(
+        &mut self,
+        addr: SocketAddr,
+        reason: RejectionReason,
+    ) -> anyhow::Result<()> {
+        let mut buf = vec![0; Self::DELIVERY_TYPE_BUF + 2];
+
+        // Write that its a rejection to the packet
+        LittleEndian::write_u16(
+            &mut buf
+                [Self::DELIVERY_TYPE_OFFSET..Self::DELIVERY_TYPE_OFFSET + Self::DELIVERY_TYPE_BUF],
+            PacketDelivery::rejection().packet_delivery_as()?,
+        );
+        LittleEndian::write_u16(&mut buf[Self::DELIVERY_TYPE_BUF..], reason.raw());
+
+        self.send_guarded(addr, &buf, None)
+    }
+
+    /// Tells queued clients that could not be promoted why they were refused
+    pub(crate) fn send_pending_rejections(&mut self) {
+        let pending = std::mem::take(&mut self.inner.pending_rejections);
+        for (addr, reason) in pending {
+            self.reject_connection(addr, reason);
+        }
+    }
+
+    /// Sends the [connection established event](CONNECTION_ESTABLISHED_EVENT) to every new
+    /// connection so the client learns its [connection token](ConnectionToken)
+    pub(crate) fn send_pending_handshakes(&mut self) {
+        let pending = std::mem::take(&mut self.inner.pending_handshakes);
+        for id in pending {
+            if let Err(e) = self.send(CONNECTION_ESTABLISHED_EVENT, &[], PacketDelivery::Reliable, id)
+            {
+                self.socket_events
+                    .push(SocketEvent::SendPacketFail(e.to_string()));
+            }
+        }
+    }
+
     /// Sends an event message to all [established connections](EstablishedConnection)
     pub fn broadcast(&mut self, event: &str, buf: &[u8], delivery: PacketDelivery) {
         let connection_ids: Vec<ConnectionId> =
-            { self.inner.connection_id_to_addr.keys().cloned().collect() };
+            { self.inner.connections.keys().cloned().collect() };
 
         for id in connection_ids {
             let _ = self.send(event, buf, delivery, id);
@@ -326,7 +1267,7 @@ impl<'socket> NautSocket<'socket, NautServer> {
         excluded: Vec<ConnectionId>,
     ) {
         let connection_ids: Vec<ConnectionId> =
-            { self.inner.connection_id_to_addr.keys().cloned().collect() };
+            { self.inner.connections.keys().cloned().collect() };
 
         for id in connection_ids {
             if excluded.contains(&id) {
@@ -348,8 +1289,7 @@ impl<'socket> NautSocket<'socket, NautServer> {
         let addr = {
             *self
                 .inner
-                .connection_id_to_addr
-                .get(&client)
+                .get_client_addr(&client)
                 .ok_or(anyhow!(
                     "There is no associated address with this client id"
                 ))?
@@ -401,6 +1341,35 @@ impl<'socket> SocketType<'socket> for NautServer {
 
         Some(*seq)
     }
+
+    fn connection_token(&self, addr: &SocketAddr) -> ConnectionToken {
+        let Some(client_id) = self.connection_addr_to_id.get(addr) else {
+            return 0;
+        };
+
+        self.connections
+            .get(client_id)
+            .map(|connection| connection.token)
+            .unwrap_or_default()
+    }
+
+    fn may_send(&mut self, addr: &SocketAddr, len: usize) -> bool {
+        if self.is_address_verified(addr) || self.outbound_addresses.contains(addr) {
+            return true;
+        }
+
+        // Anything sent to an unverified address must be a reply to the packet being handled
+        let Some((reply_addr, remaining)) = self.reply_budget.as_mut() else {
+            return false;
+        };
+
+        if reply_addr != addr || !self.reflection.may_send_unverified(len, Some(*remaining)) {
+            return false;
+        }
+
+        *remaining -= len;
+        true
+    }
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -411,4 +1380,144 @@ pub enum ServerEvent {
     OnClientTimeout(ConnectionId),
     /// Pushes to the server event queue when a client is disconnected
     OnClientDisconnected(ConnectionId),
+    /// Pushed to the server event queue when a client's address changes, with the old and new
+    /// address
+    OnClientMigrated(ConnectionId, SocketAddr, SocketAddr),
+    /// Pushed to the server event queue when a new connection is refused
+    OnConnectionRejected(SocketAddr, RejectionReason),
+    /// Pushed to the server event queue when a new connection is held in the wait queue, with its
+    /// position in the queue
+    OnClientQueued(SocketAddr, usize),
+    /// Pushed to the server event queue when a queued client stops responding and is removed from
+    /// the queue
+    OnQueuedClientTimeout(SocketAddr),
+    /// Pushed to the server event queue when an address exceeds a rate limit
+    OnRateLimitExceeded(SocketAddr, RateLimitKind),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(s: &str) -> SocketAddr {
+        s.parse().unwrap()
+    }
+
+    /// Establishes a connection which has proven it owns its address, returning its id and token
+    fn verified_connection(
+        server: &mut NautServer,
+        addr: SocketAddr,
+    ) -> (ConnectionId, ConnectionToken) {
+        let id = server.establish_new_connection(addr, false).unwrap();
+        let connection = server.connections.get_mut(&id).unwrap();
+        connection.verified = true;
+
+        (id, connection.token)
+    }
+
+    /// Answers a challenge from the address, as a client that can receive packets there would
+    fn answer_challenge(server: &mut NautServer, addr: SocketAddr) {
+        let challenge = server.reflection.challenge_packet(&addr).unwrap();
+        let answer = ReflectionGuard::challenge_answer(&challenge, 1024).unwrap();
+        assert!(server.reflection.receive_challenge_response(addr, &answer));
+    }
+
+    #[test]
+    fn connection_only_migrates_to_an_address_that_answered_a_challenge() {
+        let mut server = NautServer::new(ServerConfig::default());
+        let old_addr = addr("127.0.0.1:4000");
+        let (id, token) = verified_connection(&mut server, old_addr);
+
+        // A replayed token alone does not move the connection
+        let new_addr = addr("127.0.0.1:4001");
+        assert_eq!(server.migrate_connection(token, new_addr), None);
+        assert_eq!(server.get_client_addr(&id), Some(&old_addr));
+
+        answer_challenge(&mut server, new_addr);
+        assert_eq!(server.migrate_connection(token, new_addr), Some(old_addr));
+        assert_eq!(server.get_client_addr(&id), Some(&new_addr));
+        assert!(server.is_client_verified(&id));
+
+        // The connection is verified itself, so the address needs no separate entry
+        assert!(!server.reflection.is_address_verified(&new_addr));
+    }
+
+    #[test]
+    fn migration_needs_a_known_token_and_a_free_address() {
+        let mut server = NautServer::new(ServerConfig::default());
+        let (_, token) = verified_connection(&mut server, addr("127.0.0.1:4000"));
+        verified_connection(&mut server, addr("127.0.0.1:4001"));
+        answer_challenge(&mut server, addr("127.0.0.1:4001"));
+        answer_challenge(&mut server, addr("127.0.0.1:4002"));
+
+        assert_eq!(
+            server.migrate_connection(token, addr("127.0.0.1:4001")),
+            None
+        );
+        assert_eq!(
+            server.migrate_connection(token.wrapping_add(1), addr("127.0.0.1:4002")),
+            None
+        );
+
+        let mut server = NautServer::new(ServerConfig {
+            allow_connection_migration: false,
+            ..Default::default()
+        });
+        let (_, token) = verified_connection(&mut server, addr("127.0.0.1:4000"));
+        answer_challenge(&mut server, addr("127.0.0.1:4002"));
+        assert_eq!(
+            server.migrate_connection(token, addr("127.0.0.1:4002")),
+            None
+        );
+    }
+
+    #[test]
+    fn migration_respects_connection_limits() {
+        let mut server = NautServer::new(ServerConfig {
+            max_connections_per_ip: Some(1),
+            ..Default::default()
+        });
+        let (id, token) = verified_connection(&mut server, addr("10.0.0.1:4000"));
+        verified_connection(&mut server, addr("10.0.0.2:4000"));
+        for new_addr in ["10.0.0.1:4001", "10.0.0.2:4001"] {
+            answer_challenge(&mut server, addr(new_addr));
+        }
+
+        // Another port on the same ip is the same connection moving, not a new one
+        assert!(server
+            .migrate_connection(token, addr("10.0.0.1:4001"))
+            .is_some());
+
+        assert_eq!(
+            server.migrate_connection(token, addr("10.0.0.2:4001")),
+            None
+        );
+        assert_eq!(server.get_client_addr(&id), Some(&addr("10.0.0.1:4001")));
+        assert_eq!(
+            server.get_connections_from_ip(&"10.0.0.1".parse().unwrap()),
+            1
+        );
+        assert_eq!(
+            server.get_connections_from_ip(&"10.0.0.2".parse().unwrap()),
+            1
+        );
+
+        let mut server = NautServer::new(ServerConfig {
+            max_connections_per_prefix: Some(1),
+            ..Default::default()
+        });
+        let (_, token) = verified_connection(&mut server, addr("10.0.0.1:4000"));
+        verified_connection(&mut server, addr("10.0.1.1:4000"));
+        for new_addr in ["10.0.1.2:4000", "10.0.0.9:4000"] {
+            answer_challenge(&mut server, addr(new_addr));
+        }
+
+        assert_eq!(
+            server.migrate_connection(token, addr("10.0.1.2:4000")),
+            None
+        );
+        assert!(server
+            .migrate_connection(token, addr("10.0.0.9:4000"))
+            .is_some());
+    }
 }
@@ -0,0 +1,125 @@
+use std::{collections::VecDeque, net::SocketAddr, time::Instant};
+
+use super::config::WaitQueueConfig;
+
+/// A client held in the [wait queue](WaitQueue) until a slot is free
+pub(crate) struct QueuedClient {
+    /// The address of the queued client
+    pub addr: SocketAddr,
+    /// Whether the client may take a reserved slot
+    pub privileged: bool,
+    /// The last time we received a packet from the client
+    pub last_seen: Instant,
+    /// The last time the client was told its position
+    pub last_notified: Option<Instant>,
+}
+
+/// Holds clients over capacity until they can be promoted to a connection
+#[derive(Default)]
+pub(crate) struct WaitQueue {
+    /// How the queue behaves, the queue is disabled without one
+    pub config: Option<WaitQueueConfig>,
+    /// The queued clients in the order they joined
+    pub clients: VecDeque<QueuedClient>,
+}
+
+impl WaitQueue {
+    /// Creates a new wait queue, disabled if there is no config
+    pub(crate) fn new(config: Option<WaitQueueConfig>) -> Self {
+        Self {
+            config,
+            clients: VecDeque::new(),
+        }
+    }
+
+    /// Gets the position of the address in the queue, starting from 1
+    pub(crate) fn position(&self, addr: &SocketAddr) -> Option<usize> {
+        self.clients
+            .iter()
+            .position(|client| client.addr == *addr)
+            .map(|index| index + 1)
+    }
+
+    /// Adds the address to the back of the queue, returning its position or none if the queue is
+    /// disabled or full
+    pub(crate) fn enqueue(&mut self, addr: SocketAddr, privileged: bool) -> Option<usize> {
+        let config = self.config?;
+        if self.clients.len() >= config.max_size {
+            return None;
+        }
+
+        self.clients.push_back(QueuedClient {
+            addr,
+            privileged,
+            last_seen: Instant::now(),
+            last_notified: None,
+        });
+
+        Some(self.clients.len())
+    }
+
+    /// Marks a queued client as still responding, returning whether the address is queued
+    pub(crate) fn refresh(&mut self, addr: &SocketAddr) -> bool {
+        let Some(client) = self.clients.iter_mut().find(|client| client.addr == *addr) else {
+            return false;
+        };
+
+        client.last_seen = Instant::now();
+        true
+    }
+
+    /// Removes every queued client that has stopped responding, returning their addresses
+    pub(crate) fn remove_timed_out(&mut self) -> Vec<SocketAddr> {
+        let Some(config) = self.config else {
+            return Vec::new();
+        };
+
+        let mut timed_out = Vec::new();
+        self.clients.retain(|client| {
+            if client.last_seen.elapsed() < config.timeout {
+                return true;
+            }
+
+            timed_out.push(client.addr);
+            false
+        });
+
+        timed_out
+    }
+
+    /// Removes the first queued client that fits, checked by whether it is privileged
+    pub(crate) fn take_next<F>(&mut self, fits: F) -> Option<QueuedClient>
+    where
+        F: Fn(bool) -> bool,
+    {
+        let index = self
+            .clients
+            .iter()
+            .position(|client| fits(client.privileged))?;
+
+        self.clients.remove(index)
+    }
+
+    /// Gets the queued clients due to be told their position alongside their position, marking
+    /// them as told
+    pub(crate) fn take_due_notifications(&mut self) -> Vec<(SocketAddr, usize)> {
+        let Some(config) = self.config else {
+            return Vec::new();
+        };
+
+        let mut due = Vec::new();
+        for (index, client) in self.clients.iter_mut().enumerate() {
+            if client
+                .last_notified
+                .is_some_and(|notified| notified.elapsed() < config.position_interval)
+            {
+                continue;
+            }
+
+            client.last_notified = Some(Instant::now());
+            due.push((client.addr, index + 1));
+        }
+
+        due
+    }
+}
@@ -0,0 +1,350 @@
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    time::{Duration, Instant},
+};
+
+use crate::client::ConnectionId;
+
+/// How much of something is allowed through each second, with a burst allowing short spikes
+#[derive(Clone, Copy, Debug)]
+pub struct RateLimit {
+    /// How much is allowed through each second on average
+    pub per_second: u32,
+    /// How much can be let through at once after a quiet period
+    pub burst: u32,
+}
+
+impl RateLimit {
+    /// Creates a rate limit with a burst equal to a second's worth
+    pub fn per_second(per_second: u32) -> Self {
+        Self {
+            per_second,
+            burst: per_second,
+        }
+    }
+}
+
+/// A token bucket enforcing a [rate limit](RateLimit), refilled as time passes
+#[derive(Clone, Debug)]
+pub(crate) struct TokenBucket {
+    limit: RateLimit,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    /// Creates a new full token bucket
+    pub(crate) fn new(limit: RateLimit) -> Self {
+        Self {
+            limit,
+            tokens: limit.burst as f64,
+            last_refill: Instant::now(),
+        }
+    }
+
+    /// Refills the bucket for the time passed since it was last refilled
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens =
+            (self.tokens + elapsed * self.limit.per_second as f64).min(self.limit.burst as f64);
+        self.last_refill = now;
+    }
+
+    /// Takes the amount of tokens from the bucket, returning false and taking nothing if there
+    /// are not enough
+    pub(crate) fn try_take(&mut self, amount: u32) -> bool {
+        self.refill();
+
+        if self.tokens < amount as f64 {
+            return false;
+        }
+
+        self.tokens -= amount as f64;
+        true
+    }
+
+    /// Takes every token from the bucket
+    pub(crate) fn empty(&mut self) {
+        self.tokens = 0.0;
+        self.last_refill = Instant::now();
+    }
+
+    /// Checks if the bucket would be full by now, so forgetting it changes nothing
+    pub(crate) fn is_full(&self) -> bool {
+        let elapsed = self.last_refill.elapsed().as_secs_f64();
+        self.tokens + elapsed * self.limit.per_second as f64 >= self.limit.burst as f64
+    }
+}
+
+/// What happens when a rate limit is exceeded. A
+/// [rate limit exceeded event](crate::server::ServerEvent::OnRateLimitExceeded) is pushed whatever
+/// the policy
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RateLimitPolicy {
+    /// The packet is dropped
+    Drop,
+    /// The packet is still processed, useful to tune limits before enforcing them
+    Warn,
+    /// The packet is dropped and the client is disconnected, new connection attempts are dropped
+    Disconnect,
+}
+
+/// Which rate limit was exceeded
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RateLimitKind {
+    /// The connection sent too many packets
+    Packets,
+    /// The connection sent too many bytes
+    Bytes,
+    /// The connection sent an event too often
+    Event,
+    /// An ip address attempted too many new connections
+    NewConnections,
+}
+
+/// The config of how the [server](crate::server::NautServer) limits the rate of packets
+#[derive(Clone, Debug)]
+pub struct RateLimitConfig {
+    /// How many packets each connection may send
+    pub connection_packets: Option<RateLimit>,
+    /// How many bytes each connection may send
+    pub connection_bytes: Option<RateLimit>,
+    /// How often each connection may send specific events
+    pub events: HashMap<String, RateLimit>,
+    /// How many new connections each ip address may attempt
+    pub new_connections_per_ip: Option<RateLimit>,
+    /// What happens when a limit is exceeded
+    pub policy: RateLimitPolicy,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            connection_packets: Some(RateLimit::per_second(120)),
+            connection_bytes: Some(RateLimit::per_second(64 * 1024)),
+            events: HashMap::new(),
+            new_connections_per_ip: Some(RateLimit::per_second(4)),
+            policy: RateLimitPolicy::Drop,
+        }
+    }
+}
+
+/// Counters of how often rate limits were exceeded
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RateLimitStats {
+    /// Packets from connections that exceeded a limit
+    pub limited_packets: u64,
+    /// Bytes of the packets from connections that exceeded a limit
+    pub limited_bytes: u64,
+    /// New connection attempts that exceeded a limit
+    pub limited_connection_attempts: u64,
+    /// Connections disconnected for exceeding a limit
+    pub disconnects: u64,
+}
+
+/// The token buckets of a single connection
+#[derive(Default)]
+struct ConnectionBuckets {
+    packets: Option<TokenBucket>,
+    bytes: Option<TokenBucket>,
+    events: HashMap<String, TokenBucket>,
+    stats: RateLimitStats,
+}
+
+/// Enforces the [rate limit config](RateLimitConfig) across every connection
+#[derive(Default)]
+pub(crate) struct RateLimiter {
+    config: Option<RateLimitConfig>,
+    connections: HashMap<ConnectionId, ConnectionBuckets>,
+    new_connections: HashMap<IpAddr, TokenBucket>,
+    last_cleanup: Option<Instant>,
+    stats: RateLimitStats,
+}
+
+impl RateLimiter {
+    /// How often buckets of addresses that stopped attempting connections are forgotten
+    const CLEANUP_INTERVAL: Duration = Duration::from_secs(10);
+
+    /// Creates a new rate limiter, limiting nothing if there is no config
+    pub(crate) fn new(config: Option<RateLimitConfig>) -> Self {
+        Self {
+            config,
+            ..Default::default()
+        }
+    }
+
+    /// What happens when a limit is exceeded
+    pub(crate) fn policy(&self) -> RateLimitPolicy {
+        self.config
+            .as_ref()
+            .map(|config| config.policy)
+            .unwrap_or(RateLimitPolicy::Drop)
+    }
+
+    /// Gets the counters across every connection
+    pub(crate) fn stats(&self) -> &RateLimitStats {
+        &self.stats
+    }
+
+    /// Gets the counters of a single connection
+    pub(crate) fn connection_stats(&self, id: &ConnectionId) -> Option<&RateLimitStats> {
+        Some(&self.connections.get(id)?.stats)
+    }
+
+    /// Checks if the ip address may attempt a new connection
+    pub(crate) fn check_new_connection(&mut self, addr: IpAddr) -> Option<RateLimitKind> {
+        let limit = self.config.as_ref()?.new_connections_per_ip?;
+        let bucket = self
+            .new_connections
+            .entry(addr)
+            .or_insert_with(|| TokenBucket::new(limit));
+
+        if bucket.try_take(1) {
+            return None;
+        }
+
+        self.stats.limited_connection_attempts += 1;
+        Some(RateLimitKind::NewConnections)
+    }
+
+    /// Checks if the connection may send a packet of the event and length
+    pub(crate) fn check_packet(
+        &mut self,
+        id: ConnectionId,
+        event: &str,
+        len: usize,
+    ) -> Option<RateLimitKind> {
+        let config = self.config.as_ref()?;
+        let buckets = self
+            .connections
+            .entry(id)
+            .or_insert_with(|| ConnectionBuckets {
+                packets: config.connection_packets.map(TokenBucket::new),
+                bytes: config.connection_bytes.map(TokenBucket::new),
+                ..Default::default()
+            });
+
+        let kind = if buckets
+            .packets
+            .as_mut()
+            .is_some_and(|bucket| !bucket.try_take(1))
+        {
+            Some(RateLimitKind::Packets)
+        } else if buckets
+            .bytes
+            .as_mut()
+            .is_some_and(|bucket| !bucket.try_take(len as u32))
+        {
+            Some(RateLimitKind::Bytes)
+        } else if let Some(limit) = config.events.get(event) {
+            let bucket = buckets
+                .events
+                .entry(event.to_string())
+                .or_insert_with(|| TokenBucket::new(*limit));
+
+            (!bucket.try_take(1)).then_some(RateLimitKind::Event)
+        } else {
+            None
+        };
+
+        if kind.is_some() {
+            for stats in [&mut self.stats, &mut buckets.stats] {
+                stats.limited_packets += 1;
+                stats.limited_bytes += len as u64;
+            }
+        }
+
+        kind
+    }
+
+    /// Counts a connection being disconnected for exceeding a limit, emptying the new connection
+    /// bucket of its ip address so it cannot immediately reconnect
+    pub(crate) fn record_disconnect(&mut self, id: &ConnectionId, addr: IpAddr) {
+        self.stats.disconnects += 1;
+        if let Some(buckets) = self.connections.get_mut(id) {
+            buckets.stats.disconnects += 1;
+        }
+
+        let Some(limit) = self
+            .config
+            .as_ref()
+            .and_then(|config| config.new_connections_per_ip)
+        else {
+            return;
+        };
+
+        self.new_connections
+            .entry(addr)
+            .or_insert_with(|| TokenBucket::new(limit))
+            .empty();
+    }
+
+    /// Forgets the buckets of a freed connection
+    pub(crate) fn remove_connection(&mut self, id: &ConnectionId) {
+        self.connections.remove(id);
+    }
+
+    /// Forgets the buckets of addresses that have stopped attempting connections
+    pub(crate) fn remove_idle_buckets(&mut self) {
+        if self
+            .last_cleanup
+            .is_some_and(|last_cleanup| last_cleanup.elapsed() < Self::CLEANUP_INTERVAL)
+        {
+            return;
+        }
+
+        self.new_connections.retain(|_, bucket| !bucket.is_full());
+        self.last_cleanup = Some(Instant::now());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn bucket(per_second: u32, burst: u32) -> TokenBucket {
+        TokenBucket::new(RateLimit { per_second, burst })
+    }
+
+    /// Moves the last refill back, as if the time had passed
+    fn wait(bucket: &mut TokenBucket, duration: Duration) {
+        bucket.last_refill -= duration;
+    }
+
+    #[test]
+    fn bucket_starts_full_and_allows_its_burst() {
+        let mut bucket = bucket(1, 10);
+        assert!(bucket.is_full());
+        assert!(bucket.try_take(4));
+        assert!(bucket.try_take(6));
+        assert!(!bucket.try_take(1));
+    }
+
+    #[test]
+    fn refused_take_takes_nothing() {
+        let mut bucket = bucket(1, 10);
+        assert!(!bucket.try_take(11));
+        assert!(bucket.try_take(10));
+    }
+
+    #[test]
+    fn bucket_refills_over_time_up_to_its_burst() {
+        let mut bucket = bucket(100, 200);
+        bucket.empty();
+        assert!(!bucket.is_full());
+
+        wait(&mut bucket, Duration::from_millis(500));
+        assert!(!bucket.try_take(60));
+        assert!(bucket.try_take(50));
+
+        // However long it waits the bucket holds no more than its burst
+        wait(&mut bucket, Duration::from_secs(60));
+        assert!(bucket.is_full());
+        assert!(!bucket.try_take(201));
+        assert!(bucket.try_take(200));
+    }
+}
@@ -0,0 +1,256 @@
+use std::{
+    collections::{hash_map::RandomState, HashMap},
+    hash::BuildHasher,
+    net::SocketAddr,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::anyhow;
+use byteorder::{ByteOrder, LittleEndian};
+
+use crate::packet::{IntoPacketDelivery, PacketDelivery};
+
+use super::{config::ReflectionConfig, rate_limit::TokenBucket};
+
+/// Keeps the [server](crate::server::NautServer) from being used to reflect traffic at a spoofed
+/// address. An address is unverified until it proves it can receive our packets, and until then
+/// it never receives a response larger than its request and shares a limited send budget with
+/// every other unverified address
+pub(crate) struct ReflectionGuard {
+    config: ReflectionConfig,
+    /// Seeds the challenge cookies, so they cannot be forged without seeing our packets
+    secret: RandomState,
+    /// Addresses that returned a valid challenge and may now establish a connection
+    verified_addresses: HashMap<SocketAddr, Instant>,
+    unverified_budget: Option<TokenBucket>,
+}
+
+impl ReflectionGuard {
+    /// The offset in a challenge packet of the cookie
+    const COOKIE_OFFSET: usize = 2;
+    /// The amount of space in a challenge packet for the cookie
+    const COOKIE_BUF: usize = 8;
+    /// The offset in a challenge packet of the size its answer must be padded to
+    const REQUIRED_SIZE_OFFSET: usize = Self::COOKIE_OFFSET + Self::COOKIE_BUF;
+    /// The size of a challenge packet
+    const CHALLENGE_SIZE: usize = Self::REQUIRED_SIZE_OFFSET + 2;
+
+    /// How long a challenge cookie stays valid, a cookie is accepted for up to twice as long
+    const COOKIE_WINDOW: Duration = Duration::from_secs(10);
+    /// How long a verified address has to establish its connection
+    const VERIFIED_ADDRESS_LIFETIME: Duration = Duration::from_secs(30);
+
+    /// Creates a new reflection guard from the config
+    pub(crate) fn new(config: ReflectionConfig) -> Self {
+        Self {
+            unverified_budget: config.unverified_send_limit.map(TokenBucket::new),
+            config,
+            secret: RandomState::new(),
+            verified_addresses: HashMap::new(),
+        }
+    }
+
+    /// Whether new connections must answer a challenge before they are established
+    pub(crate) fn requires_challenge(&self) -> bool {
+        self.config.require_challenge
+    }
+
+    /// Checks if a response may be sent to an unverified address, taking its size from the send
+    /// budget. A response to a request must be no larger than the request
+    pub(crate) fn may_send_unverified(&mut self, len: usize, request_len: Option<usize>) -> bool {
+        if request_len.is_some_and(|request_len| len > request_len) {
+            return false;
+        }
+
+        self.unverified_budget
+            .as_mut()
+            .map(|budget| budget.try_take(len as u32))
+            .unwrap_or(true)
+    }
+
+    /// Creates a challenge packet for the address, which it must return padded to the
+    /// [min connection request size](ReflectionConfig::min_connection_request_size)
+    pub(crate) fn challenge_packet(&self, addr: &SocketAddr) -> anyhow::Result<Vec<u8>> {
+        let mut buf = vec![0; Self::CHALLENGE_SIZE];
+        LittleEndian::write_u16(
+            &mut buf[0..2],
+            PacketDelivery::challenge().packet_delivery_as()?,
+        );
+        LittleEndian::write_u64(
+            &mut buf[Self::COOKIE_OFFSET..Self::COOKIE_OFFSET + Self::COOKIE_BUF],
+            self.cookie(addr, Self::current_window()),
+        );
+        LittleEndian::write_u16(
+            &mut buf[Self::REQUIRED_SIZE_OFFSET..Self::CHALLENGE_SIZE],
+            self.config.min_connection_request_size,
+        );
+
+        Ok(buf)
+    }
+
+    /// Creates the answer to a challenge packet, carrying its cookie back padded to the size it
+    /// asks for, unless it asks for more than the max size
+    pub(crate) fn challenge_answer(packet: &[u8], max_size: usize) -> anyhow::Result<Vec<u8>> {
+        if packet.len() < Self::CHALLENGE_SIZE {
+            return Err(anyhow!("Challenge is too short"));
+        }
+
+        let required_size =
+            LittleEndian::read_u16(&packet[Self::REQUIRED_SIZE_OFFSET..Self::CHALLENGE_SIZE])
+                as usize;
+        if required_size > max_size {
+            return Err(anyhow!(
+                "Challenge asks for a {required_size} byte answer, more than the max of {max_size}"
+            ));
+        }
+
+        let mut buf = vec![0; required_size.max(Self::REQUIRED_SIZE_OFFSET)];
+        LittleEndian::write_u16(
+            &mut buf[0..2],
+            PacketDelivery::challenge_response().packet_delivery_as()?,
+        );
+        buf[Self::COOKIE_OFFSET..Self::REQUIRED_SIZE_OFFSET]
+            .copy_from_slice(&packet[Self::COOKIE_OFFSET..Self::REQUIRED_SIZE_OFFSET]);
+
+        Ok(buf)
+    }
+
+    /// Gets the cookie from a challenge or challenge response packet
+    pub(crate) fn get_cookie_from_packet(packet: &[u8]) -> Option<u64> {
+        let cookie = packet.get(Self::COOKIE_OFFSET..Self::COOKIE_OFFSET + Self::COOKIE_BUF)?;
+        Some(LittleEndian::read_u64(cookie))
+    }
+
+    /// Verifies the address if the challenge response carries its cookie and is padded to the
+    /// [min connection request size](ReflectionConfig::min_connection_request_size), returning
+    /// whether it was verified
+    pub(crate) fn receive_challenge_response(&mut self, addr: SocketAddr, packet: &[u8]) -> bool {
+        if packet.len() < self.config.min_connection_request_size as usize {
+            return false;
+        }
+
+        let Some(cookie) = Self::get_cookie_from_packet(packet) else {
+            return false;
+        };
+
+        // A cookie handed out just before the window changed is still accepted
+        let window = Self::current_window();
+        if cookie != self.cookie(&addr, window)
+            && cookie != self.cookie(&addr, window.saturating_sub(1))
+        {
+            return false;
+        }
+
+        self.verified_addresses.insert(addr, Instant::now());
+        true
+    }
+
+    /// Checks if the address returned a challenge and has not yet established its connection
+    pub(crate) fn is_address_verified(&self, addr: &SocketAddr) -> bool {
+        self.verified_addresses
+            .get(addr)
+            .is_some_and(|verified| verified.elapsed() < Self::VERIFIED_ADDRESS_LIFETIME)
+    }
+
+    /// Forgets a verified address once its connection is established
+    pub(crate) fn remove_verified_address(&mut self, addr: &SocketAddr) {
+        self.verified_addresses.remove(addr);
+    }
+
+    /// Forgets verified addresses that never established their connection
+    pub(crate) fn remove_expired(&mut self) {
+        self.verified_addresses
+            .retain(|_, verified| verified.elapsed() < Self::VERIFIED_ADDRESS_LIFETIME);
+    }
+
+    /// Derives the cookie of an address for a window of time
+    fn cookie(&self, addr: &SocketAddr, window: u64) -> u64 {
+        self.secret.hash_one((addr, window))
+    }
+
+    /// Gets the window of time cookies are currently handed out for
+    fn current_window() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_secs() / Self::COOKIE_WINDOW.as_secs())
+            .unwrap_or_default()
+    }
+}
+
+impl Default for ReflectionGuard {
+    fn default() -> Self {
+        Self::new(ReflectionConfig::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(s: &str) -> SocketAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn answered_challenge_verifies_its_address() {
+        let mut guard = ReflectionGuard::default();
+        let client = addr("127.0.0.1:4000");
+        assert!(!guard.is_address_verified(&client));
+
+        let challenge = guard.challenge_packet(&client).unwrap();
+        let answer = ReflectionGuard::challenge_answer(&challenge, 1024).unwrap();
+        assert_eq!(
+            answer.len(),
+            ReflectionConfig::default().min_connection_request_size as usize
+        );
+
+        assert!(guard.receive_challenge_response(client, &answer));
+        assert!(guard.is_address_verified(&client));
+
+        guard.remove_verified_address(&client);
+        assert!(!guard.is_address_verified(&client));
+    }
+
+    #[test]
+    fn cookie_is_only_valid_for_its_address() {
+        let mut guard = ReflectionGuard::default();
+        let challenge = guard.challenge_packet(&addr("127.0.0.1:4000")).unwrap();
+        let answer = ReflectionGuard::challenge_answer(&challenge, 1024).unwrap();
+
+        let victim = addr("127.0.0.1:4001");
+        assert!(!guard.receive_challenge_response(victim, &answer));
+        assert!(!guard.is_address_verified(&victim));
+    }
+
+    #[test]
+    fn forged_or_short_answers_are_rejected() {
+        let mut guard = ReflectionGuard::default();
+        let client = addr("127.0.0.1:4000");
+        let challenge = guard.challenge_packet(&client).unwrap();
+        let answer = ReflectionGuard::challenge_answer(&challenge, 1024).unwrap();
+
+        // Another guard has a different secret, so its cookies mean nothing to us
+        let other = ReflectionGuard::default();
+        let forged = other.challenge_packet(&client).unwrap();
+        let forged = ReflectionGuard::challenge_answer(&forged, 1024).unwrap();
+        assert!(!guard.receive_challenge_response(client, &forged));
+
+        assert!(!guard.receive_challenge_response(client, &answer[..answer.len() - 1]));
+        assert!(!guard.is_address_verified(&client));
+
+        // An answer larger than the client allows is never sent
+        assert!(ReflectionGuard::challenge_answer(&challenge, 255).is_err());
+    }
+
+    #[test]
+    fn unverified_responses_are_no_larger_than_requests() {
+        let mut guard = ReflectionGuard::new(ReflectionConfig {
+            unverified_send_limit: None,
+            ..Default::default()
+        });
+
+        assert!(guard.may_send_unverified(64, Some(64)));
+        assert!(!guard.may_send_unverified(65, Some(64)));
+        assert!(guard.may_send_unverified(4096, None));
+    }
+}
@@ -0,0 +1,36 @@
+use anyhow::anyhow;
+
+/// Why a [server](crate::server::NautServer) refused to establish a connection, sent to the
+/// rejected address so the client knows why it was turned away
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[repr(u16)]
+pub enum RejectionReason {
+    /// The server has reached its max amount of connections
+    ServerFull = 0,
+    /// The address has been banned from the server
+    Banned = 1,
+    /// The address exceeded a rate limit
+    RateLimited = 2,
+    /// The address, or the network it belongs to, already has the max amount of connections
+    TooManyConnectionsFromAddress = 3,
+}
+
+impl RejectionReason {
+    /// Creates a rejection reason from the value sent over the wire
+    pub fn from_raw(value: u16) -> anyhow::Result<Self> {
+        match value {
+            0 => Ok(Self::ServerFull),
+            1 => Ok(Self::Banned),
+            2 => Ok(Self::RateLimited),
+            3 => Ok(Self::TooManyConnectionsFromAddress),
+            _ => Err(anyhow!(
+                "Cannot turn value {value} into type of RejectionReason"
+            )),
+        }
+    }
+
+    /// The value sent over the wire
+    pub fn raw(&self) -> u16 {
+        *self as u16
+    }
+}
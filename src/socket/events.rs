@@ -10,4 +10,7 @@ pub enum SocketEvent {
     PacketDiscard(String),
     ReadPacketFail(String),
     SendPacketFail(String),
+    SnapshotFail(String),
+    BanListFail(String),
+    ReplicationFail(String),
 }
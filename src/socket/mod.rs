@@ -1,6 +1,7 @@
 pub mod events;
 
 use std::{
+    any::TypeId,
     collections::VecDeque,
     marker::PhantomData,
     net::{SocketAddr, ToSocketAddrs, UdpSocket},
@@ -18,14 +19,17 @@ use crate::{
         manager::AcknowledgementManager,
         packet::{AckNumber, AckPacket},
     },
-    events::{EventCallbackArgs, EventEmitter},
+    client::ConnectionToken,
+    events::{EventContext, EventEmitter},
     packet::{IntoPacketDelivery, PacketDelivery},
-    persistent::{storage::PersistentStorage, Persistent},
+    persistent::{snapshot::SnapshotConfig, storage::PersistentStorage, Persistent},
     plugins::SocketPlugin,
+    rpc::RpcManager,
     sequence::SequenceNumber,
 };
 
-pub type ReceivedPacket = (SocketAddr, Vec<u8>);
+/// A packet in the queue, with the address it was sent from and when it was polled
+pub type ReceivedPacket = (SocketAddr, Vec<u8>, Instant);
 
 pub struct NautSocket<'socket, S>
 where
@@ -42,6 +46,8 @@ where
     pub(crate) socket_events: Vec<SocketEvent>,
 
     pub(crate) persistent: PersistentStorage,
+
+    pub(crate) rpc: RpcManager<'socket, S>,
 }
 
 impl<'socket, S> NautSocket<'socket, S>
@@ -63,13 +69,21 @@ where
     /// The amount of space in each packet for the ack number
     pub const ACK_NUM_BUF: usize = 4;
 
+    /// The offset in the packet of the connection token
+    pub const CONNECTION_TOKEN_OFFSET: usize = 10;
+    /// The amount of space in each packet for the connection token
+    pub const CONNECTION_TOKEN_BUF: usize = 8;
+
     /// The offset in the packet for the length of the event title
-    pub const EVENT_LEN_OFFSET: usize = 10;
+    pub const EVENT_LEN_OFFSET: usize = 18;
     /// The amount of space in each packet for the length of the event title
     pub const EVENT_LEN_BUF: usize = 4;
 
-    pub const PACKET_PADDING: usize =
-        Self::DELIVERY_TYPE_BUF + Self::SEQ_NUM_BUF + Self::ACK_NUM_BUF + Self::EVENT_LEN_BUF;
+    pub const PACKET_PADDING: usize = Self::DELIVERY_TYPE_BUF
+        + Self::SEQ_NUM_BUF
+        + Self::ACK_NUM_BUF
+        + Self::CONNECTION_TOKEN_BUF
+        + Self::EVENT_LEN_BUF;
 
     /// Reference to the [raw socket](Self::socket)
     pub fn socket(&self) -> &UdpSocket {
@@ -86,12 +100,12 @@ where
         let mut buf = vec![0; 1024];
         while let Ok((size, addr)) = self.socket.recv_from(&mut buf) {
             let buf = buf[0..size].to_vec();
-            self.packet_queue.push_back((addr, buf));
+            self.packet_queue.push_back((addr, buf, Instant::now()));
         }
     }
 
     /// Retries a packet after [retry time](AcknowledgementManager::ack_retry_time)
-    pub(crate) fn retry_ack_packets(&self) {
+    pub(crate) fn retry_ack_packets(&mut self) {
         for AckPacket {
             bytes,
             time_created,
@@ -102,7 +116,28 @@ where
                 continue;
             }
 
-            let _ = self.socket.send_to(bytes, target);
+            let Ok(addr) = SocketAddr::from_str(target) else {
+                continue;
+            };
+
+            if !self.inner.may_send(&addr, bytes.len()) {
+                continue;
+            }
+
+            let _ = self.socket.send_to(bytes, addr);
+        }
+    }
+
+    /// Resends every packet waiting on an ack from the address without waiting on the
+    /// [retry time](AcknowledgementManager::ack_retry_time)
+    pub(crate) fn resend_ack_packets_to(&mut self, addr: &SocketAddr) {
+        let target = addr.to_string();
+        for packet in self.ack_manager.packets_waiting_on_ack.values() {
+            if packet.target != target || !self.inner.may_send(addr, packet.bytes.len()) {
+                continue;
+            }
+
+            let _ = self.socket.send_to(&packet.bytes, addr);
         }
     }
 
@@ -148,6 +183,43 @@ where
         )))
     }
 
+    /// Gets the raw sequence number from the packet if its delivery is sequenced
+    pub(crate) fn get_seq_if_sequenced(delivery: PacketDelivery, buf: &[u8]) -> Option<u32> {
+        if !delivery.is_sequenced() {
+            return None;
+        }
+
+        Self::get_seq_from_packet(buf).map(|seq_num| seq_num.raw())
+    }
+
+    /// Gets the acknowledgement number from the packet if its delivery is reliable
+    pub(crate) fn get_ack_if_reliable(delivery: PacketDelivery, buf: &[u8]) -> Option<u32> {
+        if !delivery.is_reliable() {
+            return None;
+        }
+
+        Self::get_ack_num_from_packet(buf)
+    }
+
+    /// Gets the [connection token](ConnectionToken) from the packet, a token of 0 means the
+    /// sender has not been given one yet
+    pub(crate) fn get_connection_token_from_packet(buf: &[u8]) -> Option<ConnectionToken> {
+        if Self::CONNECTION_TOKEN_OFFSET + Self::CONNECTION_TOKEN_BUF > buf.len() {
+            return None;
+        }
+
+        let token = LittleEndian::read_u64(
+            &buf[Self::CONNECTION_TOKEN_OFFSET
+                ..Self::CONNECTION_TOKEN_OFFSET + Self::CONNECTION_TOKEN_BUF],
+        );
+
+        if token == 0 {
+            return None;
+        }
+
+        Some(token)
+    }
+
     /// Get the event title from the packet
     pub(crate) fn get_event_from_packet(buf: &[u8]) -> anyhow::Result<String> {
         let length = LittleEndian::read_u32(
@@ -192,13 +264,18 @@ where
         addr: A,
     ) -> anyhow::Result<()>
     where
-        A: ToSocketAddrs + Into<String> + Clone,
+        A: ToSocketAddrs,
     {
         // Stays consistent with memory layout
         let pad = (4 - (event.len() % 4)) % 4;
         let padded_event_len = event.len() + pad;
         let total_len = Self::PACKET_PADDING + padded_event_len + buf.len();
         let delivery_type = delivery.packet_delivery_as()?;
+        // Resolved once up front, so a target given as a host name is still sent to
+        let socket_addr = addr
+            .to_socket_addrs()?
+            .next()
+            .ok_or(anyhow!("Could not resolve the address to send to"))?;
 
         let mut packet = vec![0; total_len];
         // Inserts the packet delivery type into the packet
@@ -209,11 +286,9 @@ where
         );
 
         if delivery.is_sequenced() {
-            let addr = Into::<String>::into(addr.clone());
-            let addr = SocketAddr::from_str(&addr).unwrap();
             let seq_num = self
                 .inner
-                .update_current_send_seq_num_for_event(&addr, event);
+                .update_current_send_seq_num_for_event(&socket_addr, event);
 
             if let Some(seq_num) = seq_num {
                 LittleEndian::write_u32(
@@ -237,6 +312,13 @@ where
             ack_number.raw(),
         );
 
+        // Inserts the connection token so the receiver can recognise us if our address changes
+        LittleEndian::write_u64(
+            &mut packet[Self::CONNECTION_TOKEN_OFFSET
+                ..Self::CONNECTION_TOKEN_OFFSET + Self::CONNECTION_TOKEN_BUF],
+            self.inner.connection_token(&socket_addr),
+        );
+
         // Inserts the length of the event string into the packet
         LittleEndian::write_u32(
             &mut packet[Self::EVENT_LEN_OFFSET..Self::EVENT_LEN_OFFSET + Self::EVENT_LEN_BUF],
@@ -256,11 +338,18 @@ where
             self.ack_manager.insert_packet_into_ack_waiting_list(
                 ack_number,
                 packet.to_vec(),
-                addr.clone(),
+                socket_addr.to_string(),
             );
         }
 
-        self.socket.send_to(&packet, addr)?;
+        // A reliable packet we may not send yet is still retried later
+        if !self.inner.may_send(&socket_addr, packet.len()) {
+            return Err(anyhow!(
+                "Packet to unverified address {socket_addr} was held back"
+            ));
+        }
+
+        self.socket.send_to(&packet, socket_addr)?;
 
         Ok(())
     }
@@ -270,14 +359,17 @@ where
     /// # Examples
     ///
     /// ```
+    /// # use nautilus_sockets::prelude::*;
+    /// # let mut client = NautSocket::<NautClient>::new("127.0.0.1:0")?;
     /// // When the client recieves a "hello" event it will print the bytes received
-    /// client.on("hello", |_client, (_addr, packet)| {
-    ///     println!("hello bytes {:?}", packet);
+    /// client.on("hello", |_client, ctx| {
+    ///     println!("hello bytes {:?}", ctx.payload);
     /// });
+    /// # Ok::<(), anyhow::Error>(())
     /// ```
     pub fn on<F>(&mut self, event: &str, cb: F)
     where
-        F: Fn(&mut NautSocket<S>, EventCallbackArgs) + Send + Sync + 'static,
+        F: Fn(&mut NautSocket<'socket, S>, EventContext) + Send + Sync + 'static,
     {
         self.event_emitter.register_event(event, cb);
     }
@@ -287,11 +379,14 @@ where
     /// # Examples
     ///
     /// ```
+    /// # use nautilus_sockets::prelude::*;
+    /// # let mut server = NautSocket::<NautServer>::new("127.0.0.1:0", ServerConfig::default())?;
     /// // When the server is polled it will print "Do some stuff"]
     /// // Can be used to read server events, etc.
     /// server.on_poll(|_server| {
     ///     println!("Do some stufff");
     /// });
+    /// # Ok::<(), anyhow::Error>(())
     /// ```
     pub fn on_poll<F>(&mut self, cb: F)
     where
@@ -300,11 +395,34 @@ where
         self.event_emitter.register_poll_event(cb);
     }
 
-    /// Sends an [acknowledgement packet](AckPacket) to the [address](SocketAddr)
-    pub(crate) fn send_ack_packet<A>(&self, addr: A, packet: &[u8]) -> anyhow::Result<()>
+    /// Run a function on every received packet before it is parsed, returning true consumes the
+    /// packet so it is not processed any further. Allows other protocols to share the socket
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use nautilus_sockets::prelude::*;
+    /// # let mut server = NautSocket::<NautServer>::new("127.0.0.1:0", ServerConfig::default())?;
+    /// // Consumes any packet that starts with a magic number
+    /// server.on_raw_packet(|_server, addr, packet| {
+    ///     if !packet.starts_with(&[0xFF, 0xFF, 0xFF, 0xFF]) {
+    ///         return false;
+    ///     }
+    ///
+    ///     println!("magic packet from {addr}");
+    ///     true
+    /// });
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn on_raw_packet<F>(&mut self, cb: F)
     where
-        A: ToSocketAddrs,
+        F: Fn(&mut NautSocket<S>, SocketAddr, &[u8]) -> bool + Send + Sync + 'static,
     {
+        self.event_emitter.register_raw_packet_event(cb);
+    }
+
+    /// Sends an [acknowledgement packet](AckPacket) to the [address](SocketAddr)
+    pub(crate) fn send_ack_packet(&mut self, addr: SocketAddr, packet: &[u8]) -> anyhow::Result<()> {
         let mut buf = vec![0; 6];
 
         // Write that its a ack response to the packet
@@ -322,6 +440,10 @@ where
         // Write ack num into ack delivery packet
         LittleEndian::write_u32(&mut buf[2..6], ack_num);
 
+        if !self.inner.may_send(&addr, buf.len()) {
+            return Err(anyhow!("Ack to unverified address {addr} was held back"));
+        }
+
         self.socket.send_to(&buf, addr)?;
 
         Ok(())
@@ -346,11 +468,165 @@ where
         self.persistent.insert_persistent(persistent);
     }
 
+    /// Inserts the default of the [persistent](Persistent) type, or restores it from the
+    /// [snapshot](Self::enable_snapshots) if it was saved. A persistent that fails to restore is
+    /// still inserted as its default, with a [snapshot fail](SocketEvent::SnapshotFail) event
     pub fn init_persistent<P>(&mut self)
     where
         P: Persistent + Default,
     {
-        self.persistent.init_persistent::<P>();
+        if let Err(e) = self.persistent.init_persistent::<P>() {
+            self.socket_events.push(SocketEvent::SnapshotFail(e.to_string()));
+        }
+    }
+
+    /// Removes the [persistent](Persistent) of the type, returning it if there was one
+    pub fn remove_persistent<P>(&mut self) -> Option<Arc<RwLock<P>>>
+    where
+        P: Persistent,
+    {
+        self.persistent.remove_persistent()
+    }
+
+    /// Checks if there is a [persistent](Persistent) of the type
+    pub fn contains_persistent<P>(&self) -> bool
+    where
+        P: Persistent,
+    {
+        self.persistent.contains_persistent::<P>()
+    }
+
+    /// Gets the [persistent](Persistent) of the type, [initialising](Self::init_persistent) it
+    /// first if there is none
+    pub fn get_or_init_persistent<P>(&mut self) -> Arc<RwLock<P>>
+    where
+        P: Persistent + Default,
+    {
+        let (persistent, result) = self.persistent.get_or_init_persistent();
+        if let Err(e) = result {
+            self.socket_events.push(SocketEvent::SnapshotFail(e.to_string()));
+        }
+
+        persistent
+    }
+
+    /// Restores [persistents](Persistent) that opted into snapshots from the file as they are
+    /// initialised, and saves them back to it periodically and when the socket is dropped. Must be
+    /// enabled before the persistents are initialised, such as before registering plugins
+    pub fn enable_snapshots(&mut self, config: SnapshotConfig) -> anyhow::Result<()> {
+        self.persistent.enable_snapshots(config)
+    }
+
+    /// Saves every [persistent](Persistent) that opted into snapshots to the snapshot file now
+    pub fn save_snapshot(&mut self) -> anyhow::Result<()> {
+        self.persistent.save_snapshot()
+    }
+
+    /// Saves the snapshot if its [interval](SnapshotConfig::interval) has passed
+    pub(crate) fn save_snapshot_if_due(&mut self) {
+        if let Err(e) = self.persistent.save_snapshot_if_due() {
+            self.socket_events.push(SocketEvent::SnapshotFail(e.to_string()));
+        }
+    }
+
+    /// Runs a function with a mutable reference to the [persistent](Persistent) of the type,
+    /// returning its result or none if there is no persistent of the type. A lock poisoned by an
+    /// earlier panic is recovered rather than failing. The persistent is marked as changed, use
+    /// [with persistent ref](Self::with_persistent_ref) to only read it or
+    /// [update persistent](Self::update_persistent) to report whether it changed
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// socket.with_persistent(|score: &mut Score| score.points += 1);
+    /// ```
+    pub fn with_persistent<P, F, R>(&self, f: F) -> Option<R>
+    where
+        P: Persistent,
+        F: FnOnce(&mut P) -> R,
+    {
+        self.persistent.with_persistent(f)
+    }
+
+    /// Runs a function with a mutable reference to the [persistent](Persistent) of the type,
+    /// which returns whether it changed the persistent. Only a reported change is seen by
+    /// [changed callbacks](Self::on_persistent_changed) and the
+    /// [change tick](Self::persistent_change_tick). Returns the report, or none if there is no
+    /// persistent of the type
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// // Only a new high score counts as a change
+    /// socket.update_persistent(|score: &mut Score| {
+    ///     let is_high_score = points > score.high_score;
+    ///     score.high_score = score.high_score.max(points);
+    ///     is_high_score
+    /// });
+    /// ```
+    pub fn update_persistent<P, F>(&self, f: F) -> Option<bool>
+    where
+        P: Persistent,
+        F: FnOnce(&mut P) -> bool,
+    {
+        self.persistent.update_persistent(f)
+    }
+
+    /// Marks the [persistent](Persistent) of the type as changed, needed after writing to it
+    /// through the lock of [get persistent](Self::get_persistent) as only writes through the socket
+    /// are tracked
+    pub fn mark_persistent_changed<P>(&self)
+    where
+        P: Persistent,
+    {
+        self.persistent.mark_changed(TypeId::of::<P>());
+    }
+
+    /// Gets the tick the [persistent](Persistent) of the type was last changed on, which grows
+    /// with every change to any persistent. Comparing it to a previously read tick tells if the
+    /// persistent has changed since
+    pub fn persistent_change_tick<P>(&self) -> Option<u64>
+    where
+        P: Persistent,
+    {
+        self.persistent.change_tick(TypeId::of::<P>())
+    }
+
+    /// Run a function as a callback when the [persistent](Persistent) of the type changes. It is
+    /// checked once per run of the events after the polled callbacks, so many changes
+    /// in between only run it once
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// // Only broadcasts the scores when they have actually changed
+    /// server.on_persistent_changed::<Scores, _>(|server| {
+    ///     let Some(bytes) = server.with_persistent_ref(|scores: &Scores| scores.to_bytes()) else {
+    ///         return;
+    ///     };
+    ///
+    ///     server.broadcast("scores", &bytes, PacketDelivery::Reliable);
+    /// });
+    /// ```
+    pub fn on_persistent_changed<P, F>(&mut self, cb: F)
+    where
+        P: Persistent,
+        F: Fn(&mut NautSocket<S>) + Send + Sync + 'static,
+    {
+        let type_id = TypeId::of::<P>();
+        let tick = self.persistent.change_tick(type_id).unwrap_or_default();
+        self.event_emitter
+            .register_persistent_changed_event(type_id, tick, cb);
+    }
+
+    /// Runs a function with a reference to the [persistent](Persistent) of the type, see
+    /// [with persistent](Self::with_persistent)
+    pub fn with_persistent_ref<P, F, R>(&self, f: F) -> Option<R>
+    where
+        P: Persistent,
+        F: FnOnce(&P) -> R,
+    {
+        self.persistent.with_persistent_ref(f)
     }
 }
 
@@ -373,4 +649,13 @@ pub trait SocketType<'socket>: Default {
         addr: &SocketAddr,
         event: &str,
     ) -> Option<&'socket mut SequenceNumber>;
+
+    /// Gets the [connection token](ConnectionToken) that is written into packets sent to the
+    /// address, 0 if there is no token for that address
+    fn connection_token(&self, addr: &SocketAddr) -> ConnectionToken;
+
+    /// Checks if a packet of the length may be sent to the address, used to limit what is sent
+    /// to addresses that have not proven they can receive our packets. A packet that may be
+    /// sent is counted against whatever limits the address
+    fn may_send(&mut self, addr: &SocketAddr, len: usize) -> bool;
 }
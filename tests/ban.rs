@@ -0,0 +1,72 @@
+use std::{
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+use nautilus_sockets::prelude::*;
+
+/// Runs the server and client until the condition holds, failing if it never does
+fn run_until<F>(
+    server: &mut NautSocket<NautServer>,
+    client: &mut NautSocket<NautClient>,
+    mut condition: F,
+) where
+    F: FnMut(&NautSocket<NautServer>, &NautSocket<NautClient>) -> bool,
+{
+    let started = Instant::now();
+    while !condition(server, client) {
+        assert!(
+            started.elapsed() < Duration::from_secs(5),
+            "Condition was never met"
+        );
+
+        server.poll();
+        server.run_events();
+        client.poll();
+        client.run_events();
+
+        sleep(Duration::from_millis(2));
+    }
+}
+
+#[test]
+fn banning_a_range_disconnects_clients_within_it() {
+    let mut server = NautSocket::<NautServer>::new("127.0.0.1:0", ServerConfig::default()).unwrap();
+    let mut client = NautSocket::<NautClient>::new("127.0.0.1:0").unwrap();
+
+    let banned = Arc::new(AtomicBool::new(false));
+    let client_banned = Arc::clone(&banned);
+    client.on_poll(move |client| {
+        if client.client().iter_client_events().any(|event| {
+            matches!(
+                event,
+                ClientEvent::OnConnectionRejected(RejectionReason::Banned)
+            )
+        }) {
+            client_banned.store(true, Ordering::Relaxed);
+        }
+    });
+    client
+        .connect_to(server.socket().local_addr().unwrap().to_string())
+        .unwrap();
+    client.send("hello", &[], PacketDelivery::Reliable).unwrap();
+
+    run_until(&mut server, &mut client, |server, _| {
+        server.server().get_current_connections() == 1
+    });
+
+    server.server_mut().ban_list_mut().ban(
+        IpRange::from_str("127.0.0.0/8").unwrap(),
+        None,
+        "loopback",
+    );
+
+    run_until(&mut server, &mut client, |server, _| {
+        server.server().get_current_connections() == 0 && banned.load(Ordering::Relaxed)
+    });
+}
@@ -0,0 +1,223 @@
+use nautilus_sockets::packet::{BitReader, BitWriter};
+
+/// Deterministic floats between -1 and 1, so failures can be reproduced
+fn floats(count: usize) -> Vec<f32> {
+    let mut state = 0x2545_f491_4f6c_dd1du64;
+    (0..count)
+        .map(|_| {
+            state = state
+                .wrapping_mul(6_364_136_223_846_793_005)
+                .wrapping_add(1_442_695_040_888_963_407);
+            (state >> 40) as f32 / (1u64 << 23) as f32 - 1.0
+        })
+        .collect()
+}
+
+/// Unit quaternions pointing every which way
+fn quaternions(count: usize) -> Vec<[f32; 4]> {
+    let floats = floats(count * 4);
+    floats
+        .chunks(4)
+        .filter_map(|q| {
+            let len = q.iter().map(|c| c * c).sum::<f32>().sqrt();
+            (len > 0.01).then(|| [q[0] / len, q[1] / len, q[2] / len, q[3] / len])
+        })
+        .collect()
+}
+
+fn dot(a: [f32; 4], b: [f32; 4]) -> f32 {
+    a.iter().zip(b).map(|(a, b)| a * b).sum()
+}
+
+#[test]
+fn bits_round_trip_across_byte_boundaries() {
+    let mut writer = BitWriter::new();
+    writer
+        .write_bits(0b101, 3)
+        .write_bits(0x1FF, 9)
+        .write_bits(u64::MAX, 64)
+        .write_bits(0, 0)
+        .write_bool(true);
+    assert_eq!(writer.bit_len(), 77);
+    assert_eq!(writer.len(), 10);
+
+    let packet = writer.into_inner();
+    let mut reader = BitReader::new(&packet);
+    assert_eq!(reader.read_bits(3).unwrap(), 0b101);
+    assert_eq!(reader.read_bits(9).unwrap(), 0x1FF);
+    assert_eq!(reader.read_bits(64).unwrap(), u64::MAX);
+    assert!(reader.read_bool().unwrap());
+
+    // Only the zeroed padding of the last byte is left
+    assert_eq!(reader.remaining_bits(), 3);
+    assert_eq!(reader.read_bits(3).unwrap(), 0);
+    assert!(reader.read_bits(1).is_err());
+}
+
+#[test]
+fn bits_fill_each_byte_from_the_lowest_bit() {
+    let mut writer = BitWriter::new();
+    writer
+        .write_bool(true)
+        .write_bits(0b11, 2)
+        .write_bits(0b1, 6);
+    assert_eq!(writer.as_slice(), [0b0000_1111, 0b0000_0000]);
+}
+
+#[test]
+fn ranged_integers_round_trip_and_clamp() {
+    assert_eq!(BitWriter::bits_for_range(-8, 8), 5);
+    assert_eq!(BitWriter::bits_for_range(5, 5), 0);
+    assert_eq!(BitWriter::bits_for_range(i64::MIN, i64::MAX), 64);
+
+    let mut writer = BitWriter::new();
+    writer
+        .write_ranged(-8, -8, 8)
+        .write_ranged(8, -8, 8)
+        .write_ranged(100, -8, 8)
+        .write_ranged(-100, -8, 8)
+        .write_ranged(i64::MIN, i64::MIN, i64::MAX)
+        .write_ranged(i64::MAX, i64::MIN, i64::MAX);
+
+    let packet = writer.into_inner();
+    let mut reader = BitReader::new(&packet);
+    assert_eq!(reader.read_ranged(-8, 8).unwrap(), -8);
+    assert_eq!(reader.read_ranged(-8, 8).unwrap(), 8);
+    assert_eq!(reader.read_ranged(-8, 8).unwrap(), 8);
+    assert_eq!(reader.read_ranged(-8, 8).unwrap(), -8);
+    assert_eq!(reader.read_ranged(i64::MIN, i64::MAX).unwrap(), i64::MIN);
+    assert_eq!(reader.read_ranged(i64::MIN, i64::MAX).unwrap(), i64::MAX);
+}
+
+#[test]
+fn ranged_integer_outside_its_range_is_rejected() {
+    // 0 to 4 takes 3 bits, which can hold up to 7
+    let mut writer = BitWriter::new();
+    writer.write_bits(7, 3);
+    assert!(BitReader::new(writer.as_slice()).read_ranged(0, 4).is_err());
+}
+
+#[test]
+fn quantized_floats_are_within_half_the_precision() {
+    let (min, max, precision) = (-100.0, 100.0, 0.01);
+    assert_eq!(BitWriter::bits_for_quantized(min, max, precision), 15);
+
+    let values: Vec<f32> = floats(1000).into_iter().map(|x| x * 100.0).collect();
+    let mut writer = BitWriter::new();
+    for value in values.iter() {
+        writer.write_quantized(*value, min, max, precision);
+    }
+    assert_eq!(writer.bit_len(), 15 * values.len());
+
+    let packet = writer.into_inner();
+    let mut reader = BitReader::new(&packet);
+    for value in values {
+        let read = reader.read_quantized(min, max, precision).unwrap();
+        assert!(
+            (read - value).abs() <= precision / 2.0 + 1e-4,
+            "{value} was read as {read}"
+        );
+    }
+}
+
+#[test]
+fn quantized_floats_keep_their_bounds_and_clamp() {
+    let mut writer = BitWriter::new();
+    writer
+        .write_quantized(-1.0, -1.0, 1.0, 0.1)
+        .write_quantized(1.0, -1.0, 1.0, 0.1)
+        .write_quantized(5.0, -1.0, 1.0, 0.1)
+        .write_quantized(f32::NEG_INFINITY, -1.0, 1.0, 0.1);
+
+    let packet = writer.into_inner();
+    let mut reader = BitReader::new(&packet);
+    assert_eq!(reader.read_quantized(-1.0, 1.0, 0.1).unwrap(), -1.0);
+    assert_eq!(reader.read_quantized(-1.0, 1.0, 0.1).unwrap(), 1.0);
+    assert_eq!(reader.read_quantized(-1.0, 1.0, 0.1).unwrap(), 1.0);
+    assert_eq!(reader.read_quantized(-1.0, 1.0, 0.1).unwrap(), -1.0);
+}
+
+#[test]
+fn quantized_step_outside_the_range_is_rejected() {
+    // 20 steps take 5 bits, which can hold up to 31
+    let mut writer = BitWriter::new();
+    writer.write_bits(31, 5);
+    assert!(BitReader::new(writer.as_slice())
+        .read_quantized(-1.0, 1.0, 0.1)
+        .is_err());
+}
+
+#[test]
+fn quaternions_round_trip_as_the_same_rotation() {
+    for bits in [9, 10, 15] {
+        assert_eq!(
+            BitWriter::measure(|writer| {
+                writer.write_quaternion([0.0, 0.0, 0.0, 1.0], bits);
+            }),
+            BitWriter::bits_for_quaternion(bits) as usize
+        );
+
+        // Each component is off by at most half a step
+        let step = std::f32::consts::SQRT_2 / ((1u32 << bits) - 1) as f32;
+        for quaternion in quaternions(500) {
+            let mut writer = BitWriter::new();
+            writer.write_quaternion(quaternion, bits);
+            let read = BitReader::new(writer.as_slice())
+                .read_quaternion(bits)
+                .unwrap();
+
+            // The read quaternion may be the negation, which is the same rotation
+            let sign = dot(quaternion, read).signum();
+            for (original, read) in quaternion.iter().zip(read) {
+                assert!(
+                    (original * sign - read).abs() <= step * 2.0,
+                    "{quaternion:?} was read as {read:?} with {bits} bits"
+                );
+            }
+
+            let len = read.iter().map(|c| c * c).sum::<f32>().sqrt();
+            assert!((len - 1.0).abs() < 1e-3);
+        }
+    }
+}
+
+#[test]
+fn quaternion_and_its_negation_are_written_the_same() {
+    for quaternion in quaternions(50) {
+        let negated = quaternion.map(|component| -component);
+
+        let mut writer = BitWriter::new();
+        writer.write_quaternion(quaternion, 10);
+        let mut negated_writer = BitWriter::new();
+        negated_writer.write_quaternion(negated, 10);
+
+        assert_eq!(writer.as_slice(), negated_writer.as_slice());
+    }
+}
+
+#[test]
+fn identity_quaternion_stays_the_identity() {
+    let mut writer = BitWriter::new();
+    writer.write_quaternion([0.0, 0.0, 0.0, 1.0], 10);
+    let read = BitReader::new(writer.as_slice())
+        .read_quaternion(10)
+        .unwrap();
+
+    assert!((read[3] - 1.0).abs() < 1e-5);
+    assert!(read[..3].iter().all(|component| component.abs() < 1e-3));
+}
+
+#[test]
+fn short_packets_fail_without_panicking() {
+    let mut writer = BitWriter::new();
+    writer.write_quaternion([0.0, 0.0, 0.0, 1.0], 15);
+    let packet = writer.into_inner();
+
+    assert!(BitReader::new(&packet[..packet.len() - 1])
+        .read_quaternion(15)
+        .is_err());
+    assert!(BitReader::new(&[]).read_bool().is_err());
+    assert!(BitReader::new(&[0xFF])
+        .read_quantized(-100.0, 100.0, 0.01)
+        .is_err());
+}
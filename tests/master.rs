@@ -0,0 +1,180 @@
+use std::{
+    net::SocketAddr,
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+use nautilus_sockets::prelude::*;
+
+fn master(entry_lifetime: Duration) -> (NautMasterServer<'static>, SocketAddr) {
+    let master = NautMasterServer::new(
+        "127.0.0.1:0",
+        ServerConfig::default(),
+        MasterServerPlugin { entry_lifetime },
+    )
+    .unwrap();
+    let addr = master.socket().socket().local_addr().unwrap();
+
+    (master, addr)
+}
+
+fn game_server(
+    master_addr: SocketAddr,
+    name: &str,
+    heartbeat_interval: Duration,
+) -> NautSocket<'static, NautServer> {
+    let config = ServerConfig {
+        server_name: name.to_string(),
+        ..Default::default()
+    };
+
+    let mut server = NautSocket::<NautServer>::new("127.0.0.1:0", config).unwrap();
+    server.register_plugin(MasterServerHeartbeatPlugin {
+        heartbeat_interval,
+        ..MasterServerHeartbeatPlugin::new(
+            master_addr,
+            MasterServerRegistration {
+                region: String::from("eu"),
+                tags: vec![String::from("deathmatch")],
+            },
+        )
+    });
+
+    server
+}
+
+/// Runs every socket until the condition holds, failing if it never does
+fn run_until<F>(
+    master: &mut NautMasterServer,
+    servers: &mut [NautSocket<NautServer>],
+    mut condition: F,
+) where
+    F: FnMut(&NautMasterServer) -> bool,
+{
+    let started = Instant::now();
+    while !condition(master) {
+        assert!(
+            started.elapsed() < Duration::from_secs(5),
+            "Condition was never met"
+        );
+
+        master.run();
+        for server in servers.iter_mut() {
+            server.poll();
+            server.run_events();
+        }
+
+        sleep(Duration::from_millis(2));
+    }
+}
+
+#[test]
+fn game_server_registers_without_a_connection() {
+    let (mut master, master_addr) = master(Duration::from_secs(30));
+    let mut servers = [game_server(master_addr, "crab", Duration::from_secs(10))];
+
+    run_until(&mut master, &mut servers, |master| {
+        master.entries().len() == 1
+    });
+
+    let entry = &master.entries()[0];
+    assert_eq!(entry.addr, servers[0].socket().local_addr().unwrap());
+    assert_eq!(entry.name, "crab");
+    assert_eq!(entry.region, "eu");
+    assert_eq!(entry.tags, ["deathmatch"]);
+    assert_eq!(master.socket().server().get_current_connections(), 0);
+}
+
+#[test]
+fn heartbeats_keep_a_server_listed_until_they_stop() {
+    let entry_lifetime = Duration::from_millis(300);
+    let (mut master, master_addr) = master(entry_lifetime);
+    let mut servers = [game_server(master_addr, "crab", Duration::from_millis(50))];
+
+    run_until(&mut master, &mut servers, |master| {
+        master.entries().len() == 1
+    });
+
+    // Outlives the entry lifetime several times over while heartbeats are sent
+    let started = Instant::now();
+    run_until(&mut master, &mut servers, |master| {
+        assert_eq!(master.entries().len(), 1);
+        started.elapsed() > entry_lifetime * 3
+    });
+
+    // Without heartbeats the entry expires
+    run_until(&mut master, &mut [], |master| master.entries().is_empty());
+}
+
+#[test]
+fn unregistered_server_is_removed() {
+    let (mut master, master_addr) = master(Duration::from_secs(30));
+    let mut servers = [game_server(master_addr, "crab", Duration::from_secs(10))];
+
+    run_until(&mut master, &mut servers, |master| {
+        master.entries().len() == 1
+    });
+
+    servers[0]
+        .unregister_from_master_server(master_addr)
+        .unwrap();
+    run_until(&mut master, &mut servers, |master| {
+        master.entries().is_empty()
+    });
+}
+
+#[test]
+fn query_is_answered_in_pages() {
+    let (mut master, master_addr) = master(Duration::from_secs(30));
+
+    // Long names so the list cannot fit into a single packet
+    let mut servers: Vec<_> = (0..20)
+        .map(|i| game_server(master_addr, &format!("{i:0>64}"), Duration::from_secs(10)))
+        .collect();
+    run_until(&mut master, &mut servers, |master| {
+        master.entries().len() == 20
+    });
+
+    let mut client = NautSocket::<NautClient>::new("127.0.0.1:0").unwrap();
+    client.register_plugin(MasterServerBrowserPlugin);
+    client
+        .query_master_server(master_addr, &MasterServerFilter::default())
+        .unwrap();
+
+    run_until(&mut master, &mut servers, |_| {
+        client.poll();
+        client.run_events();
+
+        client
+            .with_persistent_ref(|list: &MasterServerList| list.is_complete())
+            .unwrap()
+    });
+
+    let (entries, page_count) = client
+        .with_persistent_ref(|list: &MasterServerList| (list.entries().to_vec(), list.page_count()))
+        .unwrap();
+    assert_eq!(entries.len(), 20);
+    assert!(page_count > 1);
+    assert!(entries.iter().all(|entry| entry.name.len() == 64));
+    assert_eq!(master.socket().server().get_current_connections(), 0);
+
+    // A filter nothing passes still completes with a single empty page
+    let filter = MasterServerFilter {
+        region: Some(String::from("us")),
+        ..Default::default()
+    };
+    client.query_master_server(master_addr, &filter).unwrap();
+    run_until(&mut master, &mut servers, |_| {
+        client.poll();
+        client.run_events();
+
+        client
+            .with_persistent_ref(|list: &MasterServerList| list.is_complete())
+            .unwrap()
+    });
+
+    let (entries, page_count) = client
+        .with_persistent_ref(|list: &MasterServerList| (list.entries().len(), list.page_count()))
+        .unwrap();
+    assert_eq!((entries, page_count), (0, 1));
+}
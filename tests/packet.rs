@@ -0,0 +1,178 @@
+use nautilus_sockets::packet::{NautReader, NautWriter};
+
+#[test]
+fn primitives_round_trip() {
+    let mut writer = NautWriter::new();
+    writer
+        .write_u8(u8::MAX)
+        .write_i8(i8::MIN)
+        .write_bool(true)
+        .write_bool(false)
+        .write_u16(0xBEEF)
+        .write_u32(u32::MAX - 1)
+        .write_u64(u64::MAX)
+        .write_i16(-2)
+        .write_i32(i32::MIN)
+        .write_i64(-1)
+        .write_f32(1.5)
+        .write_f64(-0.25);
+    assert_eq!(writer.len(), 1 + 1 + 2 + 2 + 4 + 8 + 2 + 4 + 8 + 4 + 8);
+
+    let packet = writer.into_inner();
+    let mut reader = NautReader::new(&packet);
+    assert_eq!(reader.read_u8().unwrap(), u8::MAX);
+    assert_eq!(reader.read_i8().unwrap(), i8::MIN);
+    assert!(reader.read_bool().unwrap());
+    assert!(!reader.read_bool().unwrap());
+    assert_eq!(reader.read_u16().unwrap(), 0xBEEF);
+    assert_eq!(reader.read_u32().unwrap(), u32::MAX - 1);
+    assert_eq!(reader.read_u64().unwrap(), u64::MAX);
+    assert_eq!(reader.read_i16().unwrap(), -2);
+    assert_eq!(reader.read_i32().unwrap(), i32::MIN);
+    assert_eq!(reader.read_i64().unwrap(), -1);
+    assert_eq!(reader.read_f32().unwrap(), 1.5);
+    assert_eq!(reader.read_f64().unwrap(), -0.25);
+    assert!(reader.is_empty());
+}
+
+#[test]
+fn numbers_are_little_endian() {
+    let mut writer = NautWriter::new();
+    writer.write_u32(0x0403_0201);
+    assert_eq!(writer.as_slice(), [1, 2, 3, 4]);
+}
+
+#[test]
+fn varints_round_trip_in_as_few_bytes_as_they_need() {
+    let values = [
+        (0, 1),
+        (127, 1),
+        (128, 2),
+        (16_383, 2),
+        (16_384, 3),
+        (u64::MAX, 10),
+    ];
+    for (value, len) in values {
+        let mut writer = NautWriter::new();
+        writer.write_varint(value);
+        assert_eq!(writer.len(), len, "{value} should take {len} bytes");
+        assert_eq!(
+            NautReader::new(writer.as_slice()).read_varint().unwrap(),
+            value
+        );
+    }
+
+    for value in [0, -1, 1, -64, 63, i64::MIN, i64::MAX] {
+        let mut writer = NautWriter::new();
+        writer.write_varint_signed(value);
+        assert_eq!(
+            NautReader::new(writer.as_slice())
+                .read_varint_signed()
+                .unwrap(),
+            value
+        );
+    }
+
+    // Zigzag keeps small negative numbers to a single byte
+    let mut writer = NautWriter::new();
+    writer.write_varint_signed(-64);
+    assert_eq!(writer.len(), 1);
+}
+
+#[test]
+fn malformed_varints_are_rejected() {
+    // Never ends
+    assert!(NautReader::new(&[0x80; 3]).read_varint().is_err());
+    // Longer than a u64 can hold
+    assert!(NautReader::new(&[0xFF; 11]).read_varint().is_err());
+    // The tenth byte has more than the top bit of a u64
+    let mut overflow = [0xFF; 10];
+    overflow[9] = 0x02;
+    assert!(NautReader::new(&overflow).read_varint().is_err());
+}
+
+#[test]
+fn lengths_prefix_strings_bytes_arrays_and_options() {
+    let mut writer = NautWriter::new();
+    writer
+        .write_str("nautilus")
+        .write_bytes(&[1, 2, 3])
+        .write_array(&[10u16, 20, 30], |writer, value| {
+            writer.write_u16(*value);
+        })
+        .write_option(Some(&7u32), |writer, value| {
+            writer.write_u32(*value);
+        })
+        .write_option(None::<&u32>, |writer, value| {
+            writer.write_u32(*value);
+        })
+        .write_raw(&[9, 9]);
+
+    let packet = writer.into_inner();
+    let mut reader = NautReader::new(&packet);
+    assert_eq!(reader.read_str().unwrap(), "nautilus");
+    assert_eq!(reader.read_bytes().unwrap(), [1, 2, 3]);
+    assert_eq!(
+        reader.read_array(|reader| reader.read_u16()).unwrap(),
+        [10, 20, 30]
+    );
+    assert_eq!(
+        reader.read_option(|reader| reader.read_u32()).unwrap(),
+        Some(7)
+    );
+    assert_eq!(
+        reader.read_option(|reader| reader.read_u32()).unwrap(),
+        None
+    );
+    assert_eq!(reader.remaining(), [9, 9]);
+    assert_eq!(reader.read_raw(2).unwrap(), [9, 9]);
+    assert!(reader.is_empty());
+}
+
+#[test]
+fn long_strings_bytes_and_arrays_are_written_whole() {
+    let string = "\u{e9}".repeat(u16::MAX as usize);
+    let bytes = vec![7; u16::MAX as usize + 1];
+    let values = vec![3u8; u16::MAX as usize + 1];
+
+    let mut writer = NautWriter::new();
+    writer
+        .write_str(&string)
+        .write_bytes(&bytes)
+        .write_array(&values, |writer, value| {
+            writer.write_u8(*value);
+        });
+
+    let packet = writer.into_inner();
+    let mut reader = NautReader::new(&packet);
+    assert_eq!(reader.read_str().unwrap(), string);
+    assert_eq!(reader.read_bytes().unwrap(), bytes);
+    assert_eq!(
+        reader.read_array(|reader| reader.read_u8()).unwrap(),
+        values
+    );
+    assert!(reader.is_empty());
+}
+
+#[test]
+fn short_or_malformed_packets_fail_without_panicking() {
+    assert!(NautReader::new(&[1, 2, 3]).read_u32().is_err());
+    assert!(NautReader::new(&[2]).read_bool().is_err());
+    assert!(NautReader::new(&[5, b'a']).read_str().is_err());
+    assert!(NautReader::new(&[2, 0xFF, 0xFE]).read_str().is_err());
+    assert!(NautReader::new(&[0xFF; 11]).read_bytes().is_err());
+    assert!(NautReader::new(&[])
+        .read_option(|reader| reader.read_u8())
+        .is_err());
+
+    // An array claiming more values than the packet has bytes left fails before reading any
+    let mut reader = NautReader::new(&[0xFF, 0xFF, 0x03, 1, 2]);
+    assert!(reader
+        .read_array(|_| -> anyhow::Result<u8> { panic!("Read a value of a bad array") })
+        .is_err());
+
+    // A failed read leaves the reader where it was
+    let mut reader = NautReader::new(&[1, 2, 3]);
+    assert!(reader.read_u32().is_err());
+    assert_eq!(reader.read_u16().unwrap(), 0x0201);
+}
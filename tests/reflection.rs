@@ -0,0 +1,120 @@
+use std::{
+    net::UdpSocket,
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+use nautilus_sockets::prelude::*;
+
+fn server(require_challenge: bool) -> NautSocket<'static, NautServer> {
+    let config = ServerConfig {
+        reflection: ReflectionConfig {
+            require_challenge,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    NautSocket::<NautServer>::new("127.0.0.1:0", config).unwrap()
+}
+
+/// A reliable "hello" packet with no connection token, as the first packet of a new connection
+fn connection_request() -> Vec<u8> {
+    let mut packet = NautWriter::new();
+    packet
+        // Reliable delivery, no sequence number and the first ack number
+        .write_u16(2)
+        .write_u32(0)
+        .write_u32(1)
+        .write_u64(0)
+        // The event, padded to a multiple of 4 bytes
+        .write_u32(5)
+        .write_raw(b"hello\0\0\0");
+
+    packet.into_inner()
+}
+
+/// Sends a connection request from the victim, as if spoofed, and runs the server past the time
+/// it would resend anything, returning every packet the victim received
+fn spoofed_request(server: &mut NautSocket<NautServer>, request: &[u8]) -> Vec<Vec<u8>> {
+    let victim = UdpSocket::bind("127.0.0.1:0").unwrap();
+    victim.set_nonblocking(true).unwrap();
+    victim
+        .send_to(request, server.socket().local_addr().unwrap())
+        .unwrap();
+
+    let started = Instant::now();
+    while started.elapsed() < Duration::from_millis(2500) {
+        server.poll();
+        server.run_events();
+        sleep(Duration::from_millis(10));
+    }
+
+    let mut received = Vec::new();
+    let mut buf = [0; 1024];
+    while let Ok(size) = victim.recv(&mut buf) {
+        received.push(buf[..size].to_vec());
+    }
+
+    received
+}
+
+#[test]
+fn spoofed_request_is_only_challenged() {
+    let mut server = server(true);
+    let request = connection_request();
+
+    let received = spoofed_request(&mut server, &request);
+    assert_eq!(received.len(), 1);
+    assert!(received[0].len() <= request.len());
+    assert_eq!(server.server().get_current_connections(), 0);
+}
+
+#[test]
+fn spoofed_request_is_never_sent_more_than_it_sent() {
+    let mut server = server(false);
+    let request = connection_request();
+
+    // The handshake does not fit in what is left after the acknowledgement, so it is held back
+    // rather than sent or resent
+    let received = spoofed_request(&mut server, &request);
+    assert!(received.iter().map(Vec::len).sum::<usize>() <= request.len());
+    assert_eq!(server.server().get_current_connections(), 1);
+}
+
+#[test]
+fn unchallenged_client_is_verified_once_it_returns_its_token() {
+    let mut server = server(false);
+    let mut client = NautSocket::<NautClient>::new("127.0.0.1:0").unwrap();
+    client
+        .connect_to(server.socket().local_addr().unwrap().to_string())
+        .unwrap();
+
+    // Large enough to carry the acknowledgement and handshake back
+    let started = Instant::now();
+    loop {
+        assert!(
+            started.elapsed() < Duration::from_secs(5),
+            "Client was never verified"
+        );
+
+        client
+            .send("hello", &[0; 64], PacketDelivery::Unreliable)
+            .unwrap();
+        server.poll();
+        server.run_events();
+        client.poll();
+        client.run_events();
+
+        if server
+            .server()
+            .clients()
+            .iter()
+            .any(|id| server.server().is_client_verified(id))
+        {
+            break;
+        }
+
+        sleep(Duration::from_millis(2));
+    }
+}
@@ -0,0 +1,214 @@
+use std::{
+    net::UdpSocket,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+use anyhow::anyhow;
+use nautilus_sockets::prelude::*;
+
+/// The responses a requester has received, in the order they arrived
+type Responses = Arc<Mutex<Vec<Result<Vec<u8>, String>>>>;
+
+fn connected_pair() -> (
+    NautSocket<'static, NautServer>,
+    NautSocket<'static, NautClient>,
+) {
+    let server = NautSocket::<NautServer>::new("127.0.0.1:0", ServerConfig::default()).unwrap();
+    let mut client = NautSocket::<NautClient>::new("127.0.0.1:0").unwrap();
+    client
+        .connect_to(server.socket().local_addr().unwrap().to_string())
+        .unwrap();
+
+    (server, client)
+}
+
+/// Requests the method from the server, recording the response
+fn request(client: &mut NautSocket<NautClient>, method: &str, timeout: Duration) -> Responses {
+    let responses = Responses::default();
+    let recorded = Arc::clone(&responses);
+    client
+        .request(method, b"ping", timeout, move |_client, response| {
+            recorded
+                .lock()
+                .unwrap()
+                .push(response.map_err(|e| e.to_string()));
+        })
+        .unwrap();
+
+    responses
+}
+
+/// Runs the sockets until the condition holds, failing if it never does
+fn run_until<F>(
+    server: &mut NautSocket<NautServer>,
+    client: &mut NautSocket<NautClient>,
+    mut condition: F,
+) where
+    F: FnMut() -> bool,
+{
+    let started = Instant::now();
+    while !condition() {
+        assert!(
+            started.elapsed() < Duration::from_secs(5),
+            "Condition was never met"
+        );
+
+        server.poll();
+        server.run_events();
+        client.poll();
+        client.run_events();
+
+        sleep(Duration::from_millis(2));
+    }
+}
+
+#[test]
+fn request_is_answered_by_its_handler() {
+    let (mut server, mut client) = connected_pair();
+    server.on_request("echo", |_server, ctx| Ok(ctx.payload.to_vec()));
+    server.on_request("fail", |_server, _ctx| Err(anyhow!("out of stock")));
+
+    let echo = request(&mut client, "echo", Duration::from_secs(5));
+    let fail = request(&mut client, "fail", Duration::from_secs(5));
+    let missing = request(&mut client, "missing", Duration::from_secs(5));
+    run_until(&mut server, &mut client, || {
+        [&echo, &fail, &missing]
+            .iter()
+            .all(|responses| !responses.lock().unwrap().is_empty())
+    });
+
+    assert_eq!(*echo.lock().unwrap(), [Ok(b"ping".to_vec())]);
+
+    let fail = fail.lock().unwrap();
+    assert!(fail[0].as_ref().is_err_and(|e| e.contains("out of stock")));
+
+    let missing = missing.lock().unwrap();
+    assert!(missing[0]
+        .as_ref()
+        .is_err_and(|e| e.contains("no handler for request missing")));
+}
+
+#[test]
+fn unanswered_request_times_out() {
+    // Bound so the request is received, but never read so it is never answered
+    let silent = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let mut client = NautSocket::<NautClient>::new("127.0.0.1:0").unwrap();
+    client
+        .connect_to(silent.local_addr().unwrap().to_string())
+        .unwrap();
+
+    let responses = request(&mut client, "echo", Duration::from_millis(50));
+    let started = Instant::now();
+    while responses.lock().unwrap().is_empty() {
+        assert!(
+            started.elapsed() < Duration::from_secs(5),
+            "Never timed out"
+        );
+        client.poll();
+        client.run_events();
+        sleep(Duration::from_millis(2));
+    }
+
+    assert!(started.elapsed() >= Duration::from_millis(50));
+    let responses = responses.lock().unwrap();
+    assert_eq!(responses.len(), 1);
+    assert!(responses[0]
+        .as_ref()
+        .is_err_and(|e| e.contains("timed out")));
+}
+
+#[test]
+fn response_from_another_address_is_ignored() {
+    let (mut server, mut client) = connected_pair();
+    server.on_request("echo", |_server, ctx| Ok(ctx.payload.to_vec()));
+
+    let responses = request(&mut client, "echo", Duration::from_secs(5));
+
+    // Answers the first request the client makes before the server gets the chance to
+    let mut forger = NautSocket::<NautClient>::new("127.0.0.1:0").unwrap();
+    forger
+        .connect_to(client.socket().local_addr().unwrap().to_string())
+        .unwrap();
+    let mut forged = NautWriter::new();
+    forged.write_u32(1).write_bool(true).write_raw(b"forged");
+    forger
+        .send(
+            RPC_RESPONSE_EVENT,
+            forged.as_slice(),
+            PacketDelivery::Unreliable,
+        )
+        .unwrap();
+
+    sleep(Duration::from_millis(20));
+    client.poll();
+    client.run_events();
+    assert!(responses.lock().unwrap().is_empty());
+
+    run_until(&mut server, &mut client, || {
+        !responses.lock().unwrap().is_empty()
+    });
+    assert_eq!(*responses.lock().unwrap(), [Ok(b"ping".to_vec())]);
+}
+
+#[test]
+fn resent_request_is_not_handled_again() {
+    let (mut server, mut client) = connected_pair();
+
+    let handled = Arc::new(AtomicUsize::new(0));
+    let counter = Arc::clone(&handled);
+    server.on_request("count", move |_server, _ctx| {
+        let count = counter.fetch_add(1, Ordering::Relaxed) + 1;
+        Ok(count.to_le_bytes().to_vec())
+    });
+
+    let responses = Arc::new(Mutex::new(Vec::new()));
+    let received = Arc::clone(&responses);
+    client.on(RPC_RESPONSE_EVENT, move |_client, ctx| {
+        received.lock().unwrap().push(ctx.payload.to_vec());
+    });
+
+    // The same request sent twice is what the server sees when its acknowledgement is lost
+    let mut request = NautWriter::new();
+    request.write_u32(7).write_str("count").write_raw(b"a");
+    for _ in 0..2 {
+        client
+            .send(
+                RPC_REQUEST_EVENT,
+                request.as_slice(),
+                PacketDelivery::Reliable,
+            )
+            .unwrap();
+    }
+
+    run_until(&mut server, &mut client, || {
+        responses.lock().unwrap().len() == 2
+    });
+    assert_eq!(handled.load(Ordering::Relaxed), 1);
+
+    // Both are answered with the same response
+    {
+        let responses = responses.lock().unwrap();
+        assert_eq!(responses[0], responses[1]);
+    }
+
+    // A different request reusing the id is handled as a new one
+    let mut request = NautWriter::new();
+    request.write_u32(7).write_str("count").write_raw(b"b");
+    client
+        .send(
+            RPC_REQUEST_EVENT,
+            request.as_slice(),
+            PacketDelivery::Reliable,
+        )
+        .unwrap();
+
+    run_until(&mut server, &mut client, || {
+        responses.lock().unwrap().len() == 3
+    });
+    assert_eq!(handled.load(Ordering::Relaxed), 2);
+}